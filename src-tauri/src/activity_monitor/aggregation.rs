@@ -1,199 +1,1067 @@
-use chrono::{DateTime, Local, NaiveDate, TimeZone};
-use std::collections::HashMap;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Offset, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
-#[cfg(target_os = "windows")]
-use std::path::Path;
-
-#[cfg(target_os = "macos")]
-use dirs;
-#[cfg(target_os = "macos")]
-use std::io;
-
-use crate::utils::encryption::{decrypt_string, KEY};
+use crate::utils::key_manager::{active_keyring, KeyRing};
+use super::calendar::days_off;
+use super::devices::{registered_devices, source_device, DeviceList};
+use super::file_operations::get_platform_directories;
+use super::foreground::ForegroundApp;
+use super::importer::{imported_file_path, imported_records};
+use super::lifecycle::{classify_gap, lifecycle_time, RunEnding};
+use super::recorded_zone::RecordedZone;
+use super::records::{read_log_file, ActivityRecord};
+use super::settings::load_activity_settings;
+use super::tags::{resolve_overlaps, tags_in_range};
+use super::types::{
+    ActivityAggregate, ActivityHeatmap, ActivityTotals, ApplicationUsage, DayActivity, DeviceActivity, IntervalKind,
+    TagTotal, TimelineInterval, WindowTitleUsage,
+};
 
 pub fn aggregate_log_results(file_name: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let log_dir;
-    #[cfg(target_os = "macos")]
-    {
-        let home_dir = dirs::home_dir()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find home directory"))?;
-        log_dir = home_dir.join("Documents").join("rs-fairsight");
-    }
-    #[cfg(target_os = "windows")]
-    {
-        log_dir = Path::new("C:\\fairsight-log").to_path_buf();
-    }
+    let (log_dir, _) = get_platform_directories()?;
+    aggregate_log_results_in(file_name, &recorded_zone(&log_dir))
+}
 
-    let file_path = log_dir.join(&file_name);
+/// Timeline of one daily log with the day and its clock times taken in `tz`
+pub fn aggregate_log_results_in<Tz: TimeZone>(file_name: &str, tz: &Tz) -> Result<String, Box<dyn std::error::Error>> {
+    let (log_dir, _) = get_platform_directories()?;
 
     if !log_dir.exists() {
         println!("No log directory found");
         return Ok("No log files found".to_string());
     }
 
-    if !file_path.exists() {
+    let target_date = parse_log_file_date(file_name)?;
+    let (day_start, day_end) = day_bounds(target_date, tz);
+
+    let records = read_device_records_for_day(&log_dir, target_date, day_start, day_end)?;
+    if records.is_empty() && !log_dir.join(file_name).exists() {
         return Ok(format!("No log file found for {}", file_name));
     }
 
+    let (mut timeline, _) = device_timeline(records, day_start, day_end);
+    if load_activity_settings().show_days_off && !days_off(&log_dir, target_date, target_date)?.is_empty() {
+        mark_day_off(&mut timeline);
+    }
+    Ok(format_timeline(&timeline, tz))
+}
+
+/// Recorded stretches over an inclusive date range in `tz`, leaving out the time the app was
+/// not running. Stretches cut only by midnight are joined.
+pub fn timeline_range<Tz: TimeZone>(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    tz: &Tz
+) -> Result<Vec<TimelineInterval>, Box<dyn std::error::Error>> {
+    let aggregate = aggregate_activity(start_date, end_date, tz, false)?;
+    Ok(aggregate
+        .intervals
+        .into_iter()
+        .filter(|i| !i.kind.is_not_running())
+        .collect())
+}
+
+/// Timeline and totals over an inclusive date range in `tz`, per day and overall. Stretches cut
+/// only by midnight are joined. With `include_text` each day also carries its text timeline.
+/// Holidays and leave are labelled and left out of the average.
+pub fn aggregate_activity<Tz: TimeZone>(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    tz: &Tz,
+    include_text: bool
+) -> Result<ActivityAggregate, Box<dyn std::error::Error>> {
+    let (log_dir, _) = get_platform_directories()?;
+    let show_days_off = load_activity_settings().show_days_off;
+    let mut calendar = days_off(&log_dir, start_date, end_date)?;
+    let mut intervals: Vec<TimelineInterval> = Vec::new();
+    let mut days = Vec::new();
+    // Looked up once a day turns out to have activity merged from another device
+    let mut device_list: Option<DeviceList> = None;
+    let mut device_totals = DeviceActiveSeconds::new();
+
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        let (day_start, day_end) = day_bounds(date, tz);
+        let (mut timeline, device_active) = read_day_activity(&log_dir, date, day_start, day_end)?;
+        let day_off = calendar.remove(&date).unwrap_or_default();
+        if show_days_off && !day_off.is_empty() {
+            mark_day_off(&mut timeline);
+        }
+        let totals = summarize_timeline(&timeline);
+
+        let devices = if device_active.is_empty() {
+            *device_totals.entry(None).or_default() += totals.active_seconds;
+            Vec::new()
+        } else {
+            if device_list.is_none() {
+                device_list = Some(registered_devices(&log_dir)?);
+            }
+            for (device, seconds) in &device_active {
+                *device_totals.entry(device.clone()).or_default() += seconds;
+            }
+            device_list.as_ref().map_or_else(Vec::new, |list| device_activity(device_active, list))
+        };
+        days.push(DayActivity {
+            date: date.format("%Y-%m-%d").to_string(),
+            totals,
+            day_off,
+            devices,
+            text: include_text.then(|| format_timeline(&timeline, tz)),
+        });
+
+        for interval in timeline {
+            // A day ends one second before the next begins
+            match intervals.last_mut() {
+                Some(last) if last.kind == interval.kind && interval.start - last.end <= 1 => last.end = interval.end,
+                _ => intervals.push(interval),
+            }
+        }
+    }
+
+    // Joined stretches also span the second between two days, so add up the days instead
+    let mut totals = summarize_timeline(&intervals);
+    totals.active_seconds = days.iter().map(|d| d.totals.active_seconds).sum();
+    totals.inactive_seconds = days.iter().map(|d| d.totals.inactive_seconds).sum();
+    totals.suspended_seconds = days.iter().map(|d| d.totals.suspended_seconds).sum();
+    totals.clock_adjusted_seconds = days.iter().map(|d| d.totals.clock_adjusted_seconds).sum();
+    totals.not_run_seconds = days.iter().map(|d| d.totals.not_run_seconds).sum();
+    totals.day_off_seconds = days.iter().map(|d| d.totals.day_off_seconds).sum();
+    totals.powered_off_seconds = days.iter().map(|d| d.totals.powered_off_seconds).sum();
+    totals.app_closed_seconds = days.iter().map(|d| d.totals.app_closed_seconds).sum();
+    totals.crashed_seconds = days.iter().map(|d| d.totals.crashed_seconds).sum();
+    totals.unknown_seconds = days.iter().map(|d| d.totals.unknown_seconds).sum();
+
+    let working_days: Vec<&DayActivity> = days.iter().filter(|d| d.day_off.is_empty()).collect();
+    let working_active: i64 = working_days.iter().map(|d| d.totals.active_seconds).sum();
+    let average_active_seconds = working_active / working_days.len().max(1) as i64;
+
+    Ok(ActivityAggregate {
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        intervals,
+        totals,
+        working_days: working_days.len(),
+        average_active_seconds,
+        devices: device_list.as_ref().map_or_else(Vec::new, |list| device_activity(device_totals, list)),
+        days,
+    })
+}
+
+/// Active seconds per device, this device first
+fn device_activity(active: DeviceActiveSeconds, devices: &DeviceList) -> Vec<DeviceActivity> {
+    active
+        .into_iter()
+        .map(|(device, active_seconds)| DeviceActivity {
+            name: devices.name_of(device.as_deref()),
+            device_id: device.unwrap_or_else(|| devices.this_device.id.clone()),
+            active_seconds,
+        })
+        .collect()
+}
+
+/// Show the time not run on a holiday or leave day as a day off
+pub fn mark_day_off(timeline: &mut [TimelineInterval]) {
+    for interval in timeline.iter_mut().filter(|i| i.kind.is_not_running()) {
+        interval.kind = IntervalKind::DayOff;
+    }
+}
+
+/// Seconds per kind, first and last activity and the longest active stretch of a timeline
+pub fn summarize_timeline(timeline: &[TimelineInterval]) -> ActivityTotals {
+    let mut totals = ActivityTotals::default();
+    for interval in timeline {
+        let seconds = interval.end - interval.start;
+        match interval.kind {
+            IntervalKind::Active => totals.active_seconds += seconds,
+            IntervalKind::Inactive => totals.inactive_seconds += seconds,
+            IntervalKind::Suspended => totals.suspended_seconds += seconds,
+            IntervalKind::ClockAdjusted => totals.clock_adjusted_seconds += seconds,
+            IntervalKind::NotRun => totals.not_run_seconds += seconds,
+            IntervalKind::DayOff => totals.day_off_seconds += seconds,
+            IntervalKind::PoweredOff => totals.powered_off_seconds += seconds,
+            IntervalKind::AppClosed => totals.app_closed_seconds += seconds,
+            IntervalKind::Crashed => totals.crashed_seconds += seconds,
+            IntervalKind::Unknown => totals.unknown_seconds += seconds,
+        }
+    }
+
+    let active = timeline.iter().filter(|i| i.kind == IntervalKind::Active);
+    totals.first_activity = active.clone().next().map(|i| i.start);
+    totals.last_activity = active.clone().next_back().map(|i| i.end);
+    // The earliest of equally long stretches
+    totals.longest_active = active.rev().max_by_key(|i| i.end - i.start).copied();
+    totals
+}
+
+/// Active seconds of an inclusive date range bucketed by weekday and local time of day.
+/// `bucket_minutes` must divide a day, e.g. 60 for a 7×24 grid.
+pub fn activity_heatmap<Tz: TimeZone>(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    tz: &Tz,
+    bucket_minutes: u32
+) -> Result<ActivityHeatmap, Box<dyn std::error::Error>> {
+    if bucket_minutes == 0 || 1440 % bucket_minutes != 0 {
+        return Err(format!("A day cannot be divided into {}-minute buckets", bucket_minutes).into());
+    }
+    let (log_dir, _) = get_platform_directories()?;
+    let buckets = (1440 / bucket_minutes) as usize;
+    let mut active_seconds = vec![vec![0i64; buckets]; 7];
+    let mut day_counts = vec![0u32; 7];
+
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        let (day_start, day_end) = day_bounds(date, tz);
+        let timeline = read_day_timeline(&log_dir, date, day_start, day_end)?;
+        add_to_heatmap_row(&mut active_seconds[weekday], &timeline, tz, bucket_minutes as i64 * 60);
+        day_counts[weekday] += 1;
+    }
+
+    Ok(ActivityHeatmap {
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        bucket_minutes,
+        active_seconds,
+        day_counts,
+    })
+}
+
+/// Add the active time of one day's timeline to `row`, by the local clock. On the day clocks
+/// go back the repeated hour counts twice towards its bucket.
+fn add_to_heatmap_row<Tz: TimeZone>(row: &mut [i64], timeline: &[TimelineInterval], tz: &Tz, bucket_secs: i64) {
+    for interval in timeline.iter().filter(|i| i.kind == IntervalKind::Active) {
+        for (start, end, offset) in split_at_offset_changes(tz, interval.start, interval.end) {
+            let offset = offset.local_minus_utc() as i64;
+            // Seconds since local midnight; the interval lies within one local day
+            let mut time = (start + offset).rem_euclid(86_400);
+            let end = time + (end - start);
+            while time < end {
+                let bucket = (time / bucket_secs) as usize;
+                let next = end.min((bucket as i64 + 1) * bucket_secs);
+                if let Some(cell) = row.get_mut(bucket) {
+                    *cell += next - time;
+                }
+                time = next;
+            }
+        }
+    }
+}
+
+/// Time spent per focused application for one daily log file, longest first
+pub fn aggregate_application_usage(file_name: &str) -> Result<Vec<ApplicationUsage>, Box<dyn std::error::Error>> {
+    let (log_dir, _) = get_platform_directories()?;
+    let target_date = parse_log_file_date(file_name)?;
+    let (day_start, day_end) = day_bounds(target_date, &recorded_zone(&log_dir));
+
+    // Older logs rewrite each focus stretch with a growing end, so keep the latest end per start
+    let mut focus_groups: HashMap<(i64, ForegroundApp), i64> = HashMap::new();
+    for record in read_records_for_day(&log_dir, target_date, day_start, day_end)? {
+        if let ActivityRecord::Application { start, end, app } = record {
+            focus_groups
+                .entry((start as i64, app))
+                .and_modify(|e| *e = (*e).max(end as i64))
+                .or_insert(end as i64);
+        }
+    }
+
+    let mut usage: Vec<ApplicationUsage> = Vec::new();
+    for ((start, app), end) in focus_groups {
+        let seconds = clipped_seconds(start, end, day_start, day_end);
+        if seconds > 0 {
+            add_application_time(&mut usage, &app, seconds);
+        }
+    }
+
+    sort_application_usage(&mut usage);
+    Ok(usage)
+}
+
+/// Time spent per focused application over an inclusive date range
+pub fn aggregate_application_usage_range(
+    start_date: NaiveDate,
+    end_date: NaiveDate
+) -> Result<Vec<ApplicationUsage>, Box<dyn std::error::Error>> {
+    let mut combined: Vec<ApplicationUsage> = Vec::new();
+
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        let file_name = format!("rs-fairsight({}).txt", date.format("%Y-%m-%d"));
+        for day_usage in aggregate_application_usage(&file_name)? {
+            match combined.iter_mut().find(|u| u.process_name == day_usage.process_name && u.exe_path == day_usage.exe_path) {
+                Some(existing) => {
+                    existing.total_seconds += day_usage.total_seconds;
+                    for title in day_usage.window_titles {
+                        match existing.window_titles.iter_mut().find(|t| t.title == title.title) {
+                            Some(existing_title) => existing_title.seconds += title.seconds,
+                            None => existing.window_titles.push(title),
+                        }
+                    }
+                }
+                None => combined.push(day_usage),
+            }
+        }
+    }
+
+    sort_application_usage(&mut combined);
+    Ok(combined)
+}
+
+/// Time per project and task over an inclusive date range, most time first.
+/// Overlapping tags are resolved with `resolve_overlaps`, so no second is counted twice.
+pub fn aggregate_tag_totals(
+    start_date: NaiveDate,
+    end_date: NaiveDate
+) -> Result<Vec<TagTotal>, Box<dyn std::error::Error>> {
+    let (log_dir, _) = get_platform_directories()?;
+    let keyring = active_keyring()?;
+    let zone = recorded_zone(&log_dir);
+    let mut totals: Vec<TagTotal> = Vec::new();
+
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        let tags = tags_in_range(&log_dir, date, date, &keyring)?;
+        if tags.is_empty() {
+            continue;
+        }
+        let (day_start, day_end) = day_bounds(date, &zone);
+        let active = day_active_intervals(&log_dir, date, day_start, day_end)?;
+
+        for span in resolve_overlaps(&tags, day_start as u64, day_end as u64) {
+            let (start, end) = (span.start as i64, span.end as i64);
+            let active_seconds: i64 = active
+                .iter()
+                .map(|&(active_start, active_end)| clipped_seconds(active_start, active_end, start, end))
+                .sum();
+
+            let index = match totals.iter().position(|t| t.project == span.tag.project && t.task == span.tag.task) {
+                Some(index) => index,
+                None => {
+                    totals.push(TagTotal {
+                        project: span.tag.project.clone(),
+                        task: span.tag.task.clone(),
+                        tagged_seconds: 0,
+                        active_seconds: 0,
+                    });
+                    totals.len() - 1
+                }
+            };
+            totals[index].tagged_seconds += end - start;
+            totals[index].active_seconds += active_seconds;
+        }
+    }
+
+    totals.sort_by(|a, b| {
+        b.tagged_seconds
+            .cmp(&a.tagged_seconds)
+            .then_with(|| (&a.project, &a.task).cmp(&(&b.project, &b.task)))
+    });
+    Ok(totals)
+}
+
+/// Non-overlapping active stretches of a day, by start time
+fn day_active_intervals(
+    log_dir: &Path,
+    date: NaiveDate,
+    day_start: i64,
+    day_end: i64
+) -> Result<Vec<(i64, i64)>, Box<dyn std::error::Error>> {
+    let mut active_groups: HashMap<i64, i64> = HashMap::new();
+    for record in read_records_for_day(log_dir, date, day_start, day_end)? {
+        if let ActivityRecord::Active { start, end } = record {
+            active_groups
+                .entry(start as i64)
+                .and_modify(|e| *e = (*e).max(end as i64))
+                .or_insert(end as i64);
+        }
+    }
+
+    let mut intervals: Vec<(i64, i64)> = active_groups.into_iter().collect();
+    intervals.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    Ok(merged)
+}
+
+fn add_application_time(usage: &mut Vec<ApplicationUsage>, app: &ForegroundApp, seconds: i64) {
+    let index = match usage.iter().position(|u| u.process_name == app.process_name && u.exe_path == app.exe_path) {
+        Some(index) => index,
+        None => {
+            usage.push(ApplicationUsage {
+                process_name: app.process_name.clone(),
+                exe_path: app.exe_path.clone(),
+                total_seconds: 0,
+                window_titles: Vec::new(),
+            });
+            usage.len() - 1
+        }
+    };
+
+    let entry = &mut usage[index];
+    entry.total_seconds += seconds;
+    if let Some(title) = &app.window_title {
+        match entry.window_titles.iter_mut().find(|t| &t.title == title) {
+            Some(existing) => existing.seconds += seconds,
+            None => entry.window_titles.push(WindowTitleUsage {
+                title: title.clone(),
+                seconds,
+            }),
+        }
+    }
+}
+
+fn sort_application_usage(usage: &mut [ApplicationUsage]) {
+    usage.sort_by_key(|u| std::cmp::Reverse(u.total_seconds));
+    for entry in usage.iter_mut() {
+        entry.window_titles.sort_by_key(|t| std::cmp::Reverse(t.seconds));
+    }
+}
+
+fn clipped_seconds(start: i64, end: i64, range_start: i64, range_end: i64) -> i64 {
+    (end.min(range_end) - start.max(range_start)).max(0)
+}
+
+/// Extracts the date from a "rs-fairsight(YYYY-MM-DD).txt" file name
+fn parse_log_file_date(file_name: &str) -> Result<NaiveDate, Box<dyn std::error::Error>> {
     let date_str = file_name
         .strip_prefix("rs-fairsight(")
         .and_then(|s| s.strip_suffix(").txt"))
         .ok_or("Invalid filename format")?;
-    let target_date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?;
+    Ok(NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?)
+}
 
-    let day_start = Local.from_local_datetime(&target_date.and_hms_opt(0, 0, 0).unwrap()).unwrap();
-    let day_end = Local.from_local_datetime(&target_date.and_hms_opt(23, 59, 59).unwrap()).unwrap();
+/// First second of `date` in `tz`. A DST change can skip midnight, in which case the day
+/// starts at the first local time that exists.
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> i64 {
+    let midnight = date.and_time(NaiveTime::MIN);
+    (0..=24 * 60)
+        .find_map(|minutes| tz.from_local_datetime(&(midnight + Duration::minutes(minutes))).earliest())
+        .map(|start| start.timestamp())
+        .unwrap_or_else(|| midnight.and_utc().timestamp())
+}
 
-    let mut active_groups: HashMap<i64, i64> = HashMap::new();
-    let mut inactive_periods: Vec<(DateTime<Local>, DateTime<Local>)> = Vec::new();
+/// First and last second of `date` in `tz`; 23 or 25 hours apart on DST changes
+pub fn day_bounds<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> (i64, i64) {
+    let day_start = start_of_day(date, tz);
+    let next_start = date.succ_opt().map_or(day_start + 86_400, |next| start_of_day(next, tz));
+    (day_start, next_start - 1)
+}
 
-    let content = fs::read(&file_path)?;
-    let mut offset = 0;
+/// Decrypts and decodes every record of a daily log file, reporting records it had to skip
+fn read_day_records(file_path: &Path, keyring: &KeyRing) -> Result<Vec<ActivityRecord>, Box<dyn std::error::Error>> {
+    let contents = read_log_file(file_path, keyring)?;
 
-    while offset < content.len() {
-        if content.len() - offset < 12 + 4 {
-            break; // Not enough bytes for nonce (12) + length (4)
+    for problem in &contents.unreadable {
+        crate::log_warning!("aggregation", "Skipped record in {:?}: {}", file_path, problem);
+    }
+    if contents.truncated {
+        crate::log_warning!("aggregation", "{:?} ends with an incomplete record", file_path);
+    }
+
+    Ok(contents.records)
+}
+
+/// Records with the device they were merged from, if any
+type DeviceRecords = Vec<(Option<String>, ActivityRecord)>;
+/// Active seconds per device, this device under `None`
+pub type DeviceActiveSeconds = BTreeMap<Option<String>, i64>;
+
+/// Modification time and length of a file, `None` if it does not exist
+type FileStamp = Option<(SystemTime, u64)>;
+
+/// Decoded records of one date's files, valid while the files are unchanged
+struct CachedDayFiles {
+    log_stamp: FileStamp,
+    imported_stamp: FileStamp,
+    show_imported: bool,
+    records: Arc<DeviceRecords>,
+}
+
+// Each day of a range also reads its neighbours' files, and the recorded zone the files two
+// days out; a few decoded dates are enough to decode every file once while walking a range
+const DAY_FILE_CACHE_DATES: usize = 6;
+
+static DAY_FILE_CACHE: Lazy<Mutex<HashMap<(PathBuf, NaiveDate), CachedDayFiles>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Records of the log and, if `show_imported`, the imported history filed under `file_date`
+fn read_day_files(
+    log_dir: &Path,
+    file_date: NaiveDate,
+    show_imported: bool,
+    keyring: &KeyRing
+) -> Result<Arc<DeviceRecords>, Box<dyn std::error::Error>> {
+    let file_path = log_dir.join(format!("rs-fairsight({}).txt", file_date.format("%Y-%m-%d")));
+    let log_stamp = file_stamp(&file_path);
+    let imported_stamp = if show_imported { file_stamp(&imported_file_path(log_dir, file_date)) } else { None };
+    let key = (log_dir.to_path_buf(), file_date);
+    if let Some(cached) = DAY_FILE_CACHE.lock().unwrap().get(&key) {
+        if cached.log_stamp == log_stamp && cached.imported_stamp == imported_stamp && cached.show_imported == show_imported {
+            return Ok(cached.records.clone());
         }
+    }
 
-        // Read nonce (12 bytes)
-        let nonce_bytes: [u8; 12] = content[offset..offset + 12].try_into()?;
-        offset += 12;
+    let mut records = Vec::new();
+    if log_stamp.is_some() {
+        records.extend(read_day_records(&file_path, keyring)?.into_iter().map(|record| (None, record)));
+    }
+    if show_imported {
+        let imported = imported_records(log_dir, file_date, keyring)?;
+        records.extend(imported.into_iter().map(|imported| {
+            (source_device(&imported.source).map(str::to_string), *imported.record)
+        }));
+    }
+    let records = Arc::new(records);
+
+    let mut cache = DAY_FILE_CACHE.lock().unwrap();
+    cache.insert(key, CachedDayFiles { log_stamp, imported_stamp, show_imported, records: records.clone() });
+    while cache.len() > DAY_FILE_CACHE_DATES {
+        // Drop the date furthest from this one, which a range walk is moving away from
+        let Some(furthest) = cache.keys().max_by_key(|(_, date)| (*date - file_date).num_days().abs()).cloned() else {
+            break;
+        };
+        cache.remove(&furthest);
+    }
+    Ok(records)
+}
+
+/// The zone the logs in `log_dir` were recorded in, for reports given no timezone
+pub fn recorded_zone(log_dir: &Path) -> RecordedZone {
+    let log_dir = log_dir.to_path_buf();
+    RecordedZone::new(move |file_date| {
+        let Ok(keyring) = active_keyring() else { return Vec::new() };
+        // Unreadable files are reported when the day itself is read
+        let Ok(records) = read_day_files(&log_dir, file_date, load_activity_settings().show_imported, &keyring) else {
+            return Vec::new();
+        };
+        records
+            .iter()
+            .filter_map(|(device, record)| match (device, record) {
+                (None, ActivityRecord::UtcOffset { at, offset_secs }) => Some((*at as i64, *offset_secs)),
+                _ => None,
+            })
+            .collect()
+    })
+}
+
+/// Records overlapping `day_start..=day_end`. Files are named after the local date they were
+/// written on, so the neighbouring days' files are consulted too: a stretch that runs past
+/// midnight is only in the next day's file, and reports in another timezone straddle files.
+/// Imported history is included unless hidden in the settings.
+fn read_records_for_day(
+    log_dir: &Path,
+    date: NaiveDate,
+    day_start: i64,
+    day_end: i64
+) -> Result<Vec<ActivityRecord>, Box<dyn std::error::Error>> {
+    let records = read_device_records_for_day(log_dir, date, day_start, day_end)?;
+    Ok(records.into_iter().map(|(_, record)| record).collect())
+}
 
-        // Read length (4 bytes)
-        let len_bytes: [u8; 4] = content[offset..offset + 4].try_into()?;
-        let encrypted_len = u32::from_le_bytes(len_bytes) as usize;
-        offset += 4;
+/// [`read_records_for_day`] with the id of the device each record was merged from. This
+/// device's own records and history imported from other trackers come under `None`.
+fn read_device_records_for_day(
+    log_dir: &Path,
+    date: NaiveDate,
+    day_start: i64,
+    day_end: i64
+) -> Result<DeviceRecords, Box<dyn std::error::Error>> {
+    let show_imported = load_activity_settings().show_imported;
+    read_device_records_with(log_dir, date, day_start, day_end, show_imported, &active_keyring()?)
+}
+
+fn read_device_records_with(
+    log_dir: &Path,
+    date: NaiveDate,
+    day_start: i64,
+    day_end: i64,
+    show_imported: bool,
+    keyring: &KeyRing
+) -> Result<DeviceRecords, Box<dyn std::error::Error>> {
+    let mut records = Vec::new();
+    let first = date.pred_opt().unwrap_or(date);
+    let last = date.succ_opt().unwrap_or(date);
 
-        if content.len() - offset < encrypted_len {
-            break; // Not enough data for encrypted content
+    for file_date in first.iter_days().take_while(|d| *d <= last) {
+        match read_day_files(log_dir, file_date, show_imported, keyring) {
+            Ok(day_records) => records.extend(day_records.iter().cloned()),
+            // A damaged neighbour only loses the stretches that cross midnight
+            Err(e) if file_date != date => {
+                crate::log_warning!("aggregation", "Skipped the {} logs while reading {}: {}", file_date, date, e);
+            }
+            Err(e) => return Err(e),
         }
+    }
 
-        let mut encrypted_data = content[offset..offset + encrypted_len].to_vec();
-        offset += encrypted_len;
+    // Lifecycle records outside the day still tell why the tracker was not running in it
+    records.retain(|(_, record)| {
+        lifecycle_time(record).is_some()
+            || record
+                .time_range()
+                .is_some_and(|(start, end)| start as i64 <= day_end && end as i64 >= day_start)
+    });
+    Ok(records)
+}
 
-        // Decrypt the line
-        let decrypted_line = decrypt_string(&mut encrypted_data, &KEY, nonce_bytes)
-            .map_err(|e| format!("Decryption failed: {:?}", e))?;
+/// The day from `day_start` to `day_end` as consecutive stretches read from the logs
+pub fn read_day_timeline(
+    log_dir: &Path,
+    date: NaiveDate,
+    day_start: i64,
+    day_end: i64
+) -> Result<Vec<TimelineInterval>, Box<dyn std::error::Error>> {
+    Ok(read_day_activity(log_dir, date, day_start, day_end)?.0)
+}
 
-        process_decrypted_line(&decrypted_line, &mut active_groups, &mut inactive_periods)?;
+/// The day's timeline and, when other devices' logs were merged into it, the active seconds
+/// of each device with this one under `None`
+pub fn read_day_activity(
+    log_dir: &Path,
+    date: NaiveDate,
+    day_start: i64,
+    day_end: i64
+) -> Result<(Vec<TimelineInterval>, DeviceActiveSeconds), Box<dyn std::error::Error>> {
+    let records = read_device_records_for_day(log_dir, date, day_start, day_end)?;
+    Ok(device_timeline(records, day_start, day_end))
+}
+
+/// Each device's records make their own timeline; where they overlap the combined day takes
+/// the most telling stretch, so time active on two machines at once counts once
+fn device_timeline(records: DeviceRecords, day_start: i64, day_end: i64) -> (Vec<TimelineInterval>, DeviceActiveSeconds) {
+    if records.iter().all(|(device, _)| device.is_none()) {
+        let records: Vec<ActivityRecord> = records.into_iter().map(|(_, record)| record).collect();
+        return (build_timeline(&records, day_start, day_end), BTreeMap::new());
     }
 
-    // Process events and generate output
-    generate_aggregated_output(active_groups, inactive_periods, day_start, day_end)
+    let mut by_device: BTreeMap<Option<String>, Vec<ActivityRecord>> = BTreeMap::new();
+    for (device, record) in records {
+        by_device.entry(device).or_default().push(record);
+    }
+    let mut device_active = BTreeMap::new();
+    let mut timelines = Vec::new();
+    for (device, records) in by_device {
+        let timeline = build_timeline(&records, day_start, day_end);
+        let active = timeline.iter().filter(|i| i.kind == IntervalKind::Active).map(|i| i.end - i.start).sum();
+        device_active.insert(device, active);
+        timelines.push(timeline);
+    }
+    (combine_timelines(&timelines), device_active)
 }
 
-fn process_decrypted_line(
-    decrypted_line: &str,
+/// Which stretch wins where timelines of several devices overlap
+fn merge_precedence(kind: IntervalKind) -> u8 {
+    match kind {
+        IntervalKind::Active => 5,
+        IntervalKind::Inactive => 4,
+        IntervalKind::Suspended => 3,
+        IntervalKind::ClockAdjusted => 2,
+        IntervalKind::DayOff
+        | IntervalKind::PoweredOff
+        | IntervalKind::AppClosed
+        | IntervalKind::Crashed
+        | IntervalKind::Unknown => 1,
+        IntervalKind::NotRun => 0,
+    }
+}
+
+/// One timeline from several covering the same day, taking the stretch with the highest
+/// precedence at every moment
+fn combine_timelines(timelines: &[Vec<TimelineInterval>]) -> Vec<TimelineInterval> {
+    let mut bounds: Vec<i64> = timelines.iter().flatten().flat_map(|i| [i.start, i.end]).collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut kinds: Vec<Option<IntervalKind>> = vec![None; bounds.len().saturating_sub(1)];
+    for interval in timelines.iter().flatten() {
+        let first = bounds.partition_point(|&bound| bound < interval.start);
+        let last = bounds.partition_point(|&bound| bound < interval.end);
+        for kind in &mut kinds[first..last] {
+            if kind.is_none_or(|current| merge_precedence(interval.kind) > merge_precedence(current)) {
+                *kind = Some(interval.kind);
+            }
+        }
+    }
+
+    let mut combined: Vec<TimelineInterval> = Vec::new();
+    for (index, kind) in kinds.into_iter().enumerate() {
+        let Some(kind) = kind else { continue };
+        let (start, end) = (bounds[index], bounds[index + 1]);
+        match combined.last_mut() {
+            Some(last) if last.kind == kind && last.end == start => last.end = end,
+            _ => combined.push(TimelineInterval { start, end, kind }),
+        }
+    }
+    combined
+}
+
+fn process_record(
+    record: &ActivityRecord,
     active_groups: &mut HashMap<i64, i64>,
-    inactive_periods: &mut Vec<(DateTime<Local>, DateTime<Local>)>
-) -> Result<(), Box<dyn std::error::Error>> {
-    let parts: Vec<&str> = decrypted_line.split(" - ").collect();
-
-    if parts.len() == 2 {
-        if decrypted_line.starts_with("Active time") {
-            let end_str = parts[0].split_whitespace().last().unwrap();
-            let start_str = parts[1].trim();
-
-            let end_result = end_str.parse::<i64>();
-            let start_result = start_str.parse::<i64>();
-
-            match (&end_result, &start_result) {
-                (Ok(period_end), Ok(period_start)) => {
-                    let start = *period_start;
-                    let end = *period_end;
-                    active_groups
-                        .entry(start)
-                        .and_modify(|e| {
-                            *e = (*e).max(end);
-                        })
-                        .or_insert(end);
-                }
-                _ => {
-                    println!("Failed to parse: end={:?}, start={:?}", end_result, start_result);
-                }
+    inactive_periods: &mut Vec<(i64, i64)>,
+    clock_periods: &mut Vec<TimelineInterval>
+) {
+    match *record {
+        ActivityRecord::Active { start, end } => {
+            active_groups
+                .entry(start as i64)
+                .and_modify(|e| {
+                    *e = (*e).max(end as i64);
+                })
+                .or_insert(end as i64);
+        }
+        ActivityRecord::Inactive { start, end } => {
+            inactive_periods.push((start as i64, end as i64));
+        }
+        ActivityRecord::Suspended { start, end } => {
+            clock_periods.push(TimelineInterval {
+                start: start as i64,
+                end: end as i64,
+                kind: IntervalKind::Suspended,
+            });
+        }
+        ActivityRecord::ClockAdjusted { .. } => {
+            // The wall-clock range skipped over (set forward) or lived twice (set back)
+            if let Some((start, end)) = record.time_range() {
+                clock_periods.push(TimelineInterval {
+                    start: start as i64,
+                    end: end as i64,
+                    kind: IntervalKind::ClockAdjusted,
+                });
             }
-        } else if decrypted_line.starts_with("Inactive time") {
-            let end_str = parts[0].split_whitespace().last().unwrap();
-            let start_str = parts[1].trim();
-
-            match (end_str.parse::<i64>(), start_str.parse::<i64>()) {
-                (Ok(period_end), Ok(period_start)) => {
-                    let start_time = Local.timestamp_opt(period_start, 0).unwrap();
-                    let end_time = Local.timestamp_opt(period_end, 0).unwrap();
-                    inactive_periods.push((start_time, end_time));
-                }
-                _ => {
-                    println!(
-                        "Failed to parse inactive time: end='{}', start='{}'",
-                        end_str,
-                        start_str
-                    );
-                }
+        }
+        _ => {}
+    }
+}
+
+/// Cut `periods` out of every event, keeping the parts outside them
+fn carve_out(events: Vec<TimelineInterval>, periods: &[TimelineInterval]) -> Vec<TimelineInterval> {
+    let mut pieces = events;
+    for period in periods {
+        pieces = pieces
+            .into_iter()
+            .flat_map(|event| {
+                let before = (event.start < period.start)
+                    .then(|| TimelineInterval { end: event.end.min(period.start), ..event });
+                let after = (event.end > period.end)
+                    .then(|| TimelineInterval { start: event.start.max(period.end), ..event });
+                before.into_iter().chain(after)
+            })
+            .collect();
+    }
+    pieces
+}
+
+/// UTC offset of `tz` at `timestamp`
+pub fn utc_offset<Tz: TimeZone>(tz: &Tz, timestamp: i64) -> FixedOffset {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(utc) => tz.offset_from_utc_datetime(&utc.naive_utc()).fix(),
+        None => Utc.fix(),
+    }
+}
+
+/// Split `start..end` where the UTC offset of `tz` changes, so that the clock times of each
+/// piece are as far apart as the piece is long. Assumes at most one change per piece of a day.
+fn split_at_offset_changes<Tz: TimeZone>(tz: &Tz, start: i64, end: i64) -> Vec<(i64, i64, FixedOffset)> {
+    let mut pieces = Vec::new();
+    let mut piece_start = start;
+    loop {
+        let offset = utc_offset(tz, piece_start);
+        if utc_offset(tz, end) == offset {
+            pieces.push((piece_start, end, offset));
+            return pieces;
+        }
+        // First second with the new offset
+        let (mut low, mut high) = (piece_start, end);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if utc_offset(tz, mid) == offset {
+                low = mid;
+            } else {
+                high = mid;
             }
         }
+        pieces.push((piece_start, high, offset));
+        piece_start = high;
     }
-    Ok(())
 }
 
-fn generate_aggregated_output(
-    active_groups: HashMap<i64, i64>,
-    inactive_periods: Vec<(DateTime<Local>, DateTime<Local>)>,
-    day_start: DateTime<Local>,
-    day_end: DateTime<Local>
-) -> Result<String, Box<dyn std::error::Error>> {
-    let mut all_events: Vec<(DateTime<Local>, DateTime<Local>, &str)> = Vec::new();
+fn format_clock_time(timestamp: i64, offset: FixedOffset) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|utc| utc.with_timezone(&offset).format("%H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// The day from `day_start` to `day_end` as consecutive stretches, with the time not covered
+/// by any record marked as not run
+fn build_timeline(records: &[ActivityRecord], day_start: i64, day_end: i64) -> Vec<TimelineInterval> {
+    let mut active_groups: HashMap<i64, i64> = HashMap::new();
+    let mut inactive_periods: Vec<(i64, i64)> = Vec::new();
+    let mut clock_periods: Vec<TimelineInterval> = Vec::new();
+    for record in records {
+        process_record(record, &mut active_groups, &mut inactive_periods, &mut clock_periods);
+    }
+
+    let mut all_events: Vec<TimelineInterval> = Vec::new();
     for (start, max_end) in active_groups {
-        let start_time = Local.timestamp_opt(start, 0).unwrap();
-        let end_time = Local.timestamp_opt(max_end, 0).unwrap();
-        all_events.push((start_time, end_time, "Active"));
+        all_events.push(TimelineInterval { start, end: max_end, kind: IntervalKind::Active });
     }
     for (start, end) in inactive_periods {
-        all_events.push((start, end, "Inactive"));
+        all_events.push(TimelineInterval { start, end, kind: IntervalKind::Inactive });
     }
-    all_events.sort_by(|a, b| a.0.cmp(&b.0));
+    // Sleep and clock changes replace whatever the records claim for those periods
+    let mut all_events = carve_out(all_events, &clock_periods);
+    all_events.extend(clock_periods);
 
-    let mut target_events: Vec<(DateTime<Local>, DateTime<Local>, &str)> = all_events
+    let mut target_events: Vec<TimelineInterval> = all_events
         .into_iter()
-        .filter(|(start, end, _)| *start <= day_end && *end >= day_start)
-        .map(|(start, end, event_type)| {
-            let clipped_start = start.max(day_start);
-            let clipped_end = end.min(day_end);
-            (clipped_start, clipped_end, event_type)
+        .filter(|event| event.start <= day_end && event.end >= day_start)
+        .map(|event| TimelineInterval {
+            start: event.start.max(day_start),
+            end: event.end.min(day_end),
+            kind: event.kind,
         })
         .collect();
 
-    target_events.sort_by(|a, b| a.0.cmp(&b.0));
+    target_events.sort_by_key(|event| event.start);
 
+    let mut lifecycle: Vec<ActivityRecord> = records.iter().filter(|r| lifecycle_time(r).is_some()).cloned().collect();
+    lifecycle.sort_by_key(lifecycle_time);
+    let not_run = |start, end| not_run_intervals(&lifecycle, start, end);
     let mut final_events = Vec::new();
     if !target_events.is_empty() {
-        if day_start < target_events[0].0 {
-            final_events.push((day_start, target_events[0].0, "Not run"));
+        if day_start < target_events[0].start {
+            final_events.extend(not_run(day_start, target_events[0].start));
         }
         final_events.push(target_events[0]);
         for i in 1..target_events.len() {
-            if target_events[i - 1].1 < target_events[i].0 {
-                final_events.push((target_events[i - 1].1, target_events[i].0, "Not run"));
+            if target_events[i - 1].end < target_events[i].start {
+                final_events.extend(not_run(target_events[i - 1].end, target_events[i].start));
             }
             final_events.push(target_events[i]);
         }
-        if target_events.last().unwrap().1 < day_end {
-            final_events.push((target_events.last().unwrap().1, day_end, "Not run"));
+        if target_events[target_events.len() - 1].end < day_end {
+            final_events.extend(not_run(target_events[target_events.len() - 1].end, day_end));
         }
     } else {
-        final_events.push((day_start, day_end, "Not run"));
+        final_events.extend(not_run(day_start, day_end));
     }
+    final_events
+}
+
+/// A gap in the records from `start` to `end`, split by why the tracker was not running.
+/// Time the previous run was still alive counts as inactive.
+fn not_run_intervals(lifecycle: &[ActivityRecord], start: i64, end: i64) -> Vec<TimelineInterval> {
+    let Some(stopped) = classify_gap(lifecycle, start) else {
+        return vec![TimelineInterval { start, end, kind: IntervalKind::NotRun }];
+    };
+    let kind = match stopped.ending {
+        Some(RunEnding::PoweredOff) => IntervalKind::PoweredOff,
+        Some(RunEnding::AppClosed) => IntervalKind::AppClosed,
+        Some(RunEnding::Crashed) => IntervalKind::Crashed,
+        None => IntervalKind::Unknown,
+    };
+    // The restart may be after the gap, e.g. the next morning for the end of a day
+    let last_seen = (stopped.last_seen as i64).min(end);
+    let restarted_at = (stopped.restarted_at as i64).min(end);
+    [
+        TimelineInterval { start, end: last_seen, kind: IntervalKind::Inactive },
+        TimelineInterval { start: last_seen, end: restarted_at, kind },
+        TimelineInterval { start: restarted_at, end, kind: IntervalKind::NotRun },
+    ]
+    .into_iter()
+    .filter(|interval| interval.start < interval.end)
+    .collect()
+}
 
+/// "Active: 09:00:00 - 10:15:00" lines with clock times in `tz`. Stretches across a DST
+/// change are split at it so that 23- and 25-hour days add up.
+fn format_timeline<Tz: TimeZone>(timeline: &[TimelineInterval], tz: &Tz) -> String {
     let mut output = String::new();
-    for (start, end, event_type) in &final_events {
-        output.push_str(&format!(
-            "{}: {} - {}\n",
-            event_type,
-            start.format("%H:%M:%S"),
-            end.format("%H:%M:%S")
-        ));
+    for interval in timeline {
+        for (piece_start, piece_end, offset) in split_at_offset_changes(tz, interval.start, interval.end) {
+            output.push_str(&format!(
+                "{}: {} - {}\n",
+                interval.kind.label(),
+                format_clock_time(piece_start, offset),
+                format_clock_time(piece_end, offset)
+            ));
+        }
     }
     output.push('\n');
 
-    Ok(output)
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use chrono_tz::America::{New_York, Santiago};
+    use super::super::records::encode_log;
+
+    fn seconds_of(clock: &str) -> i64 {
+        let parts: Vec<i64> = clock.split(':').map(|part| part.parse().unwrap()).collect();
+        parts[0] * 3600 + parts[1] * 60 + parts[2]
+    }
+
+    /// Seconds per event type, computed from the clock times like the UI does
+    fn event_totals(output: &str) -> HashMap<String, i64> {
+        let mut totals = HashMap::new();
+        for line in output.lines().filter(|line| !line.is_empty()) {
+            let (event_type, range) = line.split_once(": ").unwrap();
+            let (start, end) = range.split_once(" - ").unwrap();
+            *totals.entry(event_type.to_string()).or_insert(0) += seconds_of(end) - seconds_of(start);
+        }
+        totals
+    }
+
+    #[test]
+    fn test_dst_day_lengths() {
+        let fall_back = NaiveDate::from_ymd_opt(2024, 11, 3).unwrap();
+        let (day_start, day_end) = day_bounds(fall_back, &New_York);
+        assert_eq!(day_end - day_start, 25 * 3600 - 1);
+
+        // Active from 00:30 EDT to 01:30 EST is two hours
+        let active_start = (day_start + 1800) as u64;
+        let records = [ActivityRecord::Active { start: active_start, end: active_start + 7200 }];
+        let output = format_timeline(&build_timeline(&records, day_start, day_end), &New_York);
+        let totals = event_totals(&output);
+        assert_eq!(totals["Active"], 7200);
+        assert_eq!(totals["Active"] + totals["Not run"], 25 * 3600 - 1);
+
+        let spring_forward = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let (day_start, day_end) = day_bounds(spring_forward, &New_York);
+        assert_eq!(day_end - day_start, 23 * 3600 - 1);
+        let records = [ActivityRecord::Inactive { start: day_start as u64, end: day_end as u64 }];
+        let output = format_timeline(&build_timeline(&records, day_start, day_end), &New_York);
+        assert_eq!(event_totals(&output)["Inactive"], 23 * 3600 - 1);
+
+        // Chile skipped midnight when DST began; the day starts at 01:00
+        let skipped_midnight = NaiveDate::from_ymd_opt(2024, 9, 8).unwrap();
+        let (day_start, _) = day_bounds(skipped_midnight, &Santiago);
+        assert_eq!(format_clock_time(day_start, utc_offset(&Santiago, day_start)), "01:00:00");
+    }
+
+    #[test]
+    fn test_summarize_timeline() {
+        let records = [
+            ActivityRecord::Active { start: 1_000, end: 1_600 },
+            ActivityRecord::Inactive { start: 1_600, end: 2_200 },
+            ActivityRecord::Active { start: 2_200, end: 2_800 },
+            ActivityRecord::Suspended { start: 2_800, end: 3_000 },
+            ActivityRecord::Active { start: 3_000, end: 3_100 },
+        ];
+        let totals = summarize_timeline(&build_timeline(&records, 0, 4_000));
+        assert_eq!(totals.active_seconds, 1_300);
+        assert_eq!(totals.inactive_seconds, 600);
+        assert_eq!(totals.suspended_seconds, 200);
+        assert_eq!(totals.not_run_seconds, 1_000 + 900);
+        assert_eq!(totals.first_activity, Some(1_000));
+        assert_eq!(totals.last_activity, Some(3_100));
+        // The first of the two ten-minute stretches
+        let longest = totals.longest_active.unwrap();
+        assert_eq!((longest.start, longest.end), (1_000, 1_600));
+    }
+
+    #[test]
+    fn test_heatmap_row() {
+        // Monday 2024-01-08 in Berlin (UTC+1), active 09:30 - 11:15 local time
+        let berlin = FixedOffset::east_opt(3600).unwrap();
+        let (day_start, day_end) = day_bounds(NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(), &berlin);
+        let active_start = (day_start + 9 * 3600 + 1800) as u64;
+        let records = [ActivityRecord::Active { start: active_start, end: active_start + 6300 }];
+
+        let mut hours = vec![0i64; 24];
+        add_to_heatmap_row(&mut hours, &build_timeline(&records, day_start, day_end), &berlin, 3600);
+        assert_eq!(&hours[8..13], &[0, 1800, 3600, 900, 0]);
+
+        let mut quarters = vec![0i64; 96];
+        add_to_heatmap_row(&mut quarters, &build_timeline(&records, day_start, day_end), &berlin, 900);
+        assert_eq!(quarters[38], 900);
+        assert_eq!(quarters[44], 900);
+        assert_eq!(quarters.iter().sum::<i64>(), 6300);
+    }
+
+    #[test]
+    fn test_device_timeline() {
+        // This machine is active, then asleep while the laptop is still in use
+        let laptop = Some("laptop".to_string());
+        let records = vec![
+            (None, ActivityRecord::Active { start: 0, end: 100 }),
+            (None, ActivityRecord::Suspended { start: 100, end: 200 }),
+            (laptop.clone(), ActivityRecord::Active { start: 50, end: 150 }),
+        ];
+        let (timeline, device_active) = device_timeline(records, 0, 300);
+        let interval = |start, end, kind| TimelineInterval { start, end, kind };
+        assert_eq!(timeline, vec![
+            interval(0, 150, IntervalKind::Active),
+            interval(150, 200, IntervalKind::Suspended),
+            interval(200, 300, IntervalKind::NotRun),
+        ]);
+        assert_eq!(device_active, BTreeMap::from([(None, 100), (laptop, 100)]));
+
+        // Without merged devices the timeline is built as before and has no breakdown
+        let (_, device_active) = device_timeline(vec![(None, ActivityRecord::Active { start: 0, end: 100 })], 0, 300);
+        assert!(device_active.is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_neighbour_is_skipped() {
+        let dir = TestDir::new("aggregation");
+        let keyring = KeyRing::with_key(1, crate::utils::encryption::generate_key().unwrap());
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        let content = encode_log(&[ActivityRecord::Active { start: 100, end: 200 }.to_plaintext()], keyring.key(1).unwrap(), 1, day(2)).unwrap();
+        fs::write(dir.join("rs-fairsight(2025-01-02).txt"), &content).unwrap();
+        // Day 2's records presented as day 3's log fail to decrypt
+        fs::write(dir.join("rs-fairsight(2025-01-03).txt"), &content).unwrap();
+
+        let records = read_device_records_with(&dir, day(2), 0, 1_000, false, &keyring).unwrap();
+        assert_eq!(records, vec![(None, ActivityRecord::Active { start: 100, end: 200 })]);
+        assert!(read_device_records_with(&dir, day(3), 0, 1_000, false, &keyring).is_err());
+
+        // Rewritten files are read again rather than served from the cache
+        let content = encode_log(&[ActivityRecord::Active { start: 100, end: 300 }.to_plaintext()], keyring.key(1).unwrap(), 1, day(2)).unwrap();
+        fs::write(dir.join("rs-fairsight(2025-01-02).txt"), &content).unwrap();
+        let records = read_device_records_with(&dir, day(2), 0, 1_000, false, &keyring).unwrap();
+        assert_eq!(records, vec![(None, ActivityRecord::Active { start: 100, end: 300 })]);
+    }
+
+    #[test]
+    fn test_gaps_are_explained_by_the_next_startup() {
+        let interval = |start, end, kind| TimelineInterval { start, end, kind };
+
+        // Stopped without a shutdown record and restarted the next morning
+        let records = [
+            ActivityRecord::Startup { at: 0, boot_time: 0 },
+            ActivityRecord::Active { start: 0, end: 1_000 },
+            ActivityRecord::Heartbeat { at: 1_000 },
+            ActivityRecord::Startup { at: 90_000, boot_time: 0 },
+        ];
+        assert_eq!(build_timeline(&records, 0, 86_399), vec![
+            interval(0, 1_000, IntervalKind::Active),
+            interval(1_000, 86_399, IntervalKind::Crashed),
+        ]);
+
+        // Nothing tells how the run before the first startup ended, nor what follows the last run
+        let records = [
+            ActivityRecord::Startup { at: 50_000, boot_time: 0 },
+            ActivityRecord::Active { start: 50_000, end: 60_000 },
+        ];
+        assert_eq!(build_timeline(&records, 0, 86_399), vec![
+            interval(0, 50_000, IntervalKind::Unknown),
+            interval(50_000, 60_000, IntervalKind::Active),
+            interval(60_000, 86_399, IntervalKind::NotRun),
+        ]);
+    }
 }
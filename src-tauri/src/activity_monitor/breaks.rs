@@ -0,0 +1,211 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use super::records::ActivityRecord;
+
+/// When to remind the user to take a break, stored with the activity settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BreakReminderSettings {
+    pub enabled: bool,
+    /// Continuous work before a reminder
+    pub work_secs: u64,
+    /// Pause in input that counts as having taken the break
+    pub break_secs: u64,
+    /// Remind again this often while the break is not taken
+    pub repeat_secs: u64,
+}
+
+impl Default for BreakReminderSettings {
+    fn default() -> Self {
+        let mut settings = BreakPreset::FiftyTen.settings();
+        settings.enabled = false;
+        settings
+    }
+}
+
+/// Common break schedules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakPreset {
+    /// 50 minutes of work, then a 10 minute break
+    FiftyTen,
+    /// Every 20 minutes, look at something 20 feet away for 20 seconds
+    TwentyTwentyTwenty,
+}
+
+impl BreakPreset {
+    pub fn settings(self) -> BreakReminderSettings {
+        match self {
+            BreakPreset::FiftyTen => BreakReminderSettings {
+                enabled: true,
+                work_secs: 50 * 60,
+                break_secs: 10 * 60,
+                repeat_secs: 5 * 60,
+            },
+            BreakPreset::TwentyTwentyTwenty => BreakReminderSettings {
+                enabled: true,
+                work_secs: 20 * 60,
+                break_secs: 20,
+                repeat_secs: 2 * 60,
+            },
+        }
+    }
+}
+
+/// Something the break engine noticed, sent to the UI and written to the daily log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BreakEvent {
+    /// Work has gone on for `worked_secs` without a break; `repeat` counts earlier reminders ignored
+    Reminder { at: u64, worked_secs: u64, break_secs: u64, repeat: u32 },
+    /// Input paused long enough after a reminder
+    BreakTaken { reminded_at: u64, start: u64, end: u64 },
+}
+
+impl BreakEvent {
+    pub fn to_record(&self) -> ActivityRecord {
+        match *self {
+            BreakEvent::Reminder { at, worked_secs, repeat, .. } => {
+                ActivityRecord::BreakReminder { at, worked_secs, repeat }
+            }
+            BreakEvent::BreakTaken { reminded_at, start, end } => {
+                ActivityRecord::BreakTaken { reminded_at, start, end }
+            }
+        }
+    }
+}
+
+struct PendingReminder {
+    first_at: u64,
+    last_at: u64,
+    repeat: u32,
+}
+
+/// Tracks continuous work from input times. Breaks can be shorter than the inactivity threshold
+/// that ends an active stretch in the log, so work is measured independently of it.
+#[derive(Default)]
+pub struct BreakEngine {
+    work_start: Option<u64>,
+    last_input: Option<u64>,
+    pending: Option<PendingReminder>,
+}
+
+impl BreakEngine {
+    /// Feed the time of a user input and return what it triggered
+    pub fn on_input(&mut self, now: u64, settings: &BreakReminderSettings) -> Vec<BreakEvent> {
+        let mut events = Vec::new();
+        if !settings.enabled {
+            *self = Self::default();
+            return events;
+        }
+
+        match self.last_input {
+            // The clock went backwards; start over rather than guess
+            Some(last_input) if now < last_input => *self = Self::default(),
+            Some(last_input) if now - last_input >= settings.break_secs => {
+                if let Some(pending) = self.pending.take() {
+                    events.push(BreakEvent::BreakTaken {
+                        reminded_at: pending.first_at,
+                        start: last_input,
+                        end: now,
+                    });
+                }
+                self.work_start = None;
+            }
+            _ => {}
+        }
+        self.last_input = Some(now);
+        let work_start = *self.work_start.get_or_insert(now);
+
+        match &mut self.pending {
+            None if now - work_start >= settings.work_secs => {
+                self.pending = Some(PendingReminder { first_at: now, last_at: now, repeat: 0 });
+                events.push(BreakEvent::Reminder {
+                    at: now,
+                    worked_secs: now - work_start,
+                    break_secs: settings.break_secs,
+                    repeat: 0,
+                });
+            }
+            Some(pending) if now - pending.last_at >= settings.repeat_secs => {
+                pending.last_at = now;
+                pending.repeat += 1;
+                events.push(BreakEvent::Reminder {
+                    at: now,
+                    worked_secs: now - work_start,
+                    break_secs: settings.break_secs,
+                    repeat: pending.repeat,
+                });
+            }
+            _ => {}
+        }
+        events
+    }
+}
+
+static BREAK_ENGINE: Lazy<Mutex<BreakEngine>> = Lazy::new(|| Mutex::new(BreakEngine::default()));
+
+pub fn track_breaks(now: u64, settings: &BreakReminderSettings) -> Vec<BreakEvent> {
+    BREAK_ENGINE.lock().unwrap().on_input(now, settings)
+}
+
+/// "20 seconds", "10 minutes"
+pub fn describe_duration(secs: u64) -> String {
+    match secs {
+        1 => "1 second".to_string(),
+        0..=59 => format!("{} seconds", secs),
+        60..=119 => "1 minute".to_string(),
+        _ => format!("{} minutes", secs / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(
+        engine: &mut BreakEngine,
+        settings: &BreakReminderSettings,
+        times: impl IntoIterator<Item = u64>
+    ) -> Vec<BreakEvent> {
+        times.into_iter().flat_map(|time| engine.on_input(time, settings)).collect()
+    }
+
+    #[test]
+    fn test_reminder_then_break() {
+        let settings = BreakPreset::FiftyTen.settings();
+        let mut engine = BreakEngine::default();
+
+        // Input every 30 seconds for 50 minutes
+        let events = feed(&mut engine, &settings, (0..=100).map(|i| i * 30));
+        assert_eq!(events, vec![BreakEvent::Reminder { at: 3000, worked_secs: 3000, break_secs: 600, repeat: 0 }]);
+
+        // Working on for another 5 minutes repeats the reminder
+        let events = feed(&mut engine, &settings, (101..=110).map(|i| i * 30));
+        assert_eq!(events, vec![BreakEvent::Reminder { at: 3300, worked_secs: 3300, break_secs: 600, repeat: 1 }]);
+
+        // A 3 minute pause is not enough; 10 minutes is
+        assert!(feed(&mut engine, &settings, [3480, 3490]).is_empty());
+        let events = feed(&mut engine, &settings, [4300]);
+        assert_eq!(events, vec![BreakEvent::BreakTaken { reminded_at: 3000, start: 3490, end: 4300 }]);
+
+        // Work is counted from the end of the break
+        assert!(feed(&mut engine, &settings, (1..=99).map(|i| 4300 + i * 30)).is_empty());
+    }
+
+    #[test]
+    fn test_short_breaks_and_disabled() {
+        let settings = BreakPreset::TwentyTwentyTwenty.settings();
+        let mut engine = BreakEngine::default();
+        let events = feed(&mut engine, &settings, (0..=120).map(|i| i * 10).chain([1230]));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1], BreakEvent::BreakTaken { reminded_at: 1200, start: 1200, end: 1230 });
+
+        let disabled = BreakReminderSettings::default();
+        assert!(feed(&mut engine, &disabled, (0..=400).map(|i| 2000 + i * 10)).is_empty());
+        assert_eq!(describe_duration(20), "20 seconds");
+        assert_eq!(describe_duration(600), "10 minutes");
+    }
+}
@@ -0,0 +1,307 @@
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::file_utils::atomic_write_with_backup;
+
+const CALENDAR_FILE_NAME: &str = "activity_calendar.json";
+/// Longest all-day event taken from a calendar, to guard against malformed end dates
+const MAX_EVENT_DAYS: i64 = 366;
+/// Yearly events without an end are repeated this many years past the import
+const OPEN_ENDED_YEARS: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DayOffKind {
+    /// Public holidays
+    Holiday,
+    /// Vacation, sick leave and other personal days off
+    Leave,
+}
+
+/// One day of an all-day calendar event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarDay {
+    pub date: String,
+    pub kind: DayOffKind,
+    pub summary: String,
+    /// Name of the calendar it was imported from
+    pub source: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub uid: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarImportReport {
+    pub source: String,
+    pub imported: usize,
+    /// Days already present from an earlier import
+    pub duplicates: usize,
+    /// Events that are not all-day or could not be read
+    pub skipped: usize,
+}
+
+/// Days converted from a calendar file, before they are stored
+#[derive(Debug, Default)]
+pub struct ParsedCalendar {
+    pub days: Vec<CalendarDay>,
+    pub skipped: usize,
+}
+
+// The calendar file is rewritten as a whole, so changes must not interleave
+static CALENDAR_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn calendar_file_path(log_dir: &Path) -> PathBuf {
+    log_dir.join(CALENDAR_FILE_NAME)
+}
+
+fn read_calendar(log_dir: &Path) -> Result<Vec<CalendarDay>, String> {
+    let path = calendar_file_path(log_dir);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| format!("Invalid calendar file {:?}: {}", path, e)),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+fn write_calendar(log_dir: &Path, days: &[CalendarDay]) -> Result<(), String> {
+    let path = calendar_file_path(log_dir);
+    let json = serde_json::to_string_pretty(days).map_err(|e| e.to_string())?;
+    atomic_write_with_backup(&path, json.as_bytes(), None).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Calendar days from `start` to `end` inclusive, by date
+pub fn days_off(log_dir: &Path, start: NaiveDate, end: NaiveDate) -> Result<BTreeMap<NaiveDate, Vec<CalendarDay>>, String> {
+    let mut days: BTreeMap<NaiveDate, Vec<CalendarDay>> = BTreeMap::new();
+    for day in read_calendar(log_dir)? {
+        if let Ok(date) = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d") {
+            if date >= start && date <= end {
+                days.entry(date).or_default().push(day);
+            }
+        }
+    }
+    Ok(days)
+}
+
+/// Read an .ics file and add its all-day events as days off of `kind`
+pub fn import_calendar(
+    log_dir: &Path,
+    path: &Path,
+    kind: DayOffKind,
+    name: Option<String>
+) -> Result<CalendarImportReport, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let source = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+    let repeat_until = NaiveDate::from_ymd_opt(Local::now().year() + OPEN_ENDED_YEARS, 12, 31).unwrap_or(NaiveDate::MAX);
+    let parsed = parse_ics(&content, kind, &source, repeat_until)?;
+
+    let _guard = CALENDAR_LOCK.lock().unwrap();
+    let mut days = read_calendar(log_dir)?;
+    let mut known: HashSet<(String, String, String)> =
+        days.iter().map(|d| (d.source.clone(), d.uid.clone(), d.date.clone())).collect();
+    let (mut imported, mut duplicates) = (0, 0);
+    for day in parsed.days {
+        if known.insert((day.source.clone(), day.uid.clone(), day.date.clone())) {
+            days.push(day);
+            imported += 1;
+        } else {
+            duplicates += 1;
+        }
+    }
+    days.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.source.cmp(&b.source)));
+    write_calendar(log_dir, &days)?;
+
+    crate::log_info!(
+        "calendar",
+        "Imported {} days off from {} ({} duplicates, {} skipped)",
+        imported, source, duplicates, parsed.skipped
+    );
+    Ok(CalendarImportReport { source, imported, duplicates, skipped: parsed.skipped })
+}
+
+/// Remove every day imported from `source`. Returns how many were removed.
+pub fn remove_calendar(log_dir: &Path, source: &str) -> Result<usize, String> {
+    let _guard = CALENDAR_LOCK.lock().unwrap();
+    let mut days = read_calendar(log_dir)?;
+    let before = days.len();
+    days.retain(|day| day.source != source);
+    let removed = before - days.len();
+    if removed > 0 {
+        write_calendar(log_dir, &days)?;
+    }
+    Ok(removed)
+}
+
+/// Undo RFC 5545 text escaping
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push(' '),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// A DATE value such as "20251224"; date-times such as "20251224T090000Z" are not all-day
+fn parse_ics_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y%m%d").ok()
+}
+
+#[derive(Default)]
+struct IcsEvent {
+    start: Option<String>,
+    end: Option<String>,
+    summary: String,
+    uid: String,
+    rrule: Option<String>,
+    cancelled: bool,
+}
+
+/// First day and the number of days of each occurrence, or `None` if the event cannot be used
+fn event_occurrences(event: &IcsEvent, repeat_until: NaiveDate) -> Option<Vec<(NaiveDate, i64)>> {
+    let start = parse_ics_date(event.start.as_deref()?)?;
+    // DTEND of an all-day event is the day after it ends
+    let days = match event.end.as_deref() {
+        Some(end) => (parse_ics_date(end)? - start).num_days(),
+        None => 1,
+    };
+    if !(1..=MAX_EVENT_DAYS).contains(&days) {
+        return None;
+    }
+
+    let Some(rrule) = &event.rrule else { return Some(vec![(start, days)]) };
+    let mut frequency = None;
+    let (mut interval, mut count, mut until) = (1, None, repeat_until);
+    for part in rrule.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => frequency = Some(value.to_ascii_uppercase()),
+            "INTERVAL" => interval = value.parse().ok().filter(|i| *i > 0)?,
+            "COUNT" => count = Some(value.parse::<usize>().ok()?),
+            "UNTIL" => until = until.min(parse_ics_date(value.get(..8)?)?),
+            _ => {}
+        }
+    }
+    // Holiday calendars repeat yearly; anything else is not a day off
+    if frequency.as_deref() != Some("YEARLY") {
+        return None;
+    }
+
+    let mut occurrences = Vec::new();
+    let mut year = start.year();
+    while count.is_none_or(|count| occurrences.len() < count) {
+        // An event on 29 February only occurs in leap years
+        if let Some(date) = start.with_year(year) {
+            if date > until {
+                break;
+            }
+            occurrences.push((date, days));
+        } else if year > until.year() {
+            break;
+        }
+        year += interval;
+    }
+    Some(occurrences)
+}
+
+/// Convert the all-day events of an iCalendar file into calendar days
+pub fn parse_ics(content: &str, kind: DayOffKind, source: &str, repeat_until: NaiveDate) -> Result<ParsedCalendar, String> {
+    // Long lines are folded onto continuation lines starting with a space or tab
+    let unfolded = content.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    if !unfolded.trim_start().starts_with("BEGIN:VCALENDAR") {
+        return Err("Not an iCalendar file".to_string());
+    }
+
+    let mut parsed = ParsedCalendar::default();
+    let mut event: Option<IcsEvent> = None;
+    for line in unfolded.lines() {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        // Parameters such as ";VALUE=DATE" follow the property name
+        let property = name.split(';').next().unwrap_or_default().to_ascii_uppercase();
+        match (property.as_str(), event.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => event = Some(IcsEvent::default()),
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                let Some(finished) = event.take() else { continue };
+                if finished.cancelled {
+                    continue;
+                }
+                let Some(occurrences) = event_occurrences(&finished, repeat_until) else {
+                    parsed.skipped += 1;
+                    continue;
+                };
+                for (first, days) in occurrences {
+                    for offset in 0..days {
+                        parsed.days.push(CalendarDay {
+                            date: (first + Duration::days(offset)).format("%Y-%m-%d").to_string(),
+                            kind,
+                            summary: finished.summary.clone(),
+                            source: source.to_string(),
+                            uid: finished.uid.clone(),
+                        });
+                    }
+                }
+            }
+            ("DTSTART", Some(event)) => event.start = Some(value.to_string()),
+            ("DTEND", Some(event)) => event.end = Some(value.to_string()),
+            ("SUMMARY", Some(event)) => event.summary = unescape_text(value),
+            ("UID", Some(event)) => event.uid = value.trim().to_string(),
+            ("RRULE", Some(event)) => event.rrule = Some(value.trim().to_string()),
+            ("STATUS", Some(event)) => event.cancelled = value.trim().eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+        BEGIN:VEVENT\r\nUID:xmas@example\r\nDTSTART;VALUE=DATE:20241225\r\nDTEND;VALUE=DATE:20241227\r\n\
+        SUMMARY:Christmas Day\\, Boxing \r\n Day\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:new-year@example\r\nDTSTART;VALUE=DATE:20240101\r\nRRULE:FREQ=YEARLY;COUNT=3\r\n\
+        SUMMARY:New Year\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:leap@example\r\nDTSTART;VALUE=DATE:20240229\r\nRRULE:FREQ=YEARLY\r\n\
+        SUMMARY:Leap day\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:meeting@example\r\nDTSTART:20241224T090000Z\r\nDTEND:20241224T100000Z\r\n\
+        SUMMARY:Meeting\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parse_ics() {
+        let until = NaiveDate::from_ymd_opt(2032, 12, 31).unwrap();
+        let parsed = parse_ics(CALENDAR, DayOffKind::Holiday, "holidays", until).unwrap();
+        let dates: Vec<(&str, &str)> = parsed.days.iter().map(|d| (d.date.as_str(), d.summary.as_str())).collect();
+        assert_eq!(
+            dates,
+            vec![
+                ("2024-12-25", "Christmas Day, Boxing Day"),
+                ("2024-12-26", "Christmas Day, Boxing Day"),
+                ("2024-01-01", "New Year"),
+                ("2025-01-01", "New Year"),
+                ("2026-01-01", "New Year"),
+                ("2024-02-29", "Leap day"),
+                ("2028-02-29", "Leap day"),
+                ("2032-02-29", "Leap day"),
+            ]
+        );
+        // The timed meeting is not a day off
+        assert_eq!(parsed.skipped, 1);
+        assert!(parse_ics("not a calendar", DayOffKind::Leave, "leave", until).is_err());
+    }
+}
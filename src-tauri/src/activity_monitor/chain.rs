@@ -0,0 +1,339 @@
+use chrono::NaiveDate;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::key_manager::KeyRing;
+use super::records::{explain_log_failure, read_log_file, ActivityRecord};
+
+/// Hash the first chained record points to when no earlier log exists
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Plaintext of a record in a chained log: the record plus the hash of the record written before it
+#[derive(Serialize)]
+struct ChainedRecordRef<'a> {
+    prev: &'a str,
+    record: &'a ActivityRecord,
+}
+
+#[derive(Deserialize)]
+struct ChainedRecord {
+    prev: String,
+    record: serde_json::Value,
+}
+
+/// Position of one decrypted record in the hash chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainLink {
+    /// Hash of the preceding record, absent for records written before chaining
+    pub prev: Option<String>,
+    /// Hash of this record's plaintext
+    pub hash: String,
+}
+
+/// Hex SHA-256 of a record's plaintext; covers the previous hash for chained records
+pub fn record_hash(plaintext: &str) -> String {
+    hex::encode(digest(&SHA256, plaintext.as_bytes()))
+}
+
+pub fn chain_plaintext(record: &ActivityRecord, prev: &str) -> String {
+    // Serializing plain data cannot fail
+    serde_json::to_string(&ChainedRecordRef { prev, record }).unwrap_or_default()
+}
+
+/// Splits a decrypted record into its chain link and the record it carries
+pub fn split_chained(plaintext: &str) -> (ChainLink, Result<ActivityRecord, String>) {
+    let hash = record_hash(plaintext);
+    let line = plaintext.trim();
+    if !line.starts_with("{\"prev\"") {
+        return (ChainLink { prev: None, hash }, ActivityRecord::from_plaintext(line));
+    }
+
+    match serde_json::from_str::<ChainedRecord>(line) {
+        Ok(chained) => {
+            let record = serde_json::from_value(chained.record)
+                .map_err(|e| format!("Invalid record '{}': {}", line, e));
+            (ChainLink { prev: Some(chained.prev), hash }, record)
+        }
+        Err(e) => (ChainLink { prev: None, hash }, Err(format!("Invalid chained record '{}': {}", line, e))),
+    }
+}
+
+/// Daily logs in `dir` by date; backups and damaged copies are left out
+pub fn day_log_files(dir: &Path) -> BTreeMap<NaiveDate, PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let date_str = name.strip_prefix("rs-fairsight(")?.strip_suffix(").txt")?;
+            let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok()?;
+            Some((date, entry.path()))
+        })
+        .collect()
+}
+
+/// Hash of the last record in a log, `None` when it holds no records
+pub fn final_hash(file_path: &Path, keyring: &KeyRing) -> Result<Option<String>, String> {
+    let contents = read_log_file(file_path, keyring).map_err(|e| e.to_string())?;
+    Ok(contents.links.last().map(|link| link.hash.clone()))
+}
+
+/// Date and final hash of the latest log before `date` that holds records
+fn latest_final_hash(log_dir: &Path, date: NaiveDate, keyring: &KeyRing) -> Result<Option<(NaiveDate, String)>, String> {
+    for (&log_date, path) in day_log_files(log_dir).range(..date).rev() {
+        if let Some(hash) = final_hash(path, keyring).map_err(|e| format!("{:?}: {}", path, e))? {
+            return Ok(Some((log_date, hash)));
+        }
+    }
+    Ok(None)
+}
+
+/// Hash a new day's first record continues from: the last record of the latest earlier log with records
+pub fn chain_anchor(log_dir: &Path, date: NaiveDate, keyring: &KeyRing) -> Result<String, String> {
+    Ok(latest_final_hash(log_dir, date, keyring)?.map_or(GENESIS_HASH.to_string(), |(_, hash)| hash))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainIssueKind {
+    /// A record fails to decrypt, or sits in a position or day it was not written for
+    Modified,
+    /// A record's previous hash does not match the record before it
+    BrokenLink,
+    /// The day does not continue from the previous log: records or whole days were removed
+    Gap,
+    /// A record without a previous hash follows chained records
+    Unchained,
+    /// The log ends in an incomplete record
+    Truncated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainIssue {
+    pub date: String,
+    /// Index of the record within its day, when the issue concerns one record
+    pub record: Option<usize>,
+    pub kind: ChainIssueKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayVerification {
+    pub date: String,
+    pub records: usize,
+    /// Records written before chaining existed, which the chain cannot vouch for
+    pub unchained_records: usize,
+    /// Hash of the day's last record; matches the next day's anchor while the logs are intact
+    pub final_hash: Option<String>,
+}
+
+/// Result of walking the hash chain over a range of days.
+/// Records deleted from the end of the last day leave nothing behind that the chain could
+/// catch, so `valid` does not cover them; compare `final_hash` with an earlier verification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainVerification {
+    pub start: String,
+    pub end: String,
+    pub days: Vec<DayVerification>,
+    pub issues: Vec<ChainIssue>,
+    pub valid: bool,
+    /// Hash of the last record in the range, for pinning the end of the chain
+    pub final_hash: Option<String>,
+}
+
+/// Walk the chain through every daily log from `start` to `end`, starting from the final hash
+/// of the latest log before `start`
+pub fn verify_log_chain(
+    log_dir: &Path,
+    start: NaiveDate,
+    end: NaiveDate,
+    keyring: &KeyRing
+) -> ChainVerification {
+    let files = day_log_files(log_dir);
+    let mut issues = Vec::new();
+    let mut days = Vec::new();
+
+    // Final hash of each log walked so far, to tell which day a broken anchor points to
+    let mut final_hashes: HashMap<String, NaiveDate> = HashMap::new();
+    let mut expected: Option<(String, Option<NaiveDate>)> = match latest_final_hash(log_dir, start, keyring) {
+        Ok(Some((date, hash))) => {
+            final_hashes.insert(hash.clone(), date);
+            Some((hash, Some(date)))
+        }
+        Ok(None) => Some((GENESIS_HASH.to_string(), None)),
+        Err(e) => {
+            issues.push(ChainIssue {
+                date: start.to_string(),
+                record: None,
+                kind: ChainIssueKind::Modified,
+                message: format!("Cannot read the logs before {}: {}", start, e),
+            });
+            None
+        }
+    };
+
+    for (&date, path) in files.range(start..=end) {
+        let day = date.to_string();
+        let contents = match read_log_file(path, keyring) {
+            Ok(contents) => contents,
+            Err(e) => {
+                let message = explain_log_failure(path, keyring).unwrap_or_else(|| e.to_string());
+                issues.push(ChainIssue { date: day, record: None, kind: ChainIssueKind::Modified, message });
+                // The next day's anchor cannot be checked against an unreadable log
+                expected = None;
+                continue;
+            }
+        };
+
+        let mut unchained_records = 0;
+        let mut chained = false;
+        let mut previous: Option<&str> = None;
+        for (index, link) in contents.links.iter().enumerate() {
+            match (&link.prev, previous) {
+                (None, _) if chained => issues.push(ChainIssue {
+                    date: day.clone(),
+                    record: Some(index),
+                    kind: ChainIssueKind::Unchained,
+                    message: format!("Record #{} carries no previous hash although earlier records do", index),
+                }),
+                (None, _) => unchained_records += 1,
+                (Some(prev), Some(previous)) => {
+                    if prev != previous {
+                        issues.push(ChainIssue {
+                            date: day.clone(),
+                            record: Some(index),
+                            kind: ChainIssueKind::BrokenLink,
+                            message: format!("Record #{} does not follow record #{}", index, index - 1),
+                        });
+                    }
+                    chained = true;
+                }
+                (Some(prev), None) => {
+                    issues.extend(check_anchor(&day, prev, expected.as_ref(), &final_hashes));
+                    chained = true;
+                }
+            }
+            previous = Some(&link.hash);
+        }
+
+        if contents.truncated {
+            issues.push(ChainIssue {
+                date: day.clone(),
+                record: Some(contents.links.len()),
+                kind: ChainIssueKind::Truncated,
+                message: "The log ends in an incomplete record".to_string(),
+            });
+        }
+
+        let final_hash = contents.links.last().map(|link| link.hash.clone());
+        if let Some(hash) = &final_hash {
+            final_hashes.insert(hash.clone(), date);
+            expected = Some((hash.clone(), Some(date)));
+        }
+        days.push(DayVerification { date: day, records: contents.links.len(), unchained_records, final_hash });
+    }
+
+    ChainVerification {
+        start: start.to_string(),
+        end: end.to_string(),
+        valid: issues.is_empty(),
+        final_hash: days.iter().rev().find_map(|day| day.final_hash.clone()),
+        days,
+        issues,
+    }
+}
+
+/// Check that a day's first record continues from the log before it
+fn check_anchor(
+    day: &str,
+    prev: &str,
+    expected: Option<&(String, Option<NaiveDate>)>,
+    final_hashes: &HashMap<String, NaiveDate>
+) -> Option<ChainIssue> {
+    let (expected_hash, expected_date) = expected?;
+    if prev == expected_hash {
+        return None;
+    }
+
+    let after = expected_date.map_or("the start of the chain".to_string(), |date| format!("the {} log", date));
+    let message = match final_hashes.get(prev) {
+        Some(date) => format!("The first record continues from the {} log instead of {}: logs in between were removed", date, after),
+        None if prev == GENESIS_HASH => format!("The first record starts a new chain instead of continuing from {}", after),
+        None => format!("The first record does not continue from {}: its last records or an entire day were removed or changed", after),
+    };
+    Some(ChainIssue { date: day.to_string(), record: Some(0), kind: ChainIssueKind::Gap, message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use crate::activity_monitor::records::{encode_log, LogHeader};
+    use crate::utils::encryption::generate_key;
+
+    fn write_day(dir: &Path, date: NaiveDate, prev: &str, keyring: &KeyRing, ends: &[u64]) -> String {
+        let mut plaintexts = Vec::new();
+        let mut prev = prev.to_string();
+        for &end in ends {
+            let plaintext = chain_plaintext(&ActivityRecord::Active { start: 0, end }, &prev);
+            prev = record_hash(&plaintext);
+            plaintexts.push(plaintext);
+        }
+        let content = encode_log(&plaintexts, keyring.key(1).unwrap(), 1, date).unwrap();
+        fs::write(dir.join(format!("rs-fairsight({}).txt", date)), content).unwrap();
+        prev
+    }
+
+    #[test]
+    fn test_chain_across_days() {
+        let dir = TestDir::new("chain");
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+
+        let first = write_day(&dir, day(1), GENESIS_HASH, &keyring, &[1, 2]);
+        assert_eq!(chain_anchor(&dir, day(2), &keyring).unwrap(), first);
+        let second = write_day(&dir, day(2), &first, &keyring, &[3, 4]);
+        let third = write_day(&dir, day(3), &second, &keyring, &[5, 6]);
+
+        let report = verify_log_chain(&dir, day(1), day(3), &keyring);
+        assert!(report.valid, "{:?}", report.issues);
+        assert_eq!(report.days.len(), 3);
+        assert_eq!(report.days[1].final_hash.as_deref(), Some(second.as_str()));
+        assert_eq!(report.final_hash.as_deref(), Some(third.as_str()));
+
+        // Cutting the last record of the last day leaves the chain intact; only the final hash changes
+        write_day(&dir, day(3), &second, &keyring, &[5]);
+        let report = verify_log_chain(&dir, day(1), day(3), &keyring);
+        assert!(report.valid);
+        assert_ne!(report.final_hash.as_deref(), Some(third.as_str()));
+
+        // Dropping the last record of day 2 breaks day 3's anchor
+        write_day(&dir, day(2), &first, &keyring, &[3]);
+        let report = verify_log_chain(&dir, day(2), day(3), &keyring);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, ChainIssueKind::Gap);
+        assert_eq!(report.issues[0].date, "2025-01-03");
+
+        // Removing day 2 entirely makes day 3 skip over it
+        fs::remove_file(dir.join("rs-fairsight(2025-01-02).txt")).unwrap();
+        let report = verify_log_chain(&dir, day(1), day(3), &keyring);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, ChainIssueKind::Gap);
+
+        // A record rewritten without its chain is caught inside the day
+        let plaintexts = vec![
+            chain_plaintext(&ActivityRecord::Active { start: 0, end: 1 }, GENESIS_HASH),
+            chain_plaintext(&ActivityRecord::Active { start: 0, end: 9 }, GENESIS_HASH),
+        ];
+        let content = encode_log(&plaintexts, keyring.key(1).unwrap(), 1, day(1)).unwrap();
+        assert_eq!(LogHeader::parse(&content), Some(LogHeader::current(1)));
+        fs::write(dir.join("rs-fairsight(2025-01-01).txt"), content).unwrap();
+        let report = verify_log_chain(&dir, day(1), day(1), &keyring);
+        assert_eq!(report.issues[0].kind, ChainIssueKind::BrokenLink);
+        assert_eq!(report.issues[0].record, Some(1));
+    }
+}
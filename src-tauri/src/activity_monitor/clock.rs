@@ -0,0 +1,193 @@
+use super::core::get_current_time;
+
+/// Wall-clock differences smaller than this are rounding and NTP slewing, not jumps
+const CLOCK_JUMP_TOLERANCE_SECS: i64 = 5;
+/// Shorter sleeps are indistinguishable from scheduling delays
+const SUSPEND_THRESHOLD_SECS: i64 = 30;
+
+/// Readings of the wall clock and two monotonic clocks taken together.
+/// `boot_ms` keeps counting while the machine sleeps and `awake_ms` does not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    pub wall: u64,
+    pub boot_ms: u64,
+    pub awake_ms: u64,
+}
+
+pub fn sample_clocks() -> ClockSample {
+    let (boot_ms, awake_ms) = platform::monotonic_ms();
+    ClockSample {
+        wall: get_current_time(),
+        boot_ms,
+        awake_ms,
+    }
+}
+
+/// When the machine booted, by the wall clock. Where no clock counts sleep this is when the
+/// process started instead.
+pub fn boot_time() -> u64 {
+    let sample = sample_clocks();
+    sample.wall.saturating_sub(sample.boot_ms / 1000)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEvent {
+    /// The machine slept from `start` to `end` (wall-clock seconds after any adjustment)
+    Suspended { start: u64, end: u64 },
+    /// The wall clock was set `offset_secs` forward (or backward if negative), noticed at `at`
+    Adjusted { at: u64, offset_secs: i64 },
+}
+
+/// Compares consecutive clock samples to tell real elapsed time from clock changes and sleep
+#[derive(Debug, Default)]
+pub struct ClockWatch {
+    last: Option<ClockSample>,
+}
+
+impl ClockWatch {
+    pub fn check(&mut self, sample: ClockSample) -> Vec<ClockEvent> {
+        let mut events = Vec::new();
+        let Some(last) = self.last.replace(sample) else { return events };
+
+        let wall_elapsed = sample.wall as i64 - last.wall as i64;
+        let boot_elapsed = (sample.boot_ms.saturating_sub(last.boot_ms) / 1000) as i64;
+        let awake_elapsed = (sample.awake_ms.saturating_sub(last.awake_ms) / 1000) as i64;
+
+        let offset_secs = wall_elapsed - boot_elapsed;
+        if offset_secs.abs() > CLOCK_JUMP_TOLERANCE_SECS {
+            events.push(ClockEvent::Adjusted { at: sample.wall, offset_secs });
+        }
+
+        // The sleep ended at most one sample interval ago; place it just before this sample
+        let suspended = boot_elapsed - awake_elapsed;
+        if suspended >= SUSPEND_THRESHOLD_SECS {
+            events.push(ClockEvent::Suspended {
+                start: sample.wall.saturating_sub(suspended as u64),
+                end: sample.wall,
+            });
+        }
+        events
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::os::raw::{c_int, c_long};
+
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: c_long,
+        tv_nsec: c_long,
+    }
+
+    const CLOCK_MONOTONIC: c_int = 1;
+    const CLOCK_BOOTTIME: c_int = 7;
+
+    extern "C" {
+        fn clock_gettime(clock_id: c_int, tp: *mut Timespec) -> c_int;
+    }
+
+    fn read_ms(clock_id: c_int) -> u64 {
+        let mut ts = Timespec { tv_sec: 0, tv_nsec: 0 };
+        if unsafe { clock_gettime(clock_id, &mut ts) } != 0 {
+            return 0;
+        }
+        ts.tv_sec as u64 * 1000 + ts.tv_nsec as u64 / 1_000_000
+    }
+
+    /// CLOCK_BOOTTIME includes suspend, CLOCK_MONOTONIC does not
+    pub fn monotonic_ms() -> (u64, u64) {
+        (read_ms(CLOCK_BOOTTIME), read_ms(CLOCK_MONOTONIC))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use winapi::um::realtimeapiset::QueryUnbiasedInterruptTime;
+    use winapi::um::sysinfoapi::GetTickCount64;
+
+    /// GetTickCount64 includes sleep; the unbiased interrupt time (100 ns units) does not
+    pub fn monotonic_ms() -> (u64, u64) {
+        let mut unbiased = 0u64;
+        unsafe {
+            let boot_ms = GetTickCount64();
+            if QueryUnbiasedInterruptTime(&mut unbiased) == 0 {
+                return (boot_ms, boot_ms);
+            }
+            (boot_ms, unbiased / 10_000)
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    #[repr(C)]
+    #[derive(Default)]
+    struct MachTimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+
+    extern "C" {
+        fn mach_absolute_time() -> u64;
+        fn mach_continuous_time() -> u64;
+        fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+    }
+
+    /// mach_continuous_time includes sleep, mach_absolute_time does not
+    pub fn monotonic_ms() -> (u64, u64) {
+        let mut info = MachTimebaseInfo::default();
+        unsafe {
+            if mach_timebase_info(&mut info) != 0 || info.denom == 0 {
+                info = MachTimebaseInfo { numer: 1, denom: 1 };
+            }
+            let to_ms = |ticks: u64| (ticks as u128 * info.numer as u128 / info.denom as u128 / 1_000_000) as u64;
+            (to_ms(mach_continuous_time()), to_ms(mach_absolute_time()))
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+mod platform {
+    use once_cell::sync::Lazy;
+    use std::time::Instant;
+
+    static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+    /// No clock that counts sleep, so suspends cannot be told apart from clock changes
+    pub fn monotonic_ms() -> (u64, u64) {
+        let elapsed = START.elapsed().as_millis() as u64;
+        (elapsed, elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(wall: u64, boot_secs: u64, awake_secs: u64) -> ClockSample {
+        ClockSample { wall, boot_ms: boot_secs * 1000, awake_ms: awake_secs * 1000 }
+    }
+
+    #[test]
+    fn test_clock_events() {
+        let mut watch = ClockWatch::default();
+        assert!(watch.check(sample(10_000, 100, 100)).is_empty());
+        assert!(watch.check(sample(10_010, 110, 110)).is_empty());
+
+        // Clock set back an hour between two samples ten seconds apart
+        assert_eq!(
+            watch.check(sample(6_420, 120, 120)),
+            vec![ClockEvent::Adjusted { at: 6_420, offset_secs: -3_600 }]
+        );
+
+        // Two hours asleep: the wall clock and boot time advance, awake time does not
+        assert_eq!(
+            watch.check(sample(6_420 + 7_210, 120 + 7_210, 130)),
+            vec![ClockEvent::Suspended { start: 6_430, end: 13_630 }]
+        );
+
+        // Small differences are not jumps
+        assert!(watch.check(sample(13_643, 7_342, 142)).is_empty());
+    }
+}
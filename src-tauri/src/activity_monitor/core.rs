@@ -2,19 +2,39 @@ use chrono::Local;
 use lazy_static::lazy_static;
 use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::utils::app_state::notify_break;
 use crate::utils::file_utils::save_backup;
+use crate::utils::key_manager::active_keyring;
+use super::breaks::track_breaks;
+use super::clock::{boot_time, sample_clocks, ClockEvent, ClockWatch};
+use super::lifecycle::{note_previous_run, HEARTBEAT_INTERVAL_SECS};
+use super::foreground::{sample_foreground_app, ForegroundApp};
+use super::records::ActivityRecord;
+use super::settings::load_activity_settings;
 use super::types::{INACTIVE_TIME_PERIOD};
-use super::file_operations::{get_platform_directories, write_encrypted_message_to_file, should_create_backup, get_current_backup_count};
+use super::file_operations::{get_platform_directories, should_create_backup, get_current_backup_count};
+use super::log_writer::{append_activity_record, sync_activity_log};
 
 lazy_static! {
     static ref LAST_TRACKED_INACTIVE_TIME: Mutex<u64> = Mutex::new(0);
     static ref LAST_TRACKED_ACTIVE_START_TIME: Mutex<u64> = Mutex::new(0);
     static ref LAST_TRACKED_ACTIVE_END_TIME: Mutex<u64> = Mutex::new(0);
+    // Focused application of the current active stretch, when it gained focus and when it was last seen
+    static ref LAST_TRACKED_APP: Mutex<Option<(ForegroundApp, u64, u64)>> = Mutex::new(None);
+    static ref CLOCK_WATCH: Mutex<ClockWatch> = Mutex::new(ClockWatch::default());
+    // Log file and UTC offset of the last offset record written
+    static ref LAST_UTC_OFFSET: Mutex<Option<(PathBuf, i32)>> = Mutex::new(None);
+    static ref LAST_HEARTBEAT: Mutex<u64> = Mutex::new(0);
+    static ref TRACKING_STARTED_AT: Mutex<u64> = Mutex::new(0);
 }
 
+// Clocks are also checked between inputs so sleeps and clock changes are noticed while idle
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 pub fn get_current_time() -> u64 {
     let now = SystemTime::now();
 
@@ -25,9 +45,134 @@ pub fn get_current_time() -> u64 {
 }
 
 pub fn initialize_time_tracking() {
-    *LAST_TRACKED_INACTIVE_TIME.lock().unwrap() = get_current_time();
-    *LAST_TRACKED_ACTIVE_START_TIME.lock().unwrap() = get_current_time();
-    *LAST_TRACKED_ACTIVE_END_TIME.lock().unwrap() = get_current_time();
+    let now = get_current_time();
+    *LAST_TRACKED_INACTIVE_TIME.lock().unwrap() = now;
+    *LAST_TRACKED_ACTIVE_START_TIME.lock().unwrap() = now;
+    *LAST_TRACKED_ACTIVE_END_TIME.lock().unwrap() = now;
+    *TRACKING_STARTED_AT.lock().unwrap() = now;
+    CLOCK_WATCH.lock().unwrap().check(sample_clocks());
+}
+
+/// Write the startup record and keep writing heartbeats. Only the instance that keeps running
+/// may do this: a second launch exits before setup and must not append to the day log.
+pub fn start_lifecycle_tracking() {
+    // The first record starts where the startup record is, so the timeline has no gap between
+    let started_at = *TRACKING_STARTED_AT.lock().unwrap();
+    if let Err(e) = record_startup(started_at) {
+        crate::log_error!("time_tracker", "Failed to record startup: {}", e);
+    }
+
+    std::thread::spawn(|| loop {
+        std::thread::sleep(CLOCK_CHECK_INTERVAL);
+        if let Err(e) = check_clocks() {
+            crate::log_error!("time_tracker", "Failed to record clock change: {}", e);
+        }
+        if let Err(e) = write_heartbeat() {
+            crate::log_error!("time_tracker", "Failed to record heartbeat: {}", e);
+        }
+    });
+}
+
+/// Note how the previous run ended, then mark the start of this one at `at`
+fn record_startup(at: u64) -> io::Result<()> {
+    let (log_dir, _) = get_platform_directories()?;
+    let boot_time = boot_time();
+    match active_keyring() {
+        Ok(keyring) => note_previous_run(&log_dir, boot_time, at, &keyring),
+        Err(e) => crate::log_warning!("time_tracker", "Cannot check how the previous run ended: {}", e),
+    }
+    *LAST_HEARTBEAT.lock().unwrap() = at;
+    append_with_offset(&current_log_path()?, &ActivityRecord::Startup { at, boot_time })
+}
+
+/// Mark the tracker as alive, at most once per heartbeat interval
+fn write_heartbeat() -> io::Result<()> {
+    let at = get_current_time();
+    let mut last_heartbeat = LAST_HEARTBEAT.lock().unwrap();
+    if at.abs_diff(*last_heartbeat) < HEARTBEAT_INTERVAL_SECS {
+        return Ok(());
+    }
+    *last_heartbeat = at;
+    append_with_offset(&current_log_path()?, &ActivityRecord::Heartbeat { at })
+}
+
+/// Mark an intentional shutdown and flush the log before the app exits
+pub fn record_shutdown() -> io::Result<()> {
+    let path = current_log_path()?;
+    if let Some(app_record) = end_focus_stretch() {
+        append_with_offset(&path, &app_record)?;
+    }
+    append_with_offset(&path, &ActivityRecord::Shutdown { at: get_current_time() })?;
+    sync_activity_log()
+}
+
+/// Today's log file, creating the log directory if needed
+fn current_log_path() -> io::Result<PathBuf> {
+    let (log_dir, _) = get_platform_directories()?;
+    if !log_dir.exists() {
+        fs::create_dir_all(&log_dir)?;
+    }
+    let current_date = Local::now().format("%Y-%m-%d").to_string();
+    Ok(log_dir.join(format!("rs-fairsight({}).txt", current_date)))
+}
+
+/// Append `record`, preceded by the local UTC offset if the file does not have it yet
+fn append_with_offset(path: &Path, record: &ActivityRecord) -> io::Result<()> {
+    let offset_secs = Local::now().offset().local_minus_utc();
+    let mut last_utc_offset = LAST_UTC_OFFSET.lock().unwrap();
+    let written = last_utc_offset.as_ref()
+        .is_some_and(|(last_path, last_offset)| last_path == path && *last_offset == offset_secs);
+    if !written {
+        append_activity_record(path, &ActivityRecord::UtcOffset { at: get_current_time(), offset_secs })?;
+        *last_utc_offset = Some((path.to_path_buf(), offset_secs));
+    }
+    append_activity_record(path, record)
+}
+
+fn check_clocks() -> io::Result<()> {
+    let mut last_tracked_inactive_time = LAST_TRACKED_INACTIVE_TIME.lock().unwrap();
+    let mut last_tracked_active_start_time = LAST_TRACKED_ACTIVE_START_TIME.lock().unwrap();
+    let mut last_tracked_active_end_time = LAST_TRACKED_ACTIVE_END_TIME.lock().unwrap();
+    record_clock_events(
+        &mut last_tracked_inactive_time,
+        &mut last_tracked_active_start_time,
+        &mut last_tracked_active_end_time
+    )?;
+    Ok(())
+}
+
+/// Compare the wall clock with the monotonic clocks and record any sleep or clock change.
+/// Active stretches never span either: the next input starts a new one.
+/// Returns whether the clock was adjusted.
+fn record_clock_events(
+    last_tracked_inactive_time: &mut u64,
+    last_tracked_active_start_time: &mut u64,
+    last_tracked_active_end_time: &mut u64
+) -> io::Result<bool> {
+    let events = CLOCK_WATCH.lock().unwrap().check(sample_clocks());
+    let adjusted = events.iter().any(|event| matches!(event, ClockEvent::Adjusted { .. }));
+    for event in events {
+        let record = match event {
+            ClockEvent::Suspended { start, end } => {
+                crate::log_info!("time_tracker", "System was suspended for {} seconds", end - start);
+                *last_tracked_active_start_time = end;
+                ActivityRecord::Suspended { start, end }
+            }
+            ClockEvent::Adjusted { at, offset_secs } => {
+                crate::log_warning!("time_tracker", "System clock was adjusted by {} seconds", offset_secs);
+                *last_tracked_inactive_time = at;
+                *last_tracked_active_start_time = at;
+                *last_tracked_active_end_time = at;
+                ActivityRecord::ClockAdjusted { at, offset_secs }
+            }
+        };
+        let path = current_log_path()?;
+        if let Some(app_record) = end_focus_stretch() {
+            append_with_offset(&path, &app_record)?;
+        }
+        append_with_offset(&path, &record)?;
+    }
+    Ok(adjusted)
 }
 
 pub fn update_track_time(current_time: u64) -> io::Result<()> {
@@ -37,47 +182,68 @@ pub fn update_track_time(current_time: u64) -> io::Result<()> {
 
     // Get platform-specific directories
     let (log_dir, backup_dir) = get_platform_directories()?;
+    let filename = current_log_path()?;
 
-    // Create directory if it doesn't exist
-    if !log_dir.exists() {
-        fs::create_dir_all(&log_dir)?;
+    // A jump or sleep since the last input must be recorded before this input is
+    let adjusted = record_clock_events(
+        &mut last_tracked_inactive_time,
+        &mut last_tracked_active_start_time,
+        &mut last_tracked_active_end_time
+    )?;
+    if adjusted {
+        // This input was timed by the adjusted clock; start a new stretch from it
+        *last_tracked_inactive_time = current_time;
+        *last_tracked_active_start_time = current_time;
+        *last_tracked_active_end_time = current_time;
     }
 
-    let current_date = Local::now().format("%Y-%m-%d").to_string();
-    let filename = log_dir.join(format!("rs-fairsight({}).txt", current_date));
-
-    let message = if current_time < *last_tracked_inactive_time {
-        let msg = "Time Sync error\n".to_string();
+    let record = if current_time < *last_tracked_inactive_time {
         crate::log_warning!("time_tracker", "Time sync error detected");
-        msg
+        ActivityRecord::TimeSyncError {
+            last_time: Some(*last_tracked_inactive_time),
+            current_time: Some(current_time),
+        }
     } else if current_time - *last_tracked_inactive_time > INACTIVE_TIME_PERIOD {
         *last_tracked_active_start_time = current_time;
-        let msg = format!(
-            "Inactive time over 5seconds {} - {}\n",
-            current_time,
-            *last_tracked_inactive_time
-        );
+        if let Some(app_record) = end_focus_stretch() {
+            append_with_offset(&filename, &app_record)?;
+        }
         crate::log_info!("time_tracker", "User became active after {} seconds of inactivity", current_time - *last_tracked_inactive_time);
-        msg
+        ActivityRecord::Inactive {
+            start: *last_tracked_inactive_time,
+            end: current_time,
+        }
     } else if *last_tracked_active_end_time != current_time {
         *last_tracked_active_end_time = current_time;
-        format!(
-            "Active time {} - {}\n",
-            *last_tracked_active_end_time,
-            *last_tracked_active_start_time
-        )
+        ActivityRecord::Active {
+            start: *last_tracked_active_start_time,
+            end: *last_tracked_active_end_time,
+        }
     } else {
-        // No message to write
+        // No record to write
         *last_tracked_inactive_time = current_time;
         return Ok(());
     };
 
-    // Write the message using our improved atomic write function
-    write_encrypted_message_to_file(&filename, &message, Some(&backup_dir))?;
+    // Each record is a single append to the day file
+    append_with_offset(&filename, &record)?;
+
+    if let ActivityRecord::Active { .. } = record {
+        if let Some(app_record) = track_foreground_app(current_time) {
+            append_with_offset(&filename, &app_record)?;
+        }
+    }
+
+    for event in track_breaks(current_time, &load_activity_settings().break_reminders) {
+        append_with_offset(&filename, &event.to_record())?;
+        notify_break(&event);
+    }
 
     // Periodic backup (reduced frequency)
-    if should_create_backup() {
-        let file_name = format!("rs-fairsight({}).txt", current_date);
+    if should_create_backup(current_time) {
+        // The backup should contain every record appended so far
+        sync_activity_log()?;
+        let file_name = filename.file_name().unwrap_or_default().to_string_lossy().to_string();
         let _count = get_current_backup_count();
         if let Err(e) = save_backup(&log_dir, &backup_dir, &file_name) {
             crate::log_error!("time_tracker", "Backup failed: {}", e);
@@ -89,3 +255,34 @@ pub fn update_track_time(current_time: u64) -> io::Result<()> {
     *last_tracked_inactive_time = current_time;
     Ok(())
 }
+
+/// Sample the focused application. Returns the record of the previous focus stretch once
+/// another application takes focus; the current stretch is only kept in memory until then.
+fn track_foreground_app(current_time: u64) -> Option<ActivityRecord> {
+    let settings = load_activity_settings();
+    if !settings.track_applications {
+        return end_focus_stretch();
+    }
+
+    let app = sample_foreground_app(settings.track_window_titles)?;
+    let mut last_tracked_app = LAST_TRACKED_APP.lock().unwrap();
+    if let Some((last_app, _, last_seen)) = last_tracked_app.as_mut() {
+        if *last_app == app {
+            *last_seen = current_time;
+            return None;
+        }
+    }
+    let previous = last_tracked_app.replace((app, current_time, current_time));
+    previous.and_then(focus_record)
+}
+
+/// The record of the current focus stretch, which ends with the active stretch it is part of
+fn end_focus_stretch() -> Option<ActivityRecord> {
+    LAST_TRACKED_APP.lock().unwrap().take().and_then(focus_record)
+}
+
+/// A focus stretch from when the application gained focus until it was last seen; nothing
+/// for an application seen only once
+fn focus_record((app, start, last_seen): (ForegroundApp, u64, u64)) -> Option<ActivityRecord> {
+    (last_seen > start).then_some(ActivityRecord::Application { start, end: last_seen, app })
+}
@@ -0,0 +1,365 @@
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::utils::encryption::random_bytes;
+use crate::utils::file_utils::atomic_write_with_backup;
+use crate::utils::key_manager::{KeyRing, KeyStore};
+use super::chain::record_hash;
+use super::core::get_current_time;
+use super::importer::{remove_imported_source, store_imported, ImportedRecord};
+use super::records::{read_log_file, ActivityRecord};
+
+const DEVICES_FILE_NAME: &str = "activity_devices.json";
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+/// Merged records are imported with the source "device:<id>"
+const DEVICE_SOURCE_PREFIX: &str = "device:";
+
+/// An installation that writes activity logs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+}
+
+/// Another device whose logs were merged into this one's
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergedDevice {
+    pub id: String,
+    pub name: String,
+    pub last_merged: u64,
+    /// Records merged so far, without duplicates
+    pub records: usize,
+}
+
+/// This installation and the devices merged into it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceList {
+    pub this_device: DeviceInfo,
+    pub merged: Vec<MergedDevice>,
+}
+
+impl DeviceList {
+    /// Name of `id`, or of this device when `None`
+    pub fn name_of(&self, id: Option<&str>) -> String {
+        match id {
+            None => self.this_device.name.clone(),
+            Some(id) if id == self.this_device.id => self.this_device.name.clone(),
+            Some(id) => self
+                .merged
+                .iter()
+                .find(|device| device.id == id)
+                .map_or_else(|| id.to_string(), |device| device.name.clone()),
+        }
+    }
+}
+
+/// How the devices file is stored; the id of this device is created on first use
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DeviceRegistry {
+    this_device: Option<DeviceInfo>,
+    #[serde(default)]
+    merged: Vec<MergedDevice>,
+}
+
+/// Another device's records over a date range, for merging on a machine without its keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceArchive {
+    pub format_version: u32,
+    pub device: DeviceInfo,
+    pub exported_at: u64,
+    pub records: Vec<ActivityRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub device: DeviceInfo,
+    pub files_read: usize,
+    pub merged: usize,
+    /// Records already present from an earlier merge
+    pub duplicates: usize,
+    /// Records that do not carry over to another device, e.g. sleep and clock changes
+    pub ignored: usize,
+    /// Log files that could not be read
+    pub failed_files: Vec<String>,
+}
+
+// The devices file is rewritten as a whole, so updates must not interleave
+static DEVICES_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub fn device_source(id: &str) -> String {
+    format!("{}{}", DEVICE_SOURCE_PREFIX, id)
+}
+
+/// Device id of an imported record's source, if it was merged from another device
+pub fn source_device(source: &str) -> Option<&str> {
+    source.strip_prefix(DEVICE_SOURCE_PREFIX)
+}
+
+fn read_registry(dir: &Path) -> Result<DeviceRegistry, String> {
+    let path = dir.join(DEVICES_FILE_NAME);
+    if !path.exists() {
+        return Ok(DeviceRegistry::default());
+    }
+    let content = fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_slice(&content).map_err(|e| format!("Invalid devices file {:?}: {}", path, e))
+}
+
+fn write_registry(dir: &Path, registry: &DeviceRegistry) -> Result<(), String> {
+    let path = dir.join(DEVICES_FILE_NAME);
+    let json = serde_json::to_string_pretty(registry).map_err(|e| format!("Failed to serialize devices: {}", e))?;
+    atomic_write_with_backup(&path, json.as_bytes(), None).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn host_name() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "This device".to_string())
+}
+
+/// This installation's device, given an id and the host name the first time
+fn this_device_in(dir: &Path, registry: &mut DeviceRegistry) -> Result<DeviceInfo, String> {
+    if let Some(device) = &registry.this_device {
+        return Ok(device.clone());
+    }
+    let id = random_bytes::<8>().map_err(|_| "Device id generation failed".to_string())?;
+    let device = DeviceInfo { id: hex::encode(id), name: host_name() };
+    registry.this_device = Some(device.clone());
+    write_registry(dir, registry)?;
+    crate::log_info!("devices", "Registered this device as {} ({})", device.name, device.id);
+    Ok(device)
+}
+
+pub fn this_device(log_dir: &Path) -> Result<DeviceInfo, String> {
+    let _guard = DEVICES_LOCK.lock().unwrap();
+    let mut registry = read_registry(log_dir)?;
+    this_device_in(log_dir, &mut registry)
+}
+
+pub fn registered_devices(log_dir: &Path) -> Result<DeviceList, String> {
+    let _guard = DEVICES_LOCK.lock().unwrap();
+    let mut registry = read_registry(log_dir)?;
+    let this_device = this_device_in(log_dir, &mut registry)?;
+    Ok(DeviceList { this_device, merged: registry.merged })
+}
+
+/// Records that describe the user rather than the machine. Another device's sleep and clock
+/// changes say nothing about this one and would cut into its activity, so they are left out.
+fn carries_over(record: &ActivityRecord) -> bool {
+    matches!(
+        record,
+        ActivityRecord::Active { .. } | ActivityRecord::Inactive { .. } | ActivityRecord::Application { .. }
+    )
+}
+
+/// Daily logs in `dir`, e.g. "rs-fairsight(2025-01-31).txt", without backups or sidecar files
+fn daily_log_files(dir: &Path) -> Vec<(NaiveDate, std::path::PathBuf)> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let date = name.strip_prefix("rs-fairsight(")?.strip_suffix(").txt")?;
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+            Some((date, entry.path()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Records of every daily log in a copied log folder, decrypted with the key store next to them
+fn read_log_folder(
+    dir: &Path,
+    passphrase: Option<&str>,
+    report: &mut MergeReport
+) -> Result<Vec<ActivityRecord>, String> {
+    let keyring = if dir.join("activity_keys.json").exists() {
+        let mut store = KeyStore::open(dir).map_err(|e| format!("Failed to open key store in {:?}: {}", dir, e))?;
+        if store.keyring().is_none() {
+            store.unlock(passphrase)?;
+        }
+        store.keyring().cloned().ok_or("The device's activity log keys are locked")?
+    } else {
+        // Logs written before key management
+        KeyRing::legacy()
+    };
+
+    let mut records = Vec::new();
+    for (_, path) in daily_log_files(dir) {
+        match read_log_file(&path, &keyring) {
+            Ok(contents) => {
+                records.extend(contents.records);
+                report.files_read += 1;
+            }
+            Err(e) => {
+                crate::log_warning!("devices", "Skipping unreadable log {:?}: {}", path, e);
+                report.failed_files.push(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Merge another device's logs from a copy of its log folder or a device archive. Its records
+/// are imported under the device's id; `device_name` names devices that never registered one.
+pub fn merge_device_logs(
+    log_dir: &Path,
+    path: &Path,
+    device_name: Option<&str>,
+    passphrase: Option<&str>,
+    keyring: &KeyRing
+) -> Result<MergeReport, String> {
+    let fallback_name = device_name
+        .map(str::to_string)
+        .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+    let mut report = MergeReport {
+        device: DeviceInfo { id: record_hash(&fallback_name)[..16].to_string(), name: fallback_name },
+        files_read: 0,
+        merged: 0,
+        duplicates: 0,
+        ignored: 0,
+        failed_files: Vec::new(),
+    };
+
+    let records = if path.is_dir() {
+        if let Some(device) = read_registry(path)?.this_device {
+            report.device = device;
+        }
+        read_log_folder(path, passphrase, &mut report)?
+    } else {
+        let content = fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let archive: DeviceArchive =
+            serde_json::from_slice(&content).map_err(|e| format!("Not a device archive: {}", e))?;
+        if archive.format_version != ARCHIVE_FORMAT_VERSION {
+            return Err(format!("Unsupported device archive version {}", archive.format_version));
+        }
+        report.device = archive.device;
+        report.files_read = 1;
+        archive.records
+    };
+    if let Some(name) = device_name {
+        report.device.name = name.to_string();
+    }
+    if report.device.id == this_device(log_dir)?.id {
+        return Err("These are this device's own logs".to_string());
+    }
+
+    let source = device_source(&report.device.id);
+    let total = records.len();
+    let imported: Vec<ImportedRecord> = records
+        .into_iter()
+        .filter(carries_over)
+        .map(|record| ImportedRecord::new(&source, record))
+        .collect();
+    report.ignored = total - imported.len();
+    (report.merged, report.duplicates) = store_imported(log_dir, imported, keyring)?;
+
+    let _guard = DEVICES_LOCK.lock().unwrap();
+    let mut registry = read_registry(log_dir)?;
+    match registry.merged.iter_mut().find(|device| device.id == report.device.id) {
+        Some(device) => {
+            device.name = report.device.name.clone();
+            device.last_merged = get_current_time();
+            device.records += report.merged;
+        }
+        None => registry.merged.push(MergedDevice {
+            id: report.device.id.clone(),
+            name: report.device.name.clone(),
+            last_merged: get_current_time(),
+            records: report.merged,
+        }),
+    }
+    write_registry(log_dir, &registry)?;
+
+    crate::log_info!(
+        "devices",
+        "Merged {} records from {} ({} duplicates, {} ignored, {} unreadable files)",
+        report.merged, report.device.name, report.duplicates, report.ignored, report.failed_files.len()
+    );
+    Ok(report)
+}
+
+/// This device's records of an inclusive date range, to merge into another device's logs
+pub fn export_device_archive(
+    log_dir: &Path,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    keyring: &KeyRing
+) -> Result<DeviceArchive, String> {
+    let mut records = Vec::new();
+    for (date, path) in daily_log_files(log_dir) {
+        if date < start_date || date > end_date {
+            continue;
+        }
+        let contents = read_log_file(&path, keyring).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        records.extend(contents.records.into_iter().filter(carries_over));
+    }
+
+    Ok(DeviceArchive {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        device: this_device(log_dir)?,
+        exported_at: get_current_time(),
+        records,
+    })
+}
+
+/// Remove everything merged from a device. Returns how many records were removed.
+pub fn remove_merged_device(log_dir: &Path, id: &str, keyring: &KeyRing) -> Result<usize, String> {
+    let removed = remove_imported_source(log_dir, &device_source(id), keyring)?;
+    let _guard = DEVICES_LOCK.lock().unwrap();
+    let mut registry = read_registry(log_dir)?;
+    registry.merged.retain(|device| device.id != id);
+    write_registry(log_dir, &registry)?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use crate::utils::encryption::generate_key;
+
+    #[test]
+    fn test_merge_archive() {
+        let dir = TestDir::new("devices");
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+
+        let archive = DeviceArchive {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            device: DeviceInfo { id: "0123456789abcdef".to_string(), name: "laptop".to_string() },
+            exported_at: 1_736_200_000,
+            records: vec![
+                ActivityRecord::Active { start: 1_736_150_000, end: 1_736_153_600 },
+                ActivityRecord::Suspended { start: 1_736_153_600, end: 1_736_160_000 },
+            ],
+        };
+        let path = dir.join("laptop.json");
+        fs::write(&path, serde_json::to_vec(&archive).unwrap()).unwrap();
+
+        let report = merge_device_logs(&dir, &path, None, None, &keyring).unwrap();
+        assert_eq!((report.merged, report.duplicates, report.ignored), (1, 0, 1));
+        let again = merge_device_logs(&dir, &path, None, None, &keyring).unwrap();
+        assert_eq!((again.merged, again.duplicates), (0, 1));
+
+        let devices = registered_devices(&dir).unwrap();
+        assert_eq!(devices.merged.len(), 1);
+        assert_eq!(devices.name_of(Some("0123456789abcdef")), "laptop");
+
+        // The device's own archive is not merged into itself
+        let own = DeviceArchive { device: devices.this_device.clone(), ..archive };
+        fs::write(&path, serde_json::to_vec(&own).unwrap()).unwrap();
+        assert!(merge_device_logs(&dir, &path, None, None, &keyring).is_err());
+
+        assert_eq!(remove_merged_device(&dir, "0123456789abcdef", &keyring).unwrap(), 1);
+        assert!(registered_devices(&dir).unwrap().merged.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::aggregation::utc_offset;
+use super::core::get_current_time;
+use super::types::{IntervalKind, TimelineInterval};
+
+/// File formats the activity timeline can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// One row per interval
+    Csv,
+    /// One JSON object per line and interval
+    Jsonl,
+    /// iCalendar with one event per active block
+    Ics,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Ics => "ics",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Move both ends to the nearest increment
+    Nearest,
+    /// Widen intervals to whole increments
+    Up,
+    /// Narrow intervals to whole increments, dropping those shorter than one
+    Down,
+}
+
+/// Snap interval boundaries to the local clock, e.g. to quarter hours for a timesheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rounding {
+    pub increment_minutes: u32,
+    pub mode: RoundingMode,
+}
+
+/// One exported interval with its times in the export timezone
+#[derive(Debug, Clone, Serialize)]
+struct ExportRow {
+    date: String,
+    start: String,
+    end: String,
+    duration_seconds: i64,
+    #[serde(rename = "type")]
+    kind: IntervalKind,
+}
+
+/// Round every interval and merge those of the same kind that now overlap
+pub fn apply_rounding<Tz: TimeZone>(
+    intervals: &[TimelineInterval],
+    rounding: Rounding,
+    tz: &Tz
+) -> Vec<TimelineInterval> {
+    let increment = rounding.increment_minutes as i64 * 60;
+    if increment == 0 {
+        return intervals.to_vec();
+    }
+
+    // Rounded on the local clock so increments line up with it in zones with odd offsets
+    let round = |timestamp: i64, up: bool| {
+        let offset = utc_offset(tz, timestamp).local_minus_utc() as i64;
+        let local = timestamp + offset;
+        let rounded = match rounding.mode {
+            RoundingMode::Nearest => (local + increment / 2).div_euclid(increment) * increment,
+            _ if up => (local + increment - 1).div_euclid(increment) * increment,
+            _ => local.div_euclid(increment) * increment,
+        };
+        rounded - offset
+    };
+
+    let mut rounded: Vec<TimelineInterval> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        let widen = rounding.mode == RoundingMode::Up;
+        let start = round(interval.start, !widen);
+        let end = round(interval.end, widen);
+        if end <= start {
+            continue;
+        }
+        match rounded.iter_mut().rev().find(|r| r.kind == interval.kind) {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => rounded.push(TimelineInterval { start, end, kind: interval.kind }),
+        }
+    }
+    rounded
+}
+
+/// Render the intervals in `format` with their times in `tz`
+pub fn render_export<Tz: TimeZone>(intervals: &[TimelineInterval], format: ExportFormat, tz: &Tz) -> String {
+    match format {
+        ExportFormat::Csv => {
+            let mut output = String::from("date,start,end,duration_seconds,type\r\n");
+            for row in intervals.iter().map(|interval| export_row(interval, tz)) {
+                output.push_str(&format!(
+                    "{},{},{},{},{}\r\n",
+                    row.date,
+                    row.start,
+                    row.end,
+                    row.duration_seconds,
+                    row.kind.label()
+                ));
+            }
+            output
+        }
+        ExportFormat::Jsonl => intervals
+            .iter()
+            .map(|interval| serde_json::to_string(&export_row(interval, tz)).unwrap_or_default() + "\n")
+            .collect(),
+        ExportFormat::Ics => render_ics(intervals, get_current_time() as i64),
+    }
+}
+
+fn export_row<Tz: TimeZone>(interval: &TimelineInterval, tz: &Tz) -> ExportRow {
+    let start = local_time(interval.start, utc_offset(tz, interval.start));
+    let end = local_time(interval.end, utc_offset(tz, interval.end));
+    ExportRow {
+        date: start.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+        start: start.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        end: end.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        duration_seconds: interval.end - interval.start,
+        kind: interval.kind,
+    }
+}
+
+fn local_time(timestamp: i64, offset: FixedOffset) -> Option<DateTime<FixedOffset>> {
+    DateTime::from_timestamp(timestamp, 0).map(|utc| utc.with_timezone(&offset))
+}
+
+/// Calendar times are written in UTC, which every client converts to its own zone
+fn ics_time(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|t| t.format("%Y%m%dT%H%M%SZ").to_string())
+        .unwrap_or_default()
+}
+
+fn render_ics(intervals: &[TimelineInterval], generated_at: i64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//InnoMonitor//Activity Export//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for interval in intervals.iter().filter(|i| i.kind == IntervalKind::Active) {
+        let minutes = (interval.end - interval.start) / 60;
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:active-{}-{}@innomonitor", interval.start, interval.end),
+            format!("DTSTAMP:{}", ics_time(generated_at)),
+            format!("DTSTART:{}", ics_time(interval.start)),
+            format!("DTEND:{}", ics_time(interval.end)),
+            "SUMMARY:Active".to_string(),
+            format!("DESCRIPTION:{}h {:02}m of activity", minutes / 60, minutes % 60),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(start: i64, end: i64, kind: IntervalKind) -> TimelineInterval {
+        TimelineInterval { start, end, kind }
+    }
+
+    #[test]
+    fn test_rounding() {
+        // 09:07 - 09:52 UTC
+        let intervals = [interval(1_700_039_220, 1_700_041_920, IntervalKind::Active)];
+        let quarter = |mode| Rounding { increment_minutes: 15, mode };
+
+        let up = apply_rounding(&intervals, quarter(RoundingMode::Up), &Utc);
+        assert_eq!(up, vec![interval(1_700_038_800, 1_700_042_400, IntervalKind::Active)]);
+        let down = apply_rounding(&intervals, quarter(RoundingMode::Down), &Utc);
+        assert_eq!(down, vec![interval(1_700_039_700, 1_700_041_500, IntervalKind::Active)]);
+        let nearest = apply_rounding(&intervals, quarter(RoundingMode::Nearest), &Utc);
+        assert_eq!(nearest, vec![interval(1_700_038_800, 1_700_041_500, IntervalKind::Active)]);
+
+        // Nepal is UTC+05:45, so local hours start at a quarter past the UTC hour
+        let nepal = FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap();
+        let hour = Rounding { increment_minutes: 60, mode: RoundingMode::Up };
+        let up = apply_rounding(&intervals, hour, &nepal);
+        assert_eq!(up, vec![interval(1_700_036_100, 1_700_043_300, IntervalKind::Active)]);
+
+        // Narrowing drops blocks shorter than one increment
+        let short = [interval(1_700_039_220, 1_700_039_400, IntervalKind::Active)];
+        assert!(apply_rounding(&short, quarter(RoundingMode::Down), &Utc).is_empty());
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let intervals = [
+            interval(1_700_039_220, 1_700_041_920, IntervalKind::Active),
+            interval(1_700_041_920, 1_700_042_520, IntervalKind::Inactive),
+        ];
+        let berlin = FixedOffset::east_opt(3600).unwrap();
+
+        let csv = render_export(&intervals, ExportFormat::Csv, &berlin);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("2023-11-15,2023-11-15T10:07:00+01:00,2023-11-15T10:52:00+01:00,2700,Active")
+        );
+
+        let jsonl = render_export(&intervals, ExportFormat::Jsonl, &berlin);
+        let second: serde_json::Value = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(second["type"], "inactive");
+        assert_eq!(second["duration_seconds"], 600);
+
+        let ics = render_ics(&intervals, 1_700_050_000);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("DTSTART:20231115T090700Z\r\nDTEND:20231115T095200Z"));
+        assert!(ics.contains("DESCRIPTION:0h 45m of activity"));
+    }
+}
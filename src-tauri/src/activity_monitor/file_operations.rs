@@ -1,16 +1,27 @@
-use std::fs;
+use once_cell::sync::OnceCell;
 use std::io;
+#[cfg(target_os = "windows")]
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 
-use crate::utils::encryption::{encrypt_string, KEY};
-use crate::utils::file_utils::atomic_write_with_backup;
-use super::types::{BACKUP_COUNTER, BACKUP_FREQUENCY};
+use super::types::{BACKUP_COUNTER, BACKUP_INTERVAL_SECS, LAST_BACKUP_TIME};
 
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "linux"))]
 use dirs;
 
+// Set by the command-line tool to read logs copied from elsewhere
+static DIRECTORY_OVERRIDE: OnceCell<(PathBuf, PathBuf)> = OnceCell::new();
+
+/// Use `log_dir` and `backup_dir` instead of the platform directories for the rest of the process
+pub fn override_platform_directories(log_dir: PathBuf, backup_dir: PathBuf) {
+    let _ = DIRECTORY_OVERRIDE.set((log_dir, backup_dir));
+}
+
 pub fn get_platform_directories() -> io::Result<(std::path::PathBuf, std::path::PathBuf)> {
+    if let Some(dirs) = DIRECTORY_OVERRIDE.get() {
+        return Ok(dirs.clone());
+    }
     #[cfg(target_os = "macos")]
     {
         let home_dir = dirs::home_dir()
@@ -19,6 +30,17 @@ pub fn get_platform_directories() -> io::Result<(std::path::PathBuf, std::path::
         let backup_dir = home_dir.join("Documents").join("rs-fairsight-backup");
         Ok((log_dir, backup_dir))
     }
+    #[cfg(target_os = "linux")]
+    {
+        let data_dir = dirs::home_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find home directory"))?
+            .join(".local")
+            .join("share")
+            .join("innomonitor");
+        let log_dir = data_dir.join("rs-fairsight");
+        let backup_dir = data_dir.join("rs-fairsight-backup");
+        Ok((log_dir, backup_dir))
+    }
     #[cfg(target_os = "windows")]
     {
         let log_dir = Path::new("C:\\fairsight-log").to_path_buf();
@@ -27,38 +49,14 @@ pub fn get_platform_directories() -> io::Result<(std::path::PathBuf, std::path::
     }
 }
 
-pub fn write_encrypted_message_to_file(
-    file_path: &Path,
-    message: &str,
-    backup_dir: Option<&Path>
-) -> io::Result<()> {
-    let (encrypted_data, nonce) = encrypt_string(message, &KEY)
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Encryption failed"))?;
-    
-    // Prepare the data to write: nonce (12 bytes) + length (4 bytes) + encrypted data
-    let mut data = Vec::new();
-    data.extend_from_slice(&nonce);
-    data.extend_from_slice(&encrypted_data);
-    
-    // Check if file exists and read current content
-    let mut existing_content = if file_path.exists() {
-        fs::read(file_path).unwrap_or_default()
-    } else {
-        Vec::new()
-    };
-    
-    // Append new data
-    existing_content.extend_from_slice(&data);
-    
-    // Use atomic write with backup
-    atomic_write_with_backup(file_path, &existing_content, backup_dir)?;
-    
-    Ok(())
-}
-
-pub fn should_create_backup() -> bool {
-    let count = BACKUP_COUNTER.fetch_add(1, Ordering::SeqCst) + 1;
-    count % BACKUP_FREQUENCY == 0
+pub fn should_create_backup(current_time: u64) -> bool {
+    BACKUP_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let last_backup = LAST_BACKUP_TIME.load(Ordering::SeqCst);
+    if current_time.saturating_sub(last_backup) < BACKUP_INTERVAL_SECS {
+        return false;
+    }
+    LAST_BACKUP_TIME.store(current_time, Ordering::SeqCst);
+    true
 }
 
 pub fn get_current_backup_count() -> usize {
@@ -0,0 +1,313 @@
+use chrono::{NaiveDate, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::utils::file_utils::atomic_write_with_backup;
+use crate::utils::key_manager::KeyRing;
+use super::aggregation::{day_bounds, read_day_timeline};
+use super::importer::imported_file_path;
+use super::records::{encode_log, read_log_file, ActivityRecord};
+use super::settings::load_activity_settings;
+use super::types::{IntervalKind, TimelineInterval};
+
+/// How active stretches are joined into focus sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusSettings {
+    /// Breaks up to this long do not end a session
+    pub gap_tolerance_minutes: u32,
+    /// Sessions shorter than this count as fragmented work
+    pub deep_focus_minutes: u32,
+}
+
+impl Default for FocusSettings {
+    fn default() -> Self {
+        Self {
+            gap_tolerance_minutes: 10,
+            deep_focus_minutes: 25,
+        }
+    }
+}
+
+/// Active stretches joined across breaks no longer than the gap tolerance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FocusSession {
+    pub start: i64,
+    pub end: i64,
+    /// Time with input; the session minus its breaks
+    pub active_seconds: i64,
+}
+
+/// A log or import file a rollup was computed from, to notice when it changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollupInput {
+    pub file: String,
+    pub len: u64,
+    pub modified: u64,
+}
+
+/// Focus sessions of one day as computed last time; kept in the day's focus file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FocusRollup {
+    pub day_start: i64,
+    pub day_end: i64,
+    pub gap_tolerance_secs: i64,
+    pub inputs: Vec<RollupInput>,
+    pub sessions: Vec<FocusSession>,
+}
+
+/// Focus metrics of one day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayFocus {
+    pub date: String,
+    pub sessions: Vec<FocusSession>,
+    pub session_count: usize,
+    pub active_seconds: i64,
+    pub median_session_seconds: i64,
+    pub longest_session_seconds: i64,
+    /// Share of active time in sessions shorter than the deep-focus length: 0 when all of it
+    /// was deep work, 1 when none of it was
+    pub fragmentation: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusReport {
+    pub start_date: String,
+    pub end_date: String,
+    pub settings: FocusSettings,
+    pub days: Vec<DayFocus>,
+}
+
+// Focus files are rewritten as a whole, so updates must not interleave
+static FOCUS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Rollups live next to the day's log, e.g. "rs-fairsight(2025-01-31).focus"
+fn focus_file_path(log_dir: &Path, date: NaiveDate) -> PathBuf {
+    log_dir.join(format!("rs-fairsight({}).focus", date.format("%Y-%m-%d")))
+}
+
+/// Join the active stretches of a timeline into sessions
+pub fn detect_focus_sessions(timeline: &[TimelineInterval], gap_tolerance_secs: i64) -> Vec<FocusSession> {
+    let mut sessions: Vec<FocusSession> = Vec::new();
+    for interval in timeline.iter().filter(|i| i.kind == IntervalKind::Active) {
+        let seconds = interval.end - interval.start;
+        match sessions.last_mut() {
+            Some(last) if interval.start - last.end <= gap_tolerance_secs => {
+                last.end = interval.end;
+                last.active_seconds += seconds;
+            }
+            _ => sessions.push(FocusSession {
+                start: interval.start,
+                end: interval.end,
+                active_seconds: seconds,
+            }),
+        }
+    }
+    sessions
+}
+
+pub fn focus_metrics(date: String, sessions: Vec<FocusSession>, deep_focus_secs: i64) -> DayFocus {
+    let mut lengths: Vec<i64> = sessions.iter().map(|s| s.end - s.start).collect();
+    lengths.sort_unstable();
+    let median_session_seconds = match lengths.len() {
+        0 => 0,
+        n if n % 2 == 1 => lengths[n / 2],
+        n => (lengths[n / 2 - 1] + lengths[n / 2]) / 2,
+    };
+
+    let active_seconds: i64 = sessions.iter().map(|s| s.active_seconds).sum();
+    let fragmented_seconds: i64 = sessions
+        .iter()
+        .filter(|s| s.end - s.start < deep_focus_secs)
+        .map(|s| s.active_seconds)
+        .sum();
+    let fragmentation = if active_seconds > 0 {
+        fragmented_seconds as f64 / active_seconds as f64
+    } else {
+        0.0
+    };
+
+    DayFocus {
+        date,
+        session_count: sessions.len(),
+        sessions,
+        active_seconds,
+        median_session_seconds,
+        longest_session_seconds: lengths.last().copied().unwrap_or(0),
+        fragmentation,
+    }
+}
+
+/// The files a day's timeline is read from: the neighbouring days' logs and, when shown,
+/// their imports
+fn rollup_inputs(log_dir: &Path, date: NaiveDate) -> Vec<RollupInput> {
+    let show_imported = load_activity_settings().show_imported;
+    let first = date.pred_opt().unwrap_or(date);
+    let last = date.succ_opt().unwrap_or(date);
+    let mut inputs = Vec::new();
+
+    for file_date in first.iter_days().take_while(|d| *d <= last) {
+        let mut paths = vec![log_dir.join(format!("rs-fairsight({}).txt", file_date.format("%Y-%m-%d")))];
+        if show_imported {
+            paths.push(imported_file_path(log_dir, file_date));
+        }
+        for path in paths {
+            if let Ok(metadata) = fs::metadata(&path) {
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |elapsed| elapsed.as_secs());
+                inputs.push(RollupInput {
+                    file: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    len: metadata.len(),
+                    modified,
+                });
+            }
+        }
+    }
+    inputs
+}
+
+fn read_rollup(path: &Path, keyring: &KeyRing) -> Option<FocusRollup> {
+    if !path.exists() {
+        return None;
+    }
+    match read_log_file(path, keyring) {
+        Ok(contents) => contents.records.into_iter().find_map(|record| match record {
+            ActivityRecord::FocusRollup(rollup) => Some(rollup),
+            _ => None,
+        }),
+        Err(e) => {
+            crate::log_warning!("focus", "Ignoring unreadable rollup {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn write_rollup(path: &Path, date: NaiveDate, rollup: FocusRollup, keyring: &KeyRing) -> Result<(), String> {
+    let key_id = keyring.current_id();
+    let key = keyring.key(key_id).ok_or("Current key is not in the key store")?;
+    let content = encode_log(&[ActivityRecord::FocusRollup(rollup).to_plaintext()], key, key_id, date)?;
+    atomic_write_with_backup(path, &content, None).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Focus sessions of `date` in `tz`, from its rollup unless the day's logs changed since
+fn day_sessions<Tz: TimeZone>(
+    log_dir: &Path,
+    date: NaiveDate,
+    tz: &Tz,
+    gap_tolerance_secs: i64,
+    keyring: &KeyRing
+) -> Result<Vec<FocusSession>, String> {
+    let (day_start, day_end) = day_bounds(date, tz);
+    let inputs = rollup_inputs(log_dir, date);
+    let path = focus_file_path(log_dir, date);
+
+    let _guard = FOCUS_LOCK.lock().unwrap();
+    if let Some(rollup) = read_rollup(&path, keyring) {
+        if rollup.day_start == day_start
+            && rollup.day_end == day_end
+            && rollup.gap_tolerance_secs == gap_tolerance_secs
+            && rollup.inputs == inputs
+        {
+            return Ok(rollup.sessions);
+        }
+    }
+
+    let timeline = read_day_timeline(log_dir, date, day_start, day_end)
+        .map_err(|e| format!("Failed to read activity for {}: {}", date, e))?;
+    let sessions = detect_focus_sessions(&timeline, gap_tolerance_secs);
+
+    // Days without any logs are not worth a file
+    if !inputs.is_empty() {
+        let rollup = FocusRollup {
+            day_start,
+            day_end,
+            gap_tolerance_secs,
+            inputs,
+            sessions: sessions.clone(),
+        };
+        if let Err(e) = write_rollup(&path, date, rollup, keyring) {
+            crate::log_warning!("focus", "Failed to store focus rollup for {}: {}", date, e);
+        }
+    }
+    Ok(sessions)
+}
+
+/// Focus sessions and metrics for every day of an inclusive date range in `tz`
+pub fn focus_report<Tz: TimeZone>(
+    log_dir: &Path,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    tz: &Tz,
+    settings: FocusSettings,
+    keyring: &KeyRing
+) -> Result<FocusReport, String> {
+    let gap_tolerance_secs = settings.gap_tolerance_minutes as i64 * 60;
+    let deep_focus_secs = settings.deep_focus_minutes as i64 * 60;
+    let mut days = Vec::new();
+
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        let sessions = day_sessions(log_dir, date, tz, gap_tolerance_secs, keyring)?;
+        days.push(focus_metrics(date.format("%Y-%m-%d").to_string(), sessions, deep_focus_secs));
+    }
+
+    Ok(FocusReport {
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        settings,
+        days,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active(start: i64, end: i64) -> TimelineInterval {
+        TimelineInterval { start, end, kind: IntervalKind::Active }
+    }
+
+    fn inactive(start: i64, end: i64) -> TimelineInterval {
+        TimelineInterval { start, end, kind: IntervalKind::Inactive }
+    }
+
+    #[test]
+    fn test_focus_sessions() {
+        // 40 minutes of work with a 6-minute break, a 20-minute break, then 10 minutes of work
+        let timeline = [
+            active(0, 1_200),
+            inactive(1_200, 1_560),
+            active(1_560, 2_760),
+            inactive(2_760, 3_960),
+            active(3_960, 4_560),
+        ];
+
+        let sessions = detect_focus_sessions(&timeline, 600);
+        assert_eq!(
+            sessions,
+            vec![
+                FocusSession { start: 0, end: 2_760, active_seconds: 2_400 },
+                FocusSession { start: 3_960, end: 4_560, active_seconds: 600 },
+            ]
+        );
+        assert_eq!(detect_focus_sessions(&timeline, 0).len(), 3);
+        assert_eq!(detect_focus_sessions(&timeline, 1_200).len(), 1);
+
+        let day = focus_metrics("2025-01-06".to_string(), sessions, 25 * 60);
+        assert_eq!(day.session_count, 2);
+        assert_eq!(day.active_seconds, 3_000);
+        assert_eq!(day.median_session_seconds, (2_760 + 600) / 2);
+        assert_eq!(day.longest_session_seconds, 2_760);
+        // Only the 10-minute session is shorter than 25 minutes
+        assert!((day.fragmentation - 0.2).abs() < 1e-9);
+
+        let empty = focus_metrics("2025-01-07".to_string(), Vec::new(), 25 * 60);
+        assert_eq!((empty.session_count, empty.fragmentation), (0, 0.0));
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+
+/// The application that had input focus when activity was recorded
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ForegroundApp {
+    pub process_name: String,
+    pub exe_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_title: Option<String>,
+}
+
+/// Sample the focused application. Returns `None` when it cannot be determined
+/// (no focused window, Wayland session, insufficient permissions).
+/// The window title is only queried when `include_title` is set.
+pub fn sample_foreground_app(include_title: bool) -> Option<ForegroundApp> {
+    platform::sample(include_title)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use once_cell::sync::Lazy;
+    use std::fs;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    use super::ForegroundApp;
+
+    struct X11State {
+        conn: RustConnection,
+        root: Window,
+        net_active_window: Atom,
+        net_wm_pid: Atom,
+        net_wm_name: Atom,
+        utf8_string: Atom,
+    }
+
+    // Reused between samples; dropped and re-established if the X server goes away
+    static X11: Lazy<Mutex<Option<X11State>>> = Lazy::new(|| Mutex::new(None));
+    // Last failed connection attempt, so sessions without X (Wayland) are not retried every sample
+    static LAST_CONNECT_FAILURE: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(60);
+
+    fn connect() -> Option<X11State> {
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let root = conn.setup().roots[screen_num].root;
+        let intern = |name: &[u8]| -> Option<Atom> {
+            Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom)
+        };
+        let net_active_window = intern(b"_NET_ACTIVE_WINDOW")?;
+        let net_wm_pid = intern(b"_NET_WM_PID")?;
+        let net_wm_name = intern(b"_NET_WM_NAME")?;
+        let utf8_string = intern(b"UTF8_STRING")?;
+
+        Some(X11State {
+            conn,
+            root,
+            net_active_window,
+            net_wm_pid,
+            net_wm_name,
+            utf8_string,
+        })
+    }
+
+    fn get_property(state: &X11State, window: Window, property: Atom, kind: Atom, length: u32) -> Option<Vec<u8>> {
+        let reply = state.conn
+            .get_property(false, window, property, kind, 0, length)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(reply.value)
+    }
+
+    fn get_cardinal(state: &X11State, window: Window, property: Atom, kind: Atom) -> Option<u32> {
+        let value = get_property(state, window, property, kind, 1)?;
+        Some(u32::from_ne_bytes(value.get(..4)?.try_into().ok()?))
+    }
+
+    /// Process name from /proc/<pid>/comm
+    pub fn parse_comm(comm: &str) -> Option<String> {
+        Some(comm.trim()).filter(|name| !name.is_empty()).map(str::to_string)
+    }
+
+    /// WM_CLASS is "instance\0class\0"; the class is the friendlier name
+    pub fn parse_wm_class(value: &[u8]) -> Option<String> {
+        value
+            .split(|b| *b == 0)
+            .rfind(|part| !part.is_empty())
+            .map(|class| String::from_utf8_lossy(class).to_string())
+    }
+
+    fn sample_with(state: &X11State, include_title: bool) -> Result<Option<ForegroundApp>, ()> {
+        let active = get_cardinal(state, state.root, state.net_active_window, AtomEnum::WINDOW.into()).ok_or(())?;
+        if active == 0 {
+            return Ok(None);
+        }
+
+        let window_title = if include_title {
+            get_property(state, active, state.net_wm_name, state.utf8_string, 1024)
+                .filter(|v| !v.is_empty())
+                .or_else(|| get_property(state, active, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into(), 1024))
+                .map(|v| String::from_utf8_lossy(&v).to_string())
+        } else {
+            None
+        };
+
+        let pid = get_cardinal(state, active, state.net_wm_pid, AtomEnum::CARDINAL.into());
+        let process_name = pid
+            .and_then(|pid| fs::read_to_string(format!("/proc/{}/comm", pid)).ok())
+            .and_then(|comm| parse_comm(&comm))
+            .or_else(|| {
+                get_property(state, active, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into(), 256)
+                    .and_then(|v| parse_wm_class(&v))
+            });
+        let exe_path = pid
+            .and_then(|pid| fs::read_link(format!("/proc/{}/exe", pid)).ok())
+            .map(|path| path.to_string_lossy().to_string());
+
+        Ok(process_name.map(|process_name| ForegroundApp {
+            process_name,
+            exe_path,
+            window_title,
+        }))
+    }
+
+    pub fn sample(include_title: bool) -> Option<ForegroundApp> {
+        let mut x11 = X11.lock().unwrap();
+        if x11.is_none() {
+            let mut last_failure = LAST_CONNECT_FAILURE.lock().unwrap();
+            if last_failure.is_some_and(|at| at.elapsed() < RECONNECT_INTERVAL) {
+                return None;
+            }
+            *x11 = connect();
+            *last_failure = x11.is_none().then(Instant::now);
+        }
+        let result = sample_with(x11.as_ref()?, include_title);
+        match result {
+            Ok(app) => app,
+            Err(_) => {
+                *x11 = None;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::Path;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId};
+
+    use super::ForegroundApp;
+
+    pub fn sample(include_title: bool) -> Option<ForegroundApp> {
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_null() {
+                return None;
+            }
+
+            let mut title_buf = [0u16; 512];
+            let title_len = if include_title {
+                GetWindowTextW(hwnd, title_buf.as_mut_ptr(), title_buf.len() as i32)
+            } else {
+                0
+            };
+            let window_title = if title_len > 0 {
+                Some(OsString::from_wide(&title_buf[..title_len as usize]).to_string_lossy().to_string())
+            } else {
+                None
+            };
+
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, &mut pid);
+            if pid == 0 {
+                return None;
+            }
+
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let mut path_buf = [0u16; 1024];
+            let mut path_len = path_buf.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, 0, path_buf.as_mut_ptr(), &mut path_len);
+            CloseHandle(process);
+            if ok == 0 {
+                return None;
+            }
+
+            let exe_path = OsString::from_wide(&path_buf[..path_len as usize]).to_string_lossy().to_string();
+            let process_name = Path::new(&exe_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| exe_path.clone());
+
+            Some(ForegroundApp {
+                process_name,
+                exe_path: Some(exe_path),
+                window_title,
+            })
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use cocoa::base::{id, nil};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    use super::ForegroundApp;
+
+    unsafe fn ns_string_to_string(ns_string: id) -> Option<String> {
+        if ns_string == nil {
+            return None;
+        }
+        let utf8: *const c_char = msg_send![ns_string, UTF8String];
+        if utf8.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(utf8).to_string_lossy().to_string())
+    }
+
+    // Window titles need the Accessibility permission on macOS, so only the application is reported
+    pub fn sample(_include_title: bool) -> Option<ForegroundApp> {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let app: id = msg_send![workspace, frontmostApplication];
+            if app == nil {
+                return None;
+            }
+
+            let process_name = ns_string_to_string(msg_send![app, localizedName])?;
+            let exe_url: id = msg_send![app, executableURL];
+            let exe_path = if exe_url == nil {
+                None
+            } else {
+                ns_string_to_string(msg_send![exe_url, path])
+            };
+
+            Some(ForegroundApp {
+                process_name,
+                exe_path,
+                window_title: None,
+            })
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::platform::{parse_comm, parse_wm_class};
+
+    #[test]
+    fn test_process_name_fallbacks() {
+        assert_eq!(parse_comm("firefox\n"), Some("firefox".to_string()));
+        assert_eq!(parse_comm(" \n"), None);
+
+        assert_eq!(parse_wm_class(b"navigator\0Firefox\0"), Some("Firefox".to_string()));
+        assert_eq!(parse_wm_class(b"xterm\0\0"), Some("xterm".to_string()));
+        assert_eq!(parse_wm_class(b""), None);
+    }
+}
@@ -0,0 +1,418 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::file_utils::atomic_write_with_backup;
+use crate::utils::key_manager::KeyRing;
+use super::chain::record_hash;
+use super::foreground::ForegroundApp;
+use super::records::{encode_log, read_log_file, ActivityRecord};
+
+/// A record converted from another tracker's history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedRecord {
+    /// Tracker and data set it came from, e.g. "activitywatch:aw-watcher-afk_laptop"
+    pub source: String,
+    /// Hash of the converted record, so importing the same data again is recognised
+    pub import_id: String,
+    pub record: Box<ActivityRecord>,
+}
+
+impl ImportedRecord {
+    pub fn new(source: &str, record: ActivityRecord) -> Self {
+        Self {
+            source: source.to_string(),
+            import_id: record_hash(&record.to_plaintext()),
+            record: Box::new(record),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportFormat {
+    /// Bucket export from ActivityWatch's web UI or REST API
+    #[serde(rename = "activitywatch")]
+    ActivityWatch,
+    /// Intervals with start and end columns, e.g. a Toggl time entry export
+    #[serde(rename = "csv")]
+    Csv,
+}
+
+/// Records converted from an import file, before they are stored
+#[derive(Debug, Default)]
+pub struct ParsedImport {
+    pub records: Vec<ImportedRecord>,
+    /// Events or rows that could not be converted
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub source: String,
+    pub imported: usize,
+    /// Records already present from an earlier import
+    pub duplicates: usize,
+    pub skipped: usize,
+}
+
+// Import files are rewritten as a whole, so imports must not interleave
+static IMPORT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Imported records are kept apart from the hash-chained day log, e.g. "rs-fairsight(2025-01-31).imported"
+pub fn imported_file_path(log_dir: &Path, date: NaiveDate) -> PathBuf {
+    log_dir.join(format!("rs-fairsight({}).imported", date.format("%Y-%m-%d")))
+}
+
+/// Records imported for one local day, filed by their start time
+pub fn imported_records(log_dir: &Path, date: NaiveDate, keyring: &KeyRing) -> Result<Vec<ImportedRecord>, String> {
+    let path = imported_file_path(log_dir, date);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = read_log_file(&path, keyring).map_err(|e| e.to_string())?;
+    Ok(contents
+        .records
+        .into_iter()
+        .filter_map(|record| match record {
+            ActivityRecord::Imported(imported) => Some(imported),
+            _ => None,
+        })
+        .collect())
+}
+
+fn write_imported_records(
+    path: &Path,
+    date: NaiveDate,
+    records: &[ImportedRecord],
+    keyring: &KeyRing
+) -> Result<(), String> {
+    let key_id = keyring.current_id();
+    let key = keyring.key(key_id).ok_or("Current key is not in the key store")?;
+    let plaintexts: Vec<String> = records
+        .iter()
+        .map(|record| ActivityRecord::Imported(record.clone()).to_plaintext())
+        .collect();
+    let content = encode_log(&plaintexts, key, key_id, date)?;
+    atomic_write_with_backup(path, &content, None).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Add `records` to the import files of their days, skipping any imported before.
+/// Returns how many were added and how many were duplicates.
+pub fn store_imported(log_dir: &Path, records: Vec<ImportedRecord>, keyring: &KeyRing) -> Result<(usize, usize), String> {
+    let mut by_day: BTreeMap<NaiveDate, Vec<ImportedRecord>> = BTreeMap::new();
+    for record in records {
+        let Some((start, _)) = record.record.time_range() else { continue };
+        let date = Local
+            .timestamp_opt(start as i64, 0)
+            .earliest()
+            .map(|time| time.date_naive())
+            .ok_or_else(|| format!("Invalid timestamp {}", start))?;
+        by_day.entry(date).or_default().push(record);
+    }
+
+    let _guard = IMPORT_LOCK.lock().unwrap();
+    let (mut added, mut duplicates) = (0, 0);
+    for (date, new_records) in by_day {
+        let mut existing = imported_records(log_dir, date, keyring)?;
+        let mut seen: HashSet<String> = existing.iter().map(|record| record.import_id.clone()).collect();
+        let before = existing.len();
+        for record in new_records {
+            if seen.insert(record.import_id.clone()) {
+                existing.push(record);
+            } else {
+                duplicates += 1;
+            }
+        }
+        if existing.len() > before {
+            added += existing.len() - before;
+            write_imported_records(&imported_file_path(log_dir, date), date, &existing, keyring)?;
+        }
+    }
+    Ok((added, duplicates))
+}
+
+/// Remove every record imported from `source`. Returns how many were removed.
+pub fn remove_imported_source(log_dir: &Path, source: &str, keyring: &KeyRing) -> Result<usize, String> {
+    let dates: Vec<NaiveDate> = fs::read_dir(log_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let date = name.strip_prefix("rs-fairsight(")?.strip_suffix(").imported")?;
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+        })
+        .collect();
+
+    let _guard = IMPORT_LOCK.lock().unwrap();
+    let mut removed = 0;
+    for date in dates {
+        let mut records = imported_records(log_dir, date, keyring)?;
+        let before = records.len();
+        records.retain(|record| record.source != source);
+        if records.len() == before {
+            continue;
+        }
+        removed += before - records.len();
+        let path = imported_file_path(log_dir, date);
+        if records.is_empty() {
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+        } else {
+            write_imported_records(&path, date, &records, keyring)?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Read, convert and store an import file
+pub fn import_file(log_dir: &Path, path: &Path, format: ImportFormat, keyring: &KeyRing) -> Result<ImportReport, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let (source, parsed) = match format {
+        ImportFormat::ActivityWatch => ("activitywatch".to_string(), parse_activitywatch(&content)?),
+        ImportFormat::Csv => {
+            let source = format!("csv:{}", file_name);
+            let parsed = parse_csv(&content, &source, &Local)?;
+            (source, parsed)
+        }
+    };
+
+    let (imported, duplicates) = store_imported(log_dir, parsed.records, keyring)?;
+    crate::log_info!(
+        "import",
+        "Imported {} records from {} ({} duplicates, {} skipped)",
+        imported, file_name, duplicates, parsed.skipped
+    );
+    Ok(ImportReport { source, imported, duplicates, skipped: parsed.skipped })
+}
+
+#[derive(Deserialize)]
+struct AwBucket {
+    #[serde(default)]
+    id: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    events: Vec<AwEvent>,
+}
+
+#[derive(Deserialize)]
+struct AwEvent {
+    timestamp: String,
+    duration: f64,
+    #[serde(default)]
+    data: BTreeMap<String, Value>,
+}
+
+/// Convert an ActivityWatch export: either `{"buckets": {...}}` or a single bucket.
+/// AFK buckets become active and inactive time, window buckets application focus.
+pub fn parse_activitywatch(json: &str) -> Result<ParsedImport, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid ActivityWatch export: {}", e))?;
+    let buckets: Vec<AwBucket> = match value.get("buckets") {
+        Some(buckets) => serde_json::from_value::<BTreeMap<String, AwBucket>>(buckets.clone())
+            .map_err(|e| format!("Invalid ActivityWatch buckets: {}", e))?
+            .into_iter()
+            .map(|(id, bucket)| AwBucket { id: if bucket.id.is_empty() { id } else { bucket.id }, ..bucket })
+            .collect(),
+        None => vec![serde_json::from_value(value).map_err(|e| format!("Invalid ActivityWatch bucket: {}", e))?],
+    };
+
+    let mut parsed = ParsedImport::default();
+    for bucket in buckets {
+        let source = format!("activitywatch:{}", bucket.id);
+        for event in &bucket.events {
+            match convert_aw_event(&bucket.kind, event) {
+                Some(record) => parsed.records.push(ImportedRecord::new(&source, record)),
+                None => parsed.skipped += 1,
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+fn convert_aw_event(bucket_kind: &str, event: &AwEvent) -> Option<ActivityRecord> {
+    let start = DateTime::parse_from_rfc3339(&event.timestamp).ok()?.timestamp();
+    let duration = event.duration.round() as i64;
+    if start < 0 || duration <= 0 {
+        return None;
+    }
+    let (start, end) = (start as u64, (start + duration) as u64);
+    let text = |key: &str| event.data.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty());
+
+    match bucket_kind {
+        "afkstatus" => match text("status")? {
+            "not-afk" => Some(ActivityRecord::Active { start, end }),
+            "afk" => Some(ActivityRecord::Inactive { start, end }),
+            _ => None,
+        },
+        "currentwindow" => Some(ActivityRecord::Application {
+            start,
+            end,
+            app: ForegroundApp {
+                process_name: text("app")?.to_string(),
+                exe_path: None,
+                window_title: text("title").map(str::to_string),
+            },
+        }),
+        _ => None,
+    }
+}
+
+/// Column positions in a CSV import, by lower-cased header
+struct CsvColumns {
+    start: Vec<usize>,
+    end: Vec<usize>,
+    description: Option<usize>,
+    project: Option<usize>,
+}
+
+impl CsvColumns {
+    fn find(headers: &csv::StringRecord) -> Result<Self, String> {
+        let names: Vec<String> = headers.iter().map(|h| h.trim().to_lowercase()).collect();
+        let position = |candidates: &[&str]| names.iter().position(|name| candidates.contains(&name.as_str()));
+        // Either one timestamp column or a date and a time column, as Toggl writes them
+        let moment = |single: &[&str], date: &str, time: &str| match position(single) {
+            Some(index) => Some(vec![index]),
+            None => Some(vec![position(&[date])?, position(&[time])?]),
+        };
+
+        Ok(Self {
+            start: moment(&["start", "start_time", "started_at", "from"], "start date", "start time")
+                .ok_or("CSV needs a start column")?,
+            end: moment(&["end", "end_time", "ended_at", "stop", "to"], "end date", "end time")
+                .ok_or("CSV needs an end column")?,
+            description: position(&["description", "note", "notes"]),
+            project: position(&["project"]),
+        })
+    }
+}
+
+/// Unix seconds, RFC 3339, or a local date and time in `tz`
+fn parse_csv_time<Tz: TimeZone>(value: &str, tz: &Tz) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Some(seconds);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.timestamp());
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|local| tz.from_local_datetime(&local).earliest())
+        .map(|time| time.timestamp())
+}
+
+/// Convert a CSV of intervals into active time, with descriptions and projects as annotations.
+/// Times without an offset are read in `tz`.
+pub fn parse_csv<Tz: TimeZone>(content: &str, source: &str, tz: &Tz) -> Result<ParsedImport, String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| format!("Invalid CSV header: {}", e))?.clone();
+    let columns = CsvColumns::find(&headers)?;
+
+    let mut parsed = ParsedImport::default();
+    for row in reader.records() {
+        let Ok(row) = row else {
+            parsed.skipped += 1;
+            continue;
+        };
+        let field = |index: usize| row.get(index).unwrap_or("").trim();
+        let time = |indices: &[usize]| {
+            let joined: Vec<&str> = indices.iter().map(|&index| field(index)).collect();
+            parse_csv_time(&joined.join(" "), tz)
+        };
+
+        let (Some(start), Some(end)) = (time(&columns.start), time(&columns.end)) else {
+            parsed.skipped += 1;
+            continue;
+        };
+        if start < 0 || end <= start {
+            parsed.skipped += 1;
+            continue;
+        }
+        let (start, end) = (start as u64, end as u64);
+        parsed.records.push(ImportedRecord::new(source, ActivityRecord::Active { start, end }));
+
+        let labels: Vec<&str> = [columns.project, columns.description]
+            .into_iter()
+            .flatten()
+            .map(field)
+            .filter(|label| !label.is_empty())
+            .collect();
+        if !labels.is_empty() {
+            let text = labels.join(": ");
+            parsed.records.push(ImportedRecord::new(source, ActivityRecord::Annotation { start, end, text }));
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use chrono::Utc;
+
+    const AW_EXPORT: &str = r#"{"buckets": {
+        "aw-watcher-afk_laptop": {"id": "aw-watcher-afk_laptop", "type": "afkstatus", "events": [
+            {"id": 2, "timestamp": "2025-01-01T10:00:00.250000+00:00", "duration": 1800.4, "data": {"status": "not-afk"}},
+            {"id": 3, "timestamp": "2025-01-01T10:30:00+00:00", "duration": 600, "data": {"status": "afk"}}
+        ]},
+        "aw-watcher-window_laptop": {"type": "currentwindow", "events": [
+            {"id": 7, "timestamp": "2025-01-01T10:00:00+00:00", "duration": 90, "data": {"app": "code", "title": "main.rs"}},
+            {"id": 8, "timestamp": "2025-01-01T10:01:30+00:00", "duration": 0, "data": {"app": "code", "title": ""}}
+        ]},
+        "aw-watcher-web": {"type": "web.tab.current", "events": [
+            {"id": 1, "timestamp": "2025-01-01T10:00:00+00:00", "duration": 5, "data": {"url": "https://example.com"}}
+        ]}
+    }}"#;
+
+    #[test]
+    fn test_activitywatch_import_is_idempotent() {
+        let parsed = parse_activitywatch(AW_EXPORT).unwrap();
+        assert_eq!(parsed.skipped, 2);
+        let records: Vec<_> = parsed.records.iter().map(|r| (r.source.as_str(), (*r.record).clone())).collect();
+        assert_eq!(records[0], ("activitywatch:aw-watcher-afk_laptop", ActivityRecord::Active {
+            start: 1_735_725_600,
+            end: 1_735_727_400,
+        }));
+        assert_eq!(records[1].1, ActivityRecord::Inactive { start: 1_735_727_400, end: 1_735_728_000 });
+        assert!(matches!(&records[2].1, ActivityRecord::Application { app, .. }
+            if app.process_name == "code" && app.window_title.as_deref() == Some("main.rs")));
+
+        let dir = TestDir::new("import");
+        let keyring = KeyRing::with_key(1, crate::utils::encryption::generate_key().unwrap());
+
+        assert_eq!(store_imported(&dir, parsed.records, &keyring).unwrap(), (3, 0));
+        let again = parse_activitywatch(AW_EXPORT).unwrap();
+        assert_eq!(store_imported(&dir, again.records, &keyring).unwrap(), (0, 3));
+
+        let date = Local.timestamp_opt(1_735_725_600, 0).unwrap().date_naive();
+        assert_eq!(imported_records(&dir, date, &keyring).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_csv_import() {
+        let toggl = "User,Project,Description,Start date,Start time,End date,End time\n\
+                     Ann,Apollo,Design review,2025-01-01,09:00:00,2025-01-01,10:15:00\n\
+                     Ann,,,2025-01-01,11:00:00,2025-01-01,10:00:00\n";
+        let parsed = parse_csv(toggl, "csv:toggl.csv", &Utc).unwrap();
+        assert_eq!(parsed.skipped, 1);
+        let records: Vec<_> = parsed.records.iter().map(|r| (*r.record).clone()).collect();
+        assert_eq!(records, vec![
+            ActivityRecord::Active { start: 1_735_722_000, end: 1_735_726_500 },
+            ActivityRecord::Annotation { start: 1_735_722_000, end: 1_735_726_500, text: "Apollo: Design review".into() },
+        ]);
+
+        let generic = "start,end\n2025-01-01T09:00:00+01:00,1735722000\n";
+        let parsed = parse_csv(generic, "csv:generic.csv", &Utc).unwrap();
+        assert_eq!(*parsed.records[0].record, ActivityRecord::Active { start: 1_735_718_400, end: 1_735_722_000 });
+
+        assert!(parse_csv("when,what\n1,2\n", "csv:bad.csv", &Utc).is_err());
+    }
+}
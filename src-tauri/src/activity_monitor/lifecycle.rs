@@ -0,0 +1,220 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::utils::key_manager::KeyRing;
+use super::records::{read_log_file, ActivityRecord};
+
+/// Seconds between heartbeat records while the tracker runs
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 60;
+/// How many days of logs are searched for the end of the previous run
+const PREVIOUS_RUN_LOOKBACK_DAYS: i64 = 7;
+
+/// How a run of the tracker ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunEnding {
+    /// Quit while the machine kept running
+    AppClosed,
+    /// The machine shut down or lost power; the next start was after a reboot
+    PoweredOff,
+    /// Stopped without a shutdown record while the machine kept running
+    Crashed,
+}
+
+/// The run before this one, as found at startup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviousRun {
+    pub ending: RunEnding,
+    /// The run wrote a shutdown record
+    pub clean: bool,
+    /// Last record of the run, in Unix seconds
+    pub last_seen: u64,
+    /// Local date and time of `last_seen`, e.g. "2025-01-31" and "17:42:05"
+    pub date: String,
+    pub time: String,
+}
+
+/// Whether the last session ended without shutting down properly, and when
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownCheck {
+    pub unexpected: bool,
+    pub previous_run: Option<PreviousRun>,
+    /// Network monitoring was still running when the app stopped
+    pub network_data_lost: bool,
+}
+
+/// Why the timeline has no records from some point until the tracker started again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoppedRun {
+    /// `None` if the run before the gap left no lifecycle records
+    pub ending: Option<RunEnding>,
+    /// Last sign of the run before the gap; it may have run idle past the start of the gap
+    pub last_seen: u64,
+    /// Startup of the next run, which may be after the end of the gap
+    pub restarted_at: u64,
+}
+
+static PREVIOUS_RUN: Lazy<Mutex<Option<PreviousRun>>> = Lazy::new(|| Mutex::new(None));
+
+/// Time of a startup, heartbeat or shutdown record
+pub fn lifecycle_time(record: &ActivityRecord) -> Option<u64> {
+    match record {
+        ActivityRecord::Startup { at, .. } | ActivityRecord::Heartbeat { at } | ActivityRecord::Shutdown { at } => {
+            Some(*at)
+        }
+        _ => None,
+    }
+}
+
+/// A run last seen at `last_seen` ended cleanly or not; the next start tells whether the
+/// machine rebooted in between
+pub fn run_ending(clean: bool, last_seen: u64, next_boot_time: u64) -> RunEnding {
+    match (clean, next_boot_time > last_seen) {
+        (_, true) => RunEnding::PoweredOff,
+        (true, false) => RunEnding::AppClosed,
+        (false, false) => RunEnding::Crashed,
+    }
+}
+
+/// Why the tracker was not running in a gap of the timeline starting at `start`, judged by
+/// the lifecycle records sorted by time: the first startup from `start` on, in the gap or after
+/// it, and the last record of the run before that startup. `None` without a later startup.
+pub fn classify_gap(lifecycle: &[ActivityRecord], start: i64) -> Option<StoppedRun> {
+    let (restarted_at, boot_time) = lifecycle.iter().find_map(|record| match record {
+        ActivityRecord::Startup { at, boot_time } if *at as i64 >= start => Some((*at, *boot_time)),
+        _ => None,
+    })?;
+    let gap_start = start.max(0) as u64;
+    let last = lifecycle
+        .iter()
+        .rfind(|record| lifecycle_time(record).is_some_and(|at| at < restarted_at));
+    let Some(last_time) = last.and_then(lifecycle_time) else {
+        return Some(StoppedRun { ending: None, last_seen: gap_start, restarted_at });
+    };
+    let last_seen = last_time.max(gap_start);
+    let clean = matches!(last, Some(ActivityRecord::Shutdown { .. }));
+    Some(StoppedRun { ending: Some(run_ending(clean, last_seen, boot_time)), last_seen, restarted_at })
+}
+
+/// Whether the most recent run in the logs up to `today` shut down cleanly, and when it was
+/// last seen. Records from `started_at` on belong to this run and are left out. `None` if the
+/// logs have no lifecycle records, e.g. from older versions.
+fn find_previous_run(log_dir: &Path, today: NaiveDate, started_at: u64, keyring: &KeyRing) -> Option<(bool, u64)> {
+    for days_back in 0..=PREVIOUS_RUN_LOOKBACK_DAYS {
+        let date = today - Duration::days(days_back);
+        let path = log_dir.join(format!("rs-fairsight({}).txt", date.format("%Y-%m-%d")));
+        if !path.exists() {
+            continue;
+        }
+        let records: Vec<ActivityRecord> = match read_log_file(&path, keyring) {
+            Ok(contents) => contents
+                .records
+                .into_iter()
+                .filter(|record| record.time_range().is_some_and(|(start, _)| start < started_at))
+                .collect(),
+            Err(e) => {
+                crate::log_warning!("lifecycle", "Cannot read {:?} for the previous run: {}", path, e);
+                continue;
+            }
+        };
+        let Some(last) = records.iter().rfind(|record| lifecycle_time(record).is_some()) else {
+            continue;
+        };
+        let last_seen = records
+            .iter()
+            .filter_map(|record| record.time_range().map(|(_, end)| end))
+            .max()
+            .unwrap_or_default();
+        return Some((matches!(last, ActivityRecord::Shutdown { .. }), last_seen));
+    }
+    None
+}
+
+/// Work out how the previous run ended, before this run's startup record at `started_at` is written
+pub fn note_previous_run(log_dir: &Path, boot_time: u64, started_at: u64, keyring: &KeyRing) {
+    let today = Local::now().date_naive();
+    let Some((clean, last_seen)) = find_previous_run(log_dir, today, started_at, keyring) else { return };
+    let ending = run_ending(clean, last_seen, boot_time);
+    let local = Local.timestamp_opt(last_seen as i64, 0).earliest();
+    let previous = PreviousRun {
+        ending,
+        clean,
+        last_seen,
+        date: local.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+        time: local.map(|t| t.format("%H:%M:%S").to_string()).unwrap_or_default(),
+    };
+    if !clean {
+        crate::log_warning!(
+            "lifecycle",
+            "The previous run ended unexpectedly ({:?}) on {} at {}",
+            ending, previous.date, previous.time
+        );
+    }
+    *PREVIOUS_RUN.lock().unwrap() = Some(previous);
+}
+
+/// How the run before this one ended, if known
+pub fn previous_run() -> Option<PreviousRun> {
+    PREVIOUS_RUN.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::records::encode_log;
+    use crate::utils::encryption::generate_key;
+    use crate::utils::test_dir::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_classify_gap() {
+        let startup = |at, boot_time| ActivityRecord::Startup { at, boot_time };
+        let heartbeat = |at| ActivityRecord::Heartbeat { at };
+        let stopped = |ending, last_seen| Some(StoppedRun { ending: Some(ending), last_seen, restarted_at: 3_000 });
+
+        // Idle until the last heartbeat at 1_600, then gone until a restart without a reboot
+        let crashed = [startup(0, 0), heartbeat(1_000), heartbeat(1_600), startup(3_000, 0)];
+        assert_eq!(classify_gap(&crashed, 1_200), stopped(RunEnding::Crashed, 1_600));
+
+        // The same after a reboot at 2_500
+        let rebooted = [startup(0, 0), heartbeat(1_600), startup(3_000, 2_500)];
+        assert_eq!(classify_gap(&rebooted, 1_200), stopped(RunEnding::PoweredOff, 1_600));
+
+        // Quit with a shutdown record, then started again with and without a reboot
+        let closed = [startup(0, 0), ActivityRecord::Shutdown { at: 1_300 }, startup(3_000, 0)];
+        assert_eq!(classify_gap(&closed, 1_200), stopped(RunEnding::AppClosed, 1_300));
+        let shut_down = [startup(0, 0), ActivityRecord::Shutdown { at: 1_300 }, startup(3_000, 2_000)];
+        assert_eq!(classify_gap(&shut_down, 1_200), stopped(RunEnding::PoweredOff, 1_300));
+
+        // The rest of today has no startup after it
+        assert_eq!(classify_gap(&crashed, 3_500), None);
+
+        // Older logs leave the run before a startup without records
+        let unknown = Some(StoppedRun { ending: None, last_seen: 0, restarted_at: 3_000 });
+        assert_eq!(classify_gap(&[startup(3_000, 0)], 0), unknown);
+    }
+
+    #[test]
+    fn test_previous_run_ignores_records_of_this_run() {
+        let dir = TestDir::new("lifecycle");
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+        let date = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let records = [
+            ActivityRecord::Startup { at: 1_000, boot_time: 900 },
+            ActivityRecord::Active { start: 1_000, end: 1_090 },
+            ActivityRecord::Heartbeat { at: 1_060 },
+            // This run started at 5_000 and wrote before looking for the previous one
+            ActivityRecord::UtcOffset { at: 5_000, offset_secs: 3_600 },
+            ActivityRecord::Active { start: 5_000, end: 5_010 },
+        ];
+        let plaintexts: Vec<String> = records.iter().map(ActivityRecord::to_plaintext).collect();
+        let content = encode_log(&plaintexts, keyring.key(1).unwrap(), 1, date).unwrap();
+        fs::write(dir.join("rs-fairsight(2025-01-02).txt"), content).unwrap();
+
+        assert_eq!(find_previous_run(&dir, date, 5_000, &keyring), Some((false, 1_090)));
+        assert_eq!(run_ending(false, 1_090, 900), RunEnding::Crashed);
+    }
+}
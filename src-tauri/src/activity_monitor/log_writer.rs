@@ -0,0 +1,445 @@
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::utils::encryption::seal_string;
+use crate::utils::file_utils::atomic_write_with_backup;
+use crate::utils::key_manager::{active_keyring, KeyRing};
+use super::file_operations::get_platform_directories;
+use super::recovery::recover_closed_day_log;
+use super::chain::{chain_anchor, chain_plaintext, final_hash, record_hash, GENESIS_HASH};
+use super::records::{
+    encode_frame, log_file_date, log_key_id, record_aad, reencrypt_log_content, scan_frames, ActivityRecord,
+    FrameDamage, LogHeader, LOG_FORMAT_VERSION,
+};
+use super::types::{MAX_PENDING_RECORDS, SYNC_EVERY_RECORDS, SYNC_INTERVAL};
+
+/// Last format version without record binding, kept for files that cannot be upgraded
+const UNBOUND_FORMAT_VERSION: u16 = 2;
+
+/// A log with a damaged record before its end. Appending would put new records behind the
+/// damage, at sequence numbers that no longer match their position.
+#[derive(Debug)]
+pub struct DamagedLog {
+    pub path: PathBuf,
+    /// Offset of the damaged record
+    pub offset: usize,
+}
+
+impl fmt::Display for DamagedLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} has a damaged record at byte {}", self.path, self.offset)
+    }
+}
+
+impl std::error::Error for DamagedLog {}
+
+/// Appends encrypted records to one daily log file.
+/// Each record is a single write; the file is fsynced in batches.
+pub struct AppendLogWriter {
+    path: PathBuf,
+    file: File,
+    key: [u8; 32],
+    header: LogHeader,
+    date: NaiveDate,
+    next_sequence: u64,
+    /// Hash of the last record, for files whose records are chained
+    last_hash: Option<String>,
+    unsynced: usize,
+    last_sync: Instant,
+}
+
+impl AppendLogWriter {
+    /// Open `path` for appending, creating it, upgrading older formats and cutting off a torn tail first.
+    /// Records are encrypted with the key the file already uses, or the current key for new files.
+    /// Fails with a [`DamagedLog`] error if a record before the end is damaged.
+    pub fn open(path: &Path, keyring: &KeyRing) -> io::Result<Self> {
+        let date = log_file_date(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is not named after its date", path))
+        })?;
+        let (header, record_count) = prepare_log_file(path, keyring)?;
+        let key = *keyring.key(header.key_id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{:?} is encrypted with unknown key {}", path, header.key_id))
+        })?;
+        let last_hash = if header.chains_records() {
+            Some(chain_head(path, date, record_count, keyring))
+        } else {
+            None
+        };
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            key,
+            header,
+            date,
+            next_sequence: record_count,
+            last_hash,
+            unsynced: 0,
+            last_sync: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&mut self, record: &ActivityRecord) -> io::Result<()> {
+        let aad = if self.header.binds_records() {
+            record_aad(self.date, self.next_sequence, self.header.version)
+        } else {
+            Vec::new()
+        };
+        let plaintext = match &self.last_hash {
+            Some(prev) => chain_plaintext(record, prev),
+            None => record.to_plaintext(),
+        };
+        let (ciphertext, nonce) = seal_string(&plaintext, &self.key, &aad)
+            .map_err(|_| io::Error::other("Encryption failed"))?;
+
+        // One write per record so a crash can only tear the last frame
+        self.file.write_all(&encode_frame(&nonce, &ciphertext))?;
+        self.next_sequence += 1;
+        if self.last_hash.is_some() {
+            self.last_hash = Some(record_hash(&plaintext));
+        }
+        self.unsynced += 1;
+
+        if self.unsynced >= SYNC_EVERY_RECORDS || self.last_sync.elapsed() >= SYNC_INTERVAL {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Flush appended records to disk
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced > 0 {
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for AppendLogWriter {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+/// Hash the next record appended to `path` continues from: its last record, or for an empty
+/// file the last record of the previous day. An unreadable predecessor restarts the chain,
+/// which verification reports.
+fn chain_head(path: &Path, date: NaiveDate, record_count: u64, keyring: &KeyRing) -> String {
+    let head = if record_count > 0 {
+        final_hash(path, keyring).map(|hash| hash.unwrap_or_else(|| GENESIS_HASH.to_string()))
+    } else {
+        chain_anchor(path.parent().unwrap_or(Path::new(".")), date, keyring)
+    };
+    head.unwrap_or_else(|e| {
+        crate::log_warning!("activity_log", "Cannot continue the hash chain of {:?}: {}", path, e);
+        GENESIS_HASH.to_string()
+    })
+}
+
+/// Make sure `path` holds a log with checksummed framing whose last frame is complete.
+/// Returns the file's header and how many records it holds.
+fn prepare_log_file(path: &Path, keyring: &KeyRing) -> io::Result<(LogHeader, u64)> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    if content.is_empty() {
+        let header = LogHeader::current(keyring.current_id());
+        atomic_write_with_backup(path, &header.to_bytes(), None)?;
+        return Ok((header, 0));
+    }
+
+    let header = LogHeader::parse(&content);
+    let scan = scan_frames(&content);
+
+    if let Some(header) = header {
+        if header.version > LOG_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} uses log format version {}, newer than supported version {}", path, header.version, LOG_FORMAT_VERSION),
+            ));
+        }
+        if header.version == LOG_FORMAT_VERSION {
+            repair_tail(path, &content, scan.valid_len, scan.damage)?;
+            return Ok((header, scan.frames.len() as u64));
+        }
+    }
+
+    // Older format: rewrite it once in the current format and key, then append from then on
+    crate::log_info!("activity_log", "Upgrading {:?} to log format version {}", path, LOG_FORMAT_VERSION);
+    match reencrypt_log_content(path, &content, keyring, keyring.current_id()) {
+        Ok(Some(upgraded)) => {
+            atomic_write_with_backup(path, &upgraded, None)?;
+            return Ok((LogHeader::current(keyring.current_id()), scan_frames(&upgraded).frames.len() as u64));
+        }
+        Ok(None) => return Ok((LogHeader::current(keyring.current_id()), scan.frames.len() as u64)),
+        Err(e) => crate::log_warning!("activity_log", "Could not re-encrypt {:?}, keeping its key: {}", path, e),
+    }
+
+    // Records that cannot be re-sealed stay as they are; new records are appended unbound
+    let unbound = LogHeader {
+        version: UNBOUND_FORMAT_VERSION,
+        key_id: log_key_id(header),
+    };
+    if header == Some(unbound) {
+        repair_tail(path, &content, scan.valid_len, scan.damage)?;
+        return Ok((unbound, scan.frames.len() as u64));
+    }
+
+    let mut reframed = unbound.to_bytes();
+    for frame in &scan.frames {
+        reframed.extend_from_slice(&encode_frame(&frame.nonce, &frame.ciphertext));
+    }
+    if scan.damage.is_some() {
+        crate::log_warning!(
+            "activity_log",
+            "Dropping {} unreadable bytes at the end of {:?} while upgrading its format",
+            content.len() - scan.valid_len, path
+        );
+    }
+    atomic_write_with_backup(path, &reframed, None)?;
+    Ok((unbound, scan.frames.len() as u64))
+}
+
+/// Cut off an incomplete last record; damage further in has to be recovered before appending
+fn repair_tail(path: &Path, content: &[u8], valid_len: usize, damage: Option<FrameDamage>) -> io::Result<()> {
+    match damage {
+        Some(FrameDamage::TornTail) => {
+            crate::log_warning!(
+                "activity_log",
+                "Discarding incomplete record at the end of {:?} ({} bytes)",
+                path, content.len() - valid_len
+            );
+            let file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(valid_len as u64)?;
+            file.sync_all()
+        }
+        Some(FrameDamage::Corrupt) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            DamagedLog { path: path.to_path_buf(), offset: valid_len },
+        )),
+        None => Ok(()),
+    }
+}
+
+#[derive(Default)]
+struct WriterState {
+    /// The writer for the day file currently being recorded
+    writer: Option<AppendLogWriter>,
+    /// Records held back while the keys are locked
+    pending: Vec<(PathBuf, ActivityRecord)>,
+}
+
+impl WriterState {
+    fn write(&mut self, path: &Path, record: &ActivityRecord, keyring: &KeyRing) -> io::Result<()> {
+        if self.writer.as_ref().is_none_or(|w| w.path() != path) {
+            if let Some(mut previous) = self.writer.take() {
+                previous.sync()?;
+            }
+            self.writer = Some(open_or_recover(path, keyring)?);
+        }
+
+        let result = self.writer.as_mut().unwrap().append(record);
+        if result.is_err() {
+            // Reopen on the next record, which also repairs anything this write left behind
+            self.writer = None;
+        }
+        result
+    }
+}
+
+/// Open the writer for `path`, rebuilding the file first if it has a damaged record
+fn open_or_recover(path: &Path, keyring: &KeyRing) -> io::Result<AppendLogWriter> {
+    match AppendLogWriter::open(path, keyring) {
+        Err(e) if e.get_ref().is_some_and(|inner| inner.is::<DamagedLog>()) => {
+            crate::log_error!("activity_log", "{}; recovering it before appending", e);
+            let (log_dir, file_name) = match (path.parent(), path.file_name()) {
+                (Some(log_dir), Some(file_name)) => (log_dir, file_name.to_string_lossy()),
+                _ => return Err(e),
+            };
+            let (_, backup_dir) = get_platform_directories()?;
+            // The writer state is locked and has no writer open, as recovery requires
+            recover_closed_day_log(log_dir, &backup_dir, &file_name, keyring)?;
+            AppendLogWriter::open(path, keyring)
+        }
+        result => result,
+    }
+}
+
+static ACTIVITY_LOG_WRITER: Lazy<Mutex<WriterState>> = Lazy::new(|| Mutex::new(WriterState::default()));
+
+/// Append `record` to the daily log at `path`, switching files when the day changes.
+/// While the activity log keys are locked, records are kept in memory and written once they are unlocked.
+pub fn append_activity_record(path: &Path, record: &ActivityRecord) -> io::Result<()> {
+    let mut state = ACTIVITY_LOG_WRITER.lock().unwrap();
+
+    // Fast path: the day file is open and nothing is waiting
+    if state.pending.is_empty() {
+        if let Some(writer) = state.writer.as_mut().filter(|w| w.path() == path) {
+            let result = writer.append(record);
+            if result.is_err() {
+                state.writer = None;
+            }
+            return result;
+        }
+    }
+
+    let keyring = match active_keyring() {
+        Ok(keyring) => keyring,
+        Err(e) => {
+            if state.pending.is_empty() {
+                crate::log_warning!("activity_log", "Holding activity records in memory: {}", e);
+            }
+            if state.pending.len() < MAX_PENDING_RECORDS {
+                state.pending.push((path.to_path_buf(), record.clone()));
+            }
+            return Ok(());
+        }
+    };
+
+    let pending = std::mem::take(&mut state.pending);
+    if !pending.is_empty() {
+        crate::log_info!("activity_log", "Writing {} records held while the keys were locked", pending.len());
+    }
+    for (index, (pending_path, pending_record)) in pending.iter().enumerate() {
+        if let Err(e) = state.write(pending_path, pending_record, &keyring) {
+            state.pending = pending[index..].to_vec();
+            return Err(e);
+        }
+    }
+    state.write(path, record, &keyring)
+}
+
+/// Flush pending records, e.g. before copying the log or shutting down
+pub fn sync_activity_log() -> io::Result<()> {
+    match ACTIVITY_LOG_WRITER.lock().unwrap().writer.as_mut() {
+        Some(writer) => writer.sync(),
+        None => Ok(()),
+    }
+}
+
+/// Run `f` with the open log flushed and closed, so it can replace a day file without
+/// racing the event loop. The next record reopens the file.
+pub fn with_activity_log_closed<T>(f: impl FnOnce() -> T) -> T {
+    let mut state = ACTIVITY_LOG_WRITER.lock().unwrap();
+    if let Some(mut previous) = state.writer.take() {
+        if let Err(e) = previous.sync() {
+            crate::log_warning!("activity_log", "Failed to flush {:?} before closing: {}", previous.path(), e);
+        }
+    }
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity_monitor::records::read_log_file;
+    use crate::utils::encryption::{generate_key, LEGACY_KEY};
+    use crate::utils::test_dir::TestDir;
+
+    fn temp_log_path(name: &str) -> (TestDir, PathBuf) {
+        let dir = TestDir::new(&format!("log-writer-{}", name));
+        let path = dir.join("rs-fairsight(2025-01-01).txt");
+        (dir, path)
+    }
+
+    #[test]
+    fn test_append_and_repair_torn_tail() {
+        let (_dir, path) = temp_log_path("torn");
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+
+        {
+            let mut writer = AppendLogWriter::open(&path, &keyring).unwrap();
+            writer.append(&ActivityRecord::Active { start: 100, end: 101 }).unwrap();
+            writer.append(&ActivityRecord::Active { start: 100, end: 102 }).unwrap();
+        }
+
+        // Simulate a crash halfway through a third append
+        let intact_len = fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[7, 0, 0]).unwrap();
+        drop(file);
+        assert!(read_log_file(&path, &keyring).unwrap().truncated);
+
+        {
+            let mut writer = AppendLogWriter::open(&path, &keyring).unwrap();
+            assert_eq!(fs::metadata(&path).unwrap().len(), intact_len);
+            writer.append(&ActivityRecord::Inactive { start: 102, end: 500 }).unwrap();
+        }
+
+        let contents = read_log_file(&path, &keyring).unwrap();
+        assert!(!contents.truncated);
+        assert_eq!(contents.records, vec![
+            ActivityRecord::Active { start: 100, end: 101 },
+            ActivityRecord::Active { start: 100, end: 102 },
+            ActivityRecord::Inactive { start: 102, end: 500 },
+        ]);
+    }
+
+    #[test]
+    fn test_damaged_record_is_recovered_before_appending() {
+        let (dir, path) = temp_log_path("damaged");
+        let backup_dir = TestDir::new("log-writer-damaged-backup");
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+
+        AppendLogWriter::open(&path, &keyring).unwrap().append(&ActivityRecord::Active { start: 100, end: 101 }).unwrap();
+        let first_len = fs::metadata(&path).unwrap().len() as usize;
+        {
+            let mut writer = AppendLogWriter::open(&path, &keyring).unwrap();
+            writer.append(&ActivityRecord::Active { start: 100, end: 102 }).unwrap();
+            writer.append(&ActivityRecord::Active { start: 100, end: 103 }).unwrap();
+        }
+
+        // Damage the nonce of the second record
+        let mut content = fs::read(&path).unwrap();
+        content[first_len + 10] ^= 0xff;
+        fs::write(&path, &content).unwrap();
+
+        let error = AppendLogWriter::open(&path, &keyring).err().unwrap();
+        assert!(error.get_ref().unwrap().is::<DamagedLog>());
+
+        recover_closed_day_log(&dir, &backup_dir, "rs-fairsight(2025-01-01).txt", &keyring).unwrap();
+        AppendLogWriter::open(&path, &keyring).unwrap().append(&ActivityRecord::Inactive { start: 103, end: 500 }).unwrap();
+        assert_eq!(read_log_file(&path, &keyring).unwrap().records, vec![
+            ActivityRecord::Active { start: 100, end: 101 },
+            ActivityRecord::Active { start: 100, end: 103 },
+            ActivityRecord::Inactive { start: 103, end: 500 },
+        ]);
+    }
+
+    #[test]
+    fn test_upgrade_legacy_file() {
+        use crate::activity_monitor::records::encode_legacy_frame;
+
+        let (_dir, path) = temp_log_path("legacy");
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+        fs::write(&path, encode_legacy_frame("Active time 200 - 100\n", &LEGACY_KEY)).unwrap();
+
+        {
+            let mut writer = AppendLogWriter::open(&path, &keyring).unwrap();
+            writer.append(&ActivityRecord::Active { start: 100, end: 201 }).unwrap();
+        }
+
+        let contents = read_log_file(&path, &keyring).unwrap();
+        // Upgrading also moves the file off the legacy key
+        assert_eq!(contents.header, Some(LogHeader::current(1)));
+        assert_eq!(contents.records, vec![
+            ActivityRecord::Active { start: 100, end: 200 },
+            ActivityRecord::Active { start: 100, end: 201 },
+        ]);
+    }
+}
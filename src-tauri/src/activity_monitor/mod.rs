@@ -1,6 +1,6 @@
 pub use self::{
     aggregation::aggregate_log_results,
-    core::{get_current_time, initialize_time_tracking},
+    core::{get_current_time, initialize_time_tracking, start_lifecycle_tracking},
     types::TimeUpdateMessage,
 };
 use once_cell::sync::Lazy;
@@ -10,9 +10,27 @@ use std::sync::{
 };
 
 pub mod aggregation;
+pub mod breaks;
+pub mod calendar;
+pub mod chain;
+pub mod clock;
 pub mod core;
+pub mod devices;
 pub mod event_loop;
+pub mod export;
 pub mod file_operations;
+pub mod focus;
+pub mod foreground;
+pub mod importer;
+pub mod lifecycle;
+pub mod log_writer;
+pub mod records;
+pub mod recorded_zone;
+pub mod recovery;
+pub mod schedule;
+pub mod settings;
+pub mod signed_report;
+pub mod tags;
 pub mod types;
 
 // Global event queue sender
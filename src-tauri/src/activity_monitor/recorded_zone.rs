@@ -0,0 +1,145 @@
+use chrono::{FixedOffset, Local, MappedLocalTime, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// UTC offsets recorded in the logs filed under one local date, as Unix seconds and offset seconds
+type OffsetLoader = dyn Fn(NaiveDate) -> Vec<(i64, i32)> + Send + Sync;
+/// Offsets already loaded, by the date of the file they were recorded in
+type LoadedOffsets = BTreeMap<NaiveDate, Vec<(i64, FixedOffset)>>;
+
+/// The timezone the logs were written in, taken from the UTC offsets the tracker recorded, so
+/// days spent elsewhere keep their own clock times. Times with no offset recorded within a day
+/// fall back to the system timezone.
+#[derive(Clone)]
+pub struct RecordedZone {
+    load: Arc<OffsetLoader>,
+    loaded: Arc<Mutex<LoadedOffsets>>,
+}
+
+/// An offset of a [`RecordedZone`]
+#[derive(Debug, Clone)]
+pub struct RecordedOffset {
+    offset: FixedOffset,
+    zone: RecordedZone,
+}
+
+impl fmt::Debug for RecordedZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecordedZone")
+    }
+}
+
+impl fmt::Display for RecordedOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.offset.fmt(f)
+    }
+}
+
+impl Offset for RecordedOffset {
+    fn fix(&self) -> FixedOffset {
+        self.offset
+    }
+}
+
+impl RecordedZone {
+    /// A zone reading the offsets of each date with `load` the first time they are needed
+    pub fn new(load: impl Fn(NaiveDate) -> Vec<(i64, i32)> + Send + Sync + 'static) -> Self {
+        RecordedZone { load: Arc::new(load), loaded: Arc::new(Mutex::new(BTreeMap::new())) }
+    }
+
+    fn with_offset(&self, offset: FixedOffset) -> RecordedOffset {
+        RecordedOffset { offset, zone: self.clone() }
+    }
+
+    /// Offsets recorded in the files of `date` and the days around it, by time
+    fn recorded_near(&self, date: NaiveDate) -> Vec<(i64, FixedOffset)> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let mut near = Vec::new();
+        for file_date in [date.pred_opt(), Some(date), date.succ_opt()].into_iter().flatten() {
+            let offsets = loaded.entry(file_date).or_insert_with(|| {
+                (self.load)(file_date)
+                    .into_iter()
+                    .filter_map(|(at, offset_secs)| Some((at, FixedOffset::east_opt(offset_secs)?)))
+                    .collect()
+            });
+            near.extend(offsets.iter().copied());
+        }
+        near.sort_by_key(|(at, _)| *at);
+        near
+    }
+
+    /// The offset last recorded before `utc`, else the first one after it
+    fn offset_at(&self, utc: &NaiveDateTime) -> FixedOffset {
+        let timestamp = utc.and_utc().timestamp();
+        let near = self.recorded_near(utc.date());
+        match near.iter().rfind(|(at, _)| *at <= timestamp).or(near.first()) {
+            Some((_, offset)) => *offset,
+            None => Local.offset_from_utc_datetime(utc).fix(),
+        }
+    }
+}
+
+impl TimeZone for RecordedZone {
+    type Offset = RecordedOffset;
+
+    fn from_offset(offset: &RecordedOffset) -> Self {
+        offset.zone.clone()
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> MappedLocalTime<RecordedOffset> {
+        self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> MappedLocalTime<RecordedOffset> {
+        let mut candidates: Vec<FixedOffset> = self.recorded_near(local.date()).into_iter().map(|(_, o)| o).collect();
+        if candidates.is_empty() {
+            return Local.offset_from_local_datetime(local).map(|offset| self.with_offset(offset.fix()));
+        }
+        // Earliest instant first, so an hour repeated by a clock change resolves like `Local`
+        candidates.sort_by_key(|offset| std::cmp::Reverse(offset.local_minus_utc()));
+        candidates.dedup();
+        let valid: Vec<FixedOffset> = candidates
+            .into_iter()
+            .filter(|offset| self.offset_at(&(*local - *offset)) == *offset)
+            .collect();
+        match valid.as_slice() {
+            [] => MappedLocalTime::None,
+            [offset] => MappedLocalTime::Single(self.with_offset(*offset)),
+            [first, .., last] => MappedLocalTime::Ambiguous(self.with_offset(*first), self.with_offset(*last)),
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> RecordedOffset {
+        self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> RecordedOffset {
+        self.with_offset(self.offset_at(utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_keep_their_recorded_offsets() {
+        // In Berlin (UTC+1) on Jan 2, then in New York (UTC-5) from Jan 5
+        let berlin_noon = 1_735_815_600;
+        let new_york_noon = 1_736_096_400;
+        let zone = RecordedZone::new(move |date| match date.format("%d").to_string().as_str() {
+            "02" => vec![(berlin_noon, 3600)],
+            "05" => vec![(new_york_noon, -5 * 3600)],
+            _ => Vec::new(),
+        });
+        let date = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        let start = |d| zone.from_local_datetime(&date(d).and_time(NaiveTime::MIN)).single().unwrap().timestamp();
+
+        assert_eq!(start(2), 1_735_772_400);
+        assert_eq!(start(5), 1_736_053_200);
+        let clock = |timestamp| zone.timestamp_opt(timestamp, 0).unwrap().format("%H:%M").to_string();
+        assert_eq!(clock(berlin_noon), "12:00");
+        assert_eq!(clock(new_york_noon), "12:00");
+    }
+}
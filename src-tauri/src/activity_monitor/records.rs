@@ -0,0 +1,733 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::utils::encryption::{decrypt_string, seal_string};
+use crate::utils::key_manager::KeyRing;
+use super::chain::{split_chained, ChainLink};
+use super::focus::FocusRollup;
+use super::foreground::ForegroundApp;
+use super::importer::ImportedRecord;
+use super::tags::TimeTag;
+
+/// Plaintext header at the start of every daily log written by this version
+pub const LOG_MAGIC: &[u8; 8] = b"FSACTLOG";
+/// Version 1 frames records as nonce + length + ciphertext.
+/// Version 2 frames them as length + CRC32 + nonce + ciphertext so torn or damaged records are detected.
+/// Version 3 binds each record to its file date and position through AES-GCM associated data.
+/// Version 4 chains new records by embedding the hash of the record before them.
+pub const LOG_FORMAT_VERSION: u16 = 4;
+/// First format version whose records are bound to their date and sequence number
+pub const BOUND_RECORDS_VERSION: u16 = 3;
+/// First format version whose new records carry the previous record's hash
+pub const CHAINED_RECORDS_VERSION: u16 = 4;
+/// Key id of the built-in key used by all logs written before key management existed
+pub const LEGACY_KEY_ID: u32 = 0;
+pub const LOG_HEADER_LEN: usize = 8 + 2 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogHeader {
+    pub version: u16,
+    pub key_id: u32,
+}
+
+impl LogHeader {
+    pub fn current(key_id: u32) -> Self {
+        Self {
+            version: LOG_FORMAT_VERSION,
+            key_id,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(LOG_HEADER_LEN);
+        bytes.extend_from_slice(LOG_MAGIC);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.key_id.to_le_bytes());
+        bytes
+    }
+
+    /// Whether records in this file are sealed with `record_aad`
+    pub fn binds_records(&self) -> bool {
+        self.version >= BOUND_RECORDS_VERSION
+    }
+
+    /// Whether records appended to this file are hash-chained
+    pub fn chains_records(&self) -> bool {
+        self.version >= CHAINED_RECORDS_VERSION
+    }
+
+    /// Parses the header at the start of `content`. Legacy files have no header and return `None`.
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() < LOG_HEADER_LEN || &content[..8] != LOG_MAGIC {
+            return None;
+        }
+        Some(Self {
+            version: u16::from_le_bytes([content[8], content[9]]),
+            key_id: u32::from_le_bytes([content[10], content[11], content[12], content[13]]),
+        })
+    }
+}
+
+/// One entry of the encrypted daily activity log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActivityRecord {
+    /// Continuous activity from `start`; rewritten with a growing `end` while it lasts
+    Active { start: u64, end: u64 },
+    /// No input between `start` and `end`
+    Inactive { start: u64, end: u64 },
+    /// The machine was asleep from `start` to `end`
+    Suspended { start: u64, end: u64 },
+    /// The wall clock was moved by `offset_secs` (negative when set back), noticed at `at`
+    ClockAdjusted { at: u64, offset_secs: i64 },
+    /// Wall-clock time went backwards without a detected adjustment; legacy records carry no times
+    TimeSyncError {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_time: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_time: Option<u64>,
+    },
+    /// `app` had focus from `start` to `end`
+    Application { start: u64, end: u64, app: ForegroundApp },
+    /// Free-text note attached to a time range
+    Annotation { start: u64, end: u64, text: String },
+    /// Project, task or note attached to a time range; kept in the day's tag file
+    Tag(TimeTag),
+    /// The user was reminded to take a break after `worked_secs` of continuous work
+    BreakReminder { at: u64, worked_secs: u64, repeat: u32 },
+    /// Input paused from `start` to `end` following the reminder at `reminded_at`
+    BreakTaken { reminded_at: u64, start: u64, end: u64 },
+    /// Local time was `offset_secs` ahead of UTC (behind if negative) from `at` on.
+    /// Written before the first record of each file and whenever the offset changes.
+    UtcOffset { at: u64, offset_secs: i32 },
+    /// The tracker started at `at` on a machine that booted at `boot_time`
+    Startup { at: u64, boot_time: u64 },
+    /// The tracker was still running at `at`; written every minute
+    Heartbeat { at: u64 },
+    /// The tracker was closed at `at`
+    Shutdown { at: u64 },
+    /// A record converted from another tracker's history; kept in the day's import file
+    Imported(ImportedRecord),
+    /// Focus sessions computed for a day; kept in the day's focus file
+    FocusRollup(FocusRollup),
+    /// A record kind written by a newer version of the application
+    #[serde(other)]
+    Unknown,
+}
+
+impl ActivityRecord {
+    /// Time range the record describes, if any
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        match self {
+            ActivityRecord::Active { start, end }
+            | ActivityRecord::Inactive { start, end }
+            | ActivityRecord::Suspended { start, end }
+            | ActivityRecord::Application { start, end, .. }
+            | ActivityRecord::Annotation { start, end, .. }
+            | ActivityRecord::BreakTaken { start, end, .. } => Some((*start, *end)),
+            ActivityRecord::Tag(tag) => Some((tag.start, tag.end)),
+            ActivityRecord::ClockAdjusted { at, offset_secs } => {
+                let previous = at.saturating_add_signed(-offset_secs);
+                Some(((*at).min(previous), (*at).max(previous)))
+            }
+            ActivityRecord::BreakReminder { at, .. }
+            | ActivityRecord::UtcOffset { at, .. }
+            | ActivityRecord::Startup { at, .. }
+            | ActivityRecord::Heartbeat { at }
+            | ActivityRecord::Shutdown { at } => Some((*at, *at)),
+            ActivityRecord::Imported(imported) => imported.record.time_range(),
+            ActivityRecord::FocusRollup(rollup) => Some((rollup.day_start as u64, rollup.day_end as u64)),
+            ActivityRecord::TimeSyncError { .. } | ActivityRecord::Unknown => None,
+        }
+    }
+
+    pub fn to_plaintext(&self) -> String {
+        // Serializing these plain data variants cannot fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decodes a decrypted record, accepting both JSON records and the legacy text lines
+    pub fn from_plaintext(plaintext: &str) -> Result<Self, String> {
+        let line = plaintext.trim();
+        if line.starts_with('{') {
+            return serde_json::from_str(line).map_err(|e| format!("Invalid record '{}': {}", line, e));
+        }
+        parse_legacy_line(line).ok_or_else(|| format!("Unrecognised legacy record '{}'", line))
+    }
+}
+
+/// Parses the free-text records written before the typed format
+fn parse_legacy_line(line: &str) -> Option<ActivityRecord> {
+    if line == "Time Sync error" {
+        return Some(ActivityRecord::TimeSyncError {
+            last_time: None,
+            current_time: None,
+        });
+    }
+
+    if let Some(rest) = line.strip_prefix("Application time ") {
+        let (end_str, rest) = rest.split_once(" - ")?;
+        let (start_str, app_json) = rest.split_once(' ')?;
+        return Some(ActivityRecord::Application {
+            start: start_str.trim().parse().ok()?,
+            end: end_str.trim().parse().ok()?,
+            app: serde_json::from_str(app_json.trim()).ok()?,
+        });
+    }
+
+    let (is_active, rest) = if let Some(rest) = line.strip_prefix("Active time ") {
+        (true, rest)
+    } else if let Some(rest) = line.strip_prefix("Inactive time over 5seconds ") {
+        (false, rest)
+    } else {
+        return None;
+    };
+
+    let (end_str, start_str) = rest.split_once(" - ")?;
+    let end = end_str.trim().parse().ok()?;
+    let start = start_str.trim().parse().ok()?;
+    Some(if is_active {
+        ActivityRecord::Active { start, end }
+    } else {
+        ActivityRecord::Inactive { start, end }
+    })
+}
+
+const NONCE_LEN: usize = 12;
+/// Length (4) + CRC32 (4) in front of every version 2 record
+const FRAME_PREFIX_LEN: usize = 4 + 4;
+/// Records are small JSON documents; anything larger is a corrupted length
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// One encrypted record located in a log file
+#[derive(Debug, Clone)]
+pub struct LogFrame {
+    /// Byte offset of the frame within the file
+    pub offset: usize,
+    pub nonce: [u8; 12],
+    pub ciphertext: Vec<u8>,
+}
+
+impl LogFrame {
+    pub fn decrypt(&self, key: &[u8; 32], aad: &[u8]) -> Result<String, String> {
+        let mut encrypted_data = self.ciphertext.clone();
+        decrypt_string(&mut encrypted_data, key, self.nonce, aad)
+            .map_err(|e| format!("Decryption failed for record at byte {}: {:?}", self.offset, e))
+    }
+}
+
+/// Associated data sealed into a record: the day file it belongs to, its position in that file
+/// and the format version, so records cannot be reordered, replayed or moved between days
+pub fn record_aad(date: NaiveDate, sequence: u64, version: u16) -> Vec<u8> {
+    let mut aad = date.format("%Y-%m-%d").to_string().into_bytes();
+    aad.extend_from_slice(&sequence.to_le_bytes());
+    aad.extend_from_slice(&version.to_le_bytes());
+    aad
+}
+
+/// Associated data for record `sequence` of a file with `header`; empty for formats before binding
+fn header_aad(header: Option<LogHeader>, date: Option<NaiveDate>, sequence: u64) -> Vec<u8> {
+    match (header, date) {
+        (Some(header), Some(date)) if header.binds_records() => record_aad(date, sequence, header.version),
+        _ => Vec::new(),
+    }
+}
+
+/// Extracts the date from a daily log or backup name, e.g. "rs-fairsight(2025-01-31).txt.backup_..."
+pub fn log_file_date(file_path: &Path) -> Option<NaiveDate> {
+    let file_name = file_path.file_name()?.to_string_lossy().to_string();
+    let (date_str, _) = file_name.strip_prefix("rs-fairsight(")?.split_once(')')?;
+    NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok()
+}
+
+/// Why scanning a log file stopped before its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDamage {
+    /// The last record is incomplete, as left by a crash in the middle of an append
+    TornTail,
+    /// A record in the middle of the file fails its length or checksum check
+    Corrupt,
+}
+
+#[derive(Debug, Default)]
+pub struct FrameScan {
+    pub frames: Vec<LogFrame>,
+    /// Offset just past the last intact frame
+    pub valid_len: usize,
+    pub damage: Option<FrameDamage>,
+}
+
+/// Record framing used by a format version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Legacy and version 1: nonce (12) + length (4) + ciphertext
+    Legacy,
+    /// Version 2: length (4) + CRC32 (4) + nonce (12) + ciphertext
+    Checksummed,
+}
+
+impl Framing {
+    pub fn for_header(header: Option<LogHeader>) -> Self {
+        match header {
+            Some(header) if header.version >= 2 => Framing::Checksummed,
+            _ => Framing::Legacy,
+        }
+    }
+}
+
+/// Decodes a complete, intact frame starting at `offset`, returning it with the offset just past it.
+/// Unlike `scan_frames` this does not classify damage, which lets recovery probe arbitrary offsets.
+pub fn frame_at(content: &[u8], offset: usize, framing: Framing) -> Option<(LogFrame, usize)> {
+    let rest = content.get(offset..)?;
+    match framing {
+        Framing::Checksummed => {
+            let payload_len = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+            let checksum = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?);
+            if !(NONCE_LEN..=MAX_FRAME_LEN).contains(&payload_len) {
+                return None;
+            }
+            let payload = rest.get(FRAME_PREFIX_LEN..FRAME_PREFIX_LEN + payload_len)?;
+            if crc32fast::hash(payload) != checksum {
+                return None;
+            }
+            let frame = LogFrame {
+                offset,
+                nonce: payload[..NONCE_LEN].try_into().ok()?,
+                ciphertext: payload[NONCE_LEN..].to_vec(),
+            };
+            Some((frame, offset + FRAME_PREFIX_LEN + payload_len))
+        }
+        Framing::Legacy => {
+            let nonce: [u8; 12] = rest.get(..NONCE_LEN)?.try_into().ok()?;
+            let encrypted_len = u32::from_le_bytes(rest.get(NONCE_LEN..NONCE_LEN + 4)?.try_into().ok()?) as usize;
+            if encrypted_len > MAX_FRAME_LEN {
+                return None;
+            }
+            let data_start = NONCE_LEN + 4;
+            let ciphertext = rest.get(data_start..data_start + encrypted_len)?.to_vec();
+            Some((LogFrame { offset, nonce, ciphertext }, offset + data_start + encrypted_len))
+        }
+    }
+}
+
+/// Encodes a version 2 frame: length and CRC32 of nonce + ciphertext, then nonce + ciphertext
+pub fn encode_frame(nonce: &[u8; 12], ciphertext: &[u8]) -> Vec<u8> {
+    let payload_len = NONCE_LEN + ciphertext.len();
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(nonce);
+    hasher.update(ciphertext);
+
+    let mut frame = Vec::with_capacity(FRAME_PREFIX_LEN + payload_len);
+    frame.extend_from_slice(&(payload_len as u32).to_le_bytes());
+    frame.extend_from_slice(&hasher.finalize().to_le_bytes());
+    frame.extend_from_slice(nonce);
+    frame.extend_from_slice(ciphertext);
+    frame
+}
+
+/// Splits a log file into its encrypted records, using the framing of the file's format version.
+/// Scanning stops at the first damaged record.
+pub fn scan_frames(content: &[u8]) -> FrameScan {
+    let header = LogHeader::parse(content);
+    let start = if header.is_some() { LOG_HEADER_LEN } else { 0 };
+    match Framing::for_header(header) {
+        Framing::Checksummed => scan_checksummed_frames(content, start),
+        Framing::Legacy => scan_legacy_frames(content, start),
+    }
+}
+
+fn scan_checksummed_frames(content: &[u8], start: usize) -> FrameScan {
+    let mut scan = FrameScan {
+        valid_len: start,
+        ..Default::default()
+    };
+    let mut offset = start;
+
+    while offset < content.len() {
+        let remaining = content.len() - offset;
+        if remaining < FRAME_PREFIX_LEN {
+            scan.damage = Some(FrameDamage::TornTail);
+            break;
+        }
+
+        let payload_len = u32::from_le_bytes(content[offset..offset + 4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(content[offset + 4..offset + 8].try_into().unwrap());
+        if !(NONCE_LEN..=MAX_FRAME_LEN).contains(&payload_len) {
+            scan.damage = Some(FrameDamage::Corrupt);
+            break;
+        }
+        if remaining < FRAME_PREFIX_LEN + payload_len {
+            scan.damage = Some(FrameDamage::TornTail);
+            break;
+        }
+
+        let payload_start = offset + FRAME_PREFIX_LEN;
+        let payload = &content[payload_start..payload_start + payload_len];
+        if crc32fast::hash(payload) != checksum {
+            // A bad checksum on the very last frame is a partially flushed append
+            let is_tail = payload_start + payload_len == content.len();
+            scan.damage = Some(if is_tail { FrameDamage::TornTail } else { FrameDamage::Corrupt });
+            break;
+        }
+
+        scan.frames.push(LogFrame {
+            offset,
+            nonce: payload[..NONCE_LEN].try_into().unwrap(),
+            ciphertext: payload[NONCE_LEN..].to_vec(),
+        });
+        offset = payload_start + payload_len;
+        scan.valid_len = offset;
+    }
+
+    scan
+}
+
+/// Legacy and version 1 framing: nonce (12) + length (4) + ciphertext, without a checksum
+fn scan_legacy_frames(content: &[u8], start: usize) -> FrameScan {
+    let mut scan = FrameScan {
+        valid_len: start,
+        ..Default::default()
+    };
+    let mut offset = start;
+
+    while offset < content.len() {
+        if content.len() - offset < NONCE_LEN + 4 {
+            scan.damage = Some(FrameDamage::TornTail);
+            break;
+        }
+
+        let nonce: [u8; 12] = content[offset..offset + NONCE_LEN].try_into().unwrap();
+        let len_start = offset + NONCE_LEN;
+        let encrypted_len = u32::from_le_bytes(content[len_start..len_start + 4].try_into().unwrap()) as usize;
+        let data_start = len_start + 4;
+
+        if content.len() - data_start < encrypted_len {
+            scan.damage = Some(FrameDamage::TornTail);
+            break;
+        }
+
+        scan.frames.push(LogFrame {
+            offset,
+            nonce,
+            ciphertext: content[data_start..data_start + encrypted_len].to_vec(),
+        });
+        offset = data_start + encrypted_len;
+        scan.valid_len = offset;
+    }
+
+    scan
+}
+
+/// Decoded contents of one daily log file
+#[derive(Debug, Default)]
+pub struct LogContents {
+    pub header: Option<LogHeader>,
+    pub records: Vec<ActivityRecord>,
+    /// Hash chain position of every decrypted record, readable or not
+    pub links: Vec<ChainLink>,
+    /// Records that decrypted but could not be understood
+    pub unreadable: Vec<String>,
+    /// The file ended in the middle of a record
+    pub truncated: bool,
+}
+
+/// Key id a log was encrypted with; files without a header predate key management
+pub fn log_key_id(header: Option<LogHeader>) -> u32 {
+    header.map_or(LEGACY_KEY_ID, |header| header.key_id)
+}
+
+/// Look up the key for a log in `keyring`
+pub fn log_key(header: Option<LogHeader>, keyring: &KeyRing) -> Result<[u8; 32], String> {
+    let key_id = log_key_id(header);
+    keyring
+        .key(key_id)
+        .copied()
+        .ok_or_else(|| format!("Log is encrypted with key {}, which is not in the key store", key_id))
+}
+
+/// Date a log's records are bound to; only needed for formats that bind records
+fn bound_date(file_path: &Path, header: Option<LogHeader>) -> Result<Option<NaiveDate>, String> {
+    if !header.is_some_and(|header| header.binds_records()) {
+        return Ok(None);
+    }
+    log_file_date(file_path)
+        .map(Some)
+        .ok_or_else(|| format!("{:?} is not named after its date, so its records cannot be verified", file_path))
+}
+
+/// How far to look for the position a misplaced record was sealed for
+const MISPLACED_SEARCH_DAYS: i64 = 31;
+const MISPLACED_SEARCH_RECORDS: u64 = 64;
+
+/// Explain why record `sequence` of a bound log failed to decrypt: moved within the file,
+/// moved from another day's file, or damaged. Only positions near `sequence` are tried.
+fn describe_misplaced_record(
+    file_path: &Path,
+    frame: &LogFrame,
+    key: &[u8; 32],
+    header: LogHeader,
+    date: NaiveDate,
+    sequence: u64
+) -> String {
+    let prefix = format!("{:?}: record #{} at byte {} is out of place", file_path, sequence, frame.offset);
+    let nearby = || sequence.saturating_sub(MISPLACED_SEARCH_RECORDS)..=sequence + MISPLACED_SEARCH_RECORDS;
+
+    for other in nearby() {
+        if other != sequence && frame.decrypt(key, &record_aad(date, other, header.version)).is_ok() {
+            let kind = if other < sequence { "replayed or reordered" } else { "earlier records were removed" };
+            return format!("{}: it was sealed as record #{} of this file ({})", prefix, other, kind);
+        }
+    }
+
+    for offset in (-MISPLACED_SEARCH_DAYS..=MISPLACED_SEARCH_DAYS).filter(|offset| *offset != 0) {
+        let Some(other_date) = date.checked_add_signed(chrono::Duration::days(offset)) else { continue };
+        for other in nearby() {
+            if frame.decrypt(key, &record_aad(other_date, other, header.version)).is_ok() {
+                return format!("{}: it was sealed as record #{} of the {} log", prefix, other, other_date);
+            }
+        }
+    }
+
+    format!("{:?}: record #{} at byte {} fails authentication (damaged or forged)", file_path, sequence, frame.offset)
+}
+
+/// Where the first record of a bound log that [`read_log_file`] rejects was sealed, if it can be
+/// found. Slower than reading the log, so only worth calling when verifying or recovering it.
+pub fn explain_log_failure(file_path: &Path, keyring: &KeyRing) -> Option<String> {
+    let content = fs::read(file_path).ok()?;
+    let header = LogHeader::parse(&content)?;
+    let key = log_key(Some(header), keyring).ok()?;
+    let date = bound_date(file_path, Some(header)).ok()??;
+    scan_frames(&content).frames.iter().enumerate().find_map(|(sequence, frame)| {
+        let sequence = sequence as u64;
+        frame.decrypt(&key, &header_aad(Some(header), Some(date), sequence)).is_err().then(|| {
+            describe_misplaced_record(file_path, frame, &key, header, date, sequence)
+        })
+    })
+}
+
+/// Reads and decrypts a daily log file, legacy or current format.
+/// Fails if a record cannot be decrypted, is not where it was sealed, or a record before the last one is damaged.
+pub fn read_log_file(file_path: &Path, keyring: &KeyRing) -> Result<LogContents, Box<dyn std::error::Error>> {
+    let content = fs::read(file_path)?;
+    let mut contents = LogContents {
+        header: LogHeader::parse(&content),
+        ..Default::default()
+    };
+
+    if let Some(header) = contents.header {
+        if header.version > LOG_FORMAT_VERSION {
+            return Err(format!(
+                "{:?} uses log format version {}, newer than supported version {}",
+                file_path, header.version, LOG_FORMAT_VERSION
+            ).into());
+        }
+    }
+    let key = log_key(contents.header, keyring).map_err(|e| format!("{:?}: {}", file_path, e))?;
+    let date = bound_date(file_path, contents.header)?;
+
+    let scan = scan_frames(&content);
+    match scan.damage {
+        Some(FrameDamage::TornTail) => contents.truncated = true,
+        Some(FrameDamage::Corrupt) => {
+            return Err(format!("{:?} has a damaged record at byte {}", file_path, scan.valid_len).into());
+        }
+        None => {}
+    }
+
+    for (sequence, frame) in scan.frames.iter().enumerate() {
+        let sequence = sequence as u64;
+        let plaintext = match frame.decrypt(&key, &header_aad(contents.header, date, sequence)) {
+            Ok(plaintext) => plaintext,
+            // Where the record came from is left to `explain_log_failure`
+            Err(_) if date.is_some() => {
+                return Err(format!(
+                    "{:?}: record #{} at byte {} is out of place or damaged",
+                    file_path, sequence, frame.offset
+                ).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let (link, record) = split_chained(&plaintext);
+        contents.links.push(link);
+        match record {
+            Ok(record) => contents.records.push(record),
+            Err(e) => contents.unreadable.push(e),
+        }
+    }
+
+    Ok(contents)
+}
+
+/// Seals `plaintexts` as a complete current-format log for `date`
+pub fn encode_log(plaintexts: &[String], key: &[u8; 32], key_id: u32, date: NaiveDate) -> Result<Vec<u8>, String> {
+    let mut content = LogHeader::current(key_id).to_bytes();
+    for (sequence, plaintext) in plaintexts.iter().enumerate() {
+        let aad = record_aad(date, sequence as u64, LOG_FORMAT_VERSION);
+        let (ciphertext, nonce) = seal_string(plaintext, key, &aad)
+            .map_err(|_| "Encryption failed".to_string())?;
+        content.extend_from_slice(&encode_frame(&nonce, &ciphertext));
+    }
+    Ok(content)
+}
+
+/// Re-encrypts the log at `file_path` with key `key_id` in the current format.
+/// Returns `None` when the log already is. An incomplete last record is dropped; any other damage is an error.
+pub fn reencrypt_log_content(
+    file_path: &Path,
+    content: &[u8],
+    keyring: &KeyRing,
+    key_id: u32
+) -> Result<Option<Vec<u8>>, String> {
+    let header = LogHeader::parse(content);
+    if header == Some(LogHeader::current(key_id)) && scan_frames(content).damage.is_none() {
+        return Ok(None);
+    }
+    if let Some(header) = header {
+        if header.version > LOG_FORMAT_VERSION {
+            return Err(format!("Log format version {} is newer than supported version {}", header.version, LOG_FORMAT_VERSION));
+        }
+    }
+
+    let old_key = log_key(header, keyring)?;
+    let new_key = keyring
+        .key(key_id)
+        .ok_or_else(|| format!("Key {} is not in the key store", key_id))?;
+    let old_date = bound_date(file_path, header)?;
+    let new_date = log_file_date(file_path)
+        .ok_or_else(|| format!("{:?} is not named after its date", file_path))?;
+    let scan = scan_frames(content);
+    if scan.damage == Some(FrameDamage::Corrupt) {
+        return Err(format!("Damaged record at byte {}", scan.valid_len));
+    }
+
+    let plaintexts = scan
+        .frames
+        .iter()
+        .enumerate()
+        .map(|(sequence, frame)| frame.decrypt(&old_key, &header_aad(header, old_date, sequence as u64)))
+        .collect::<Result<Vec<_>, _>>()?;
+    encode_log(&plaintexts, new_key, key_id, new_date).map(Some)
+}
+
+/// Builds a pre-header legacy frame, as written by versions before the log format header
+#[cfg(test)]
+pub fn encode_legacy_frame(plaintext: &str, key: &[u8; 32]) -> Vec<u8> {
+    let (ciphertext, nonce) = seal_string(plaintext, key, &[]).unwrap();
+    let mut frame = nonce.to_vec();
+    frame.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&ciphertext);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    #[test]
+    fn test_legacy_lines() {
+        assert_eq!(
+            ActivityRecord::from_plaintext("Active time 200 - 100\n"),
+            Ok(ActivityRecord::Active { start: 100, end: 200 })
+        );
+        assert_eq!(
+            ActivityRecord::from_plaintext("Inactive time over 5seconds 900 - 300\n"),
+            Ok(ActivityRecord::Inactive { start: 300, end: 900 })
+        );
+        assert_eq!(
+            ActivityRecord::from_plaintext("Time Sync error\n"),
+            Ok(ActivityRecord::TimeSyncError { last_time: None, current_time: None })
+        );
+        assert!(ActivityRecord::from_plaintext("garbage").is_err());
+    }
+
+    #[test]
+    fn test_typed_records() {
+        let record = ActivityRecord::Annotation {
+            start: 1,
+            end: 2,
+            text: "standup - planning".to_string(),
+        };
+        assert_eq!(ActivityRecord::from_plaintext(&record.to_plaintext()), Ok(record));
+        assert_eq!(
+            ActivityRecord::from_plaintext(r#"{"type":"from_the_future","x":1}"#),
+            Ok(ActivityRecord::Unknown)
+        );
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = LogHeader::current(7);
+        assert_eq!(LogHeader::parse(&header.to_bytes()), Some(header));
+        assert_eq!(LogHeader::parse(&[0u8; 32]), None);
+    }
+
+    #[test]
+    fn test_checksummed_frames() {
+        let mut content = LogHeader::current(LEGACY_KEY_ID).to_bytes();
+        content.extend_from_slice(&encode_frame(&[1; 12], b"first record"));
+        content.extend_from_slice(&encode_frame(&[2; 12], b"second record"));
+        let intact_len = content.len();
+
+        let scan = scan_frames(&content);
+        assert_eq!(scan.frames.len(), 2);
+        assert_eq!(scan.frames[1].ciphertext, b"second record");
+        assert_eq!(scan.valid_len, intact_len);
+        assert_eq!(scan.damage, None);
+
+        // A partially written append is a torn tail
+        let third = encode_frame(&[3; 12], b"third record");
+        let mut torn = content.clone();
+        torn.extend_from_slice(&third[..third.len() - 3]);
+        let scan = scan_frames(&torn);
+        assert_eq!(scan.frames.len(), 2);
+        assert_eq!(scan.valid_len, intact_len);
+        assert_eq!(scan.damage, Some(FrameDamage::TornTail));
+
+        // A flipped bit before the last record is corruption
+        let mut corrupt = content.clone();
+        corrupt[LOG_HEADER_LEN + FRAME_PREFIX_LEN + 2] ^= 0x01;
+        let scan = scan_frames(&corrupt);
+        assert!(scan.frames.is_empty());
+        assert_eq!(scan.damage, Some(FrameDamage::Corrupt));
+    }
+
+    #[test]
+    fn test_misplaced_records_are_rejected() {
+        let dir = TestDir::new("records");
+        let keyring = KeyRing::with_key(1, crate::utils::encryption::generate_key().unwrap());
+        let key = keyring.key(1).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let path = dir.join("rs-fairsight(2025-01-02).txt");
+
+        let plaintexts: Vec<String> = (1..=3)
+            .map(|end| ActivityRecord::Active { start: 0, end }.to_plaintext())
+            .collect();
+        let content = encode_log(&plaintexts, key, 1, date).unwrap();
+        fs::write(&path, &content).unwrap();
+        assert_eq!(read_log_file(&path, &keyring).unwrap().records.len(), 3);
+
+        // Swap the first two records
+        let scan = scan_frames(&content);
+        let offsets: Vec<usize> = scan.frames.iter().map(|frame| frame.offset).collect();
+        let first = &content[offsets[0]..offsets[1]];
+        let second = &content[offsets[1]..offsets[2]];
+        let swapped = [&content[..LOG_HEADER_LEN], second, first, &content[offsets[2]..]].concat();
+        fs::write(&path, &swapped).unwrap();
+        let error = read_log_file(&path, &keyring).unwrap_err().to_string();
+        assert!(error.contains("record #0"), "{}", error);
+        let explanation = explain_log_failure(&path, &keyring).unwrap();
+        assert!(explanation.contains("sealed as record #1"), "{}", explanation);
+
+        // The same file presented as another day's log
+        let moved = dir.join("rs-fairsight(2025-01-03).txt");
+        fs::write(&moved, &content).unwrap();
+        assert!(read_log_file(&moved, &keyring).is_err());
+        let explanation = explain_log_failure(&moved, &keyring).unwrap();
+        assert!(explanation.contains("of the 2025-01-02 log"), "{}", explanation);
+    }
+}
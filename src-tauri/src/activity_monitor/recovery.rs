@@ -0,0 +1,398 @@
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::file_utils::atomic_write_with_backup;
+use crate::utils::key_manager::KeyRing;
+use super::core::get_current_time;
+use super::log_writer::with_activity_log_closed;
+use super::records::{
+    encode_log, frame_at, log_file_date, log_key, record_aad, Framing, LogFrame, LogHeader, LEGACY_KEY_ID,
+    LOG_FORMAT_VERSION, LOG_HEADER_LEN,
+};
+
+/// Bytes of the original file that held no readable record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LostRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Outcome of salvaging one daily activity log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub file_name: String,
+    pub recovered_at: u64,
+    pub original_size: usize,
+    /// Records in the rewritten file, including those merged from backups
+    pub records_kept: usize,
+    pub records_from_backups: usize,
+    pub lost_ranges: Vec<LostRange>,
+    /// Backups that contributed records missing from the damaged file
+    pub backups_merged: Vec<String>,
+    /// Copy of the damaged file kept in the backup directory
+    pub damaged_copy: Option<String>,
+}
+
+// Reports produced since startup, newest last
+static RECOVERY_REPORTS: Lazy<Mutex<Vec<RecoveryReport>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+pub fn get_recovery_reports() -> Vec<RecoveryReport> {
+    RECOVERY_REPORTS.lock().unwrap().clone()
+}
+
+struct Salvage {
+    key_id: u32,
+    plaintexts: Vec<String>,
+    lost_ranges: Vec<LostRange>,
+}
+
+/// How far past the expected position a bound record may have been sealed, e.g. after lost records
+const SEQUENCE_SEARCH_WINDOW: u64 = 256;
+/// Longest ciphertext salvage tries to decrypt. Records are a few hundred bytes, so inside a
+/// damaged region almost every offset is skipped on its length field alone.
+const SALVAGE_MAX_CIPHERTEXT_LEN: usize = 64 * 1024;
+/// AES-GCM tag plus the shortest record
+const MIN_CIPHERTEXT_LEN: usize = 16 + 2;
+
+/// Collect every record in `content` that decrypts with the file's key at a plausible position.
+/// After a damaged region, scanning resumes at the next offset where an intact record starts.
+fn salvage_frames(content: &[u8], date: Option<NaiveDate>, keyring: &KeyRing) -> Result<Salvage, String> {
+    let header = LogHeader::parse(content);
+    if let Some(found) = header {
+        let key = log_key(header, keyring)?;
+        let binding = if found.binds_records() {
+            Some((date.ok_or("Log is not named after its date, so its records cannot be verified")?, found.version))
+        } else {
+            None
+        };
+        return Ok(salvage_with(content, found.key_id, LOG_HEADER_LEN, Framing::for_header(header), &key, binding));
+    }
+
+    // Either a legacy file or one whose header was damaged: keep whichever reading recovers more
+    let legacy_key = log_key(None, keyring)?;
+    let legacy = salvage_with(content, LEGACY_KEY_ID, 0, Framing::Legacy, &legacy_key, None);
+    if content.len() > LOG_HEADER_LEN {
+        let current_id = keyring.current_id();
+        let current_key = *keyring.key(current_id).ok_or("Current key is not in the key store")?;
+        let binding = date.map(|date| (date, LOG_FORMAT_VERSION));
+        let checksummed = salvage_with(content, current_id, LOG_HEADER_LEN, Framing::Checksummed, &current_key, binding);
+        if checksummed.plaintexts.len() > legacy.plaintexts.len() {
+            return Ok(checksummed);
+        }
+    }
+    Ok(legacy)
+}
+
+/// Decrypt `frame` as record `expected` or a later one of a bound log, or unbound otherwise.
+/// Returns the plaintext and the position it was sealed for.
+fn open_frame(frame: &LogFrame, key: &[u8; 32], binding: Option<(NaiveDate, u16)>, expected: u64) -> Option<(String, u64)> {
+    match binding {
+        Some((date, version)) => (expected..expected + SEQUENCE_SEARCH_WINDOW).find_map(|sequence| {
+            let plaintext = frame.decrypt(key, &record_aad(date, sequence, version)).ok()?;
+            Some((plaintext, sequence))
+        }),
+        None => frame.decrypt(key, &[]).ok().map(|plaintext| (plaintext, expected)),
+    }
+}
+
+fn salvage_with(
+    content: &[u8],
+    key_id: u32,
+    start: usize,
+    framing: Framing,
+    key: &[u8; 32],
+    binding: Option<(NaiveDate, u16)>
+) -> Salvage {
+    let mut salvage = Salvage {
+        key_id,
+        plaintexts: Vec::new(),
+        lost_ranges: Vec::new(),
+    };
+    let mut damaged_from = None;
+    let mut offset = start;
+    let mut expected = 0;
+
+    while offset < content.len() {
+        // The AES-GCM tag makes a successful decrypt a reliable sign of a real record boundary.
+        // Records replayed from earlier positions or other days fail it too and are dropped.
+        let opened = if plausible_frame(content, offset, framing) {
+            frame_at(content, offset, framing)
+                .and_then(|(frame, end)| Some((open_frame(&frame, key, binding, expected)?, end)))
+        } else {
+            None
+        };
+        match opened {
+            Some(((plaintext, sequence), end)) => {
+                if let Some(lost_start) = damaged_from.take() {
+                    salvage.lost_ranges.push(LostRange { start: lost_start, end: offset });
+                }
+                salvage.plaintexts.push(plaintext);
+                expected = sequence + 1;
+                offset = end;
+            }
+            None => {
+                damaged_from.get_or_insert(offset);
+                offset += 1;
+            }
+        }
+    }
+
+    if let Some(lost_start) = damaged_from {
+        salvage.lost_ranges.push(LostRange { start: lost_start, end: content.len() });
+    }
+    salvage
+}
+
+/// Whether the length field of a frame at `offset` could belong to a record, checked before
+/// anything is copied or decrypted
+fn plausible_frame(content: &[u8], offset: usize, framing: Framing) -> bool {
+    // Legacy frames start with the nonce; checksummed lengths include it
+    let (length_at, nonce_len) = match framing {
+        Framing::Legacy => (12, 0),
+        Framing::Checksummed => (0, 12),
+    };
+    let Some(length) = content
+        .get(offset + length_at..offset + length_at + 4)
+        .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+    else {
+        return false;
+    };
+    let ciphertext_len = (u32::from_le_bytes(length) as usize).saturating_sub(nonce_len);
+    (MIN_CIPHERTEXT_LEN..=SALVAGE_MAX_CIPHERTEXT_LEN).contains(&ciphertext_len)
+}
+
+/// Interleave records found only in a backup with `records`, keeping the order both share.
+/// Records are identified by their plaintext, which carries their timestamps and survives re-sealing.
+fn merge_records(records: Vec<String>, backup_records: Vec<String>) -> (Vec<String>, usize) {
+    let positions: HashMap<&str, usize> = records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| (record.as_str(), i))
+        .collect();
+    let mut merged = Vec::with_capacity(records.len() + backup_records.len());
+    let mut seen = HashSet::new();
+    let mut added = 0;
+    let mut next = 0;
+
+    for backup_record in backup_records {
+        match positions.get(backup_record.as_str()) {
+            Some(&position) => {
+                // Emit our own records up to the shared one
+                while next <= position {
+                    if seen.insert(records[next].clone()) {
+                        merged.push(records[next].clone());
+                    }
+                    next += 1;
+                }
+            }
+            None => {
+                if seen.insert(backup_record.clone()) {
+                    merged.push(backup_record);
+                    added += 1;
+                }
+            }
+        }
+    }
+    for record in &records[next.min(records.len())..] {
+        if seen.insert(record.clone()) {
+            merged.push(record.clone());
+        }
+    }
+
+    (merged, added)
+}
+
+/// Backups of `file_name`, newest first
+fn find_backups(backup_dir: &Path, file_name: &str) -> Vec<PathBuf> {
+    let prefix = format!("{}.backup_", file_name);
+    let mut backups: Vec<_> = fs::read_dir(backup_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.modified().ok()?)))
+        .collect();
+    backups.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    backups.into_iter().map(|(path, _)| path).collect()
+}
+
+/// Rebuild `file_name` in `log_dir` from every record that still decrypts, merging in records that
+/// only survive in backups. The damaged original is kept next to the backups.
+pub fn recover_day_log(
+    log_dir: &Path,
+    backup_dir: &Path,
+    file_name: &str,
+    keyring: &KeyRing
+) -> io::Result<RecoveryReport> {
+    with_activity_log_closed(|| recover_closed_day_log(log_dir, backup_dir, file_name, keyring))
+}
+
+/// [`recover_day_log`] for callers that already closed the writer of the day's log
+pub fn recover_closed_day_log(
+    log_dir: &Path,
+    backup_dir: &Path,
+    file_name: &str,
+    keyring: &KeyRing
+) -> io::Result<RecoveryReport> {
+    let report = rebuild_day_log(log_dir, backup_dir, file_name, keyring)?;
+
+    crate::log_info!(
+        "recovery",
+        "Recovered {}: kept {} records ({} from backups), lost {} byte range(s)",
+        file_name, report.records_kept, report.records_from_backups, report.lost_ranges.len()
+    );
+    for range in &report.lost_ranges {
+        crate::log_warning!("recovery", "{}: bytes {}..{} could not be recovered", file_name, range.start, range.end);
+    }
+
+    RECOVERY_REPORTS.lock().unwrap().push(report.clone());
+    Ok(report)
+}
+
+fn rebuild_day_log(
+    log_dir: &Path,
+    backup_dir: &Path,
+    file_name: &str,
+    keyring: &KeyRing
+) -> io::Result<RecoveryReport> {
+    let log_path = log_dir.join(file_name);
+    let date = log_file_date(&log_path);
+
+    let content = match fs::read(&log_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let salvage = salvage_frames(&content, date, keyring)
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+    let mut records = salvage.plaintexts;
+    let mut report = RecoveryReport {
+        file_name: file_name.to_string(),
+        recovered_at: get_current_time(),
+        original_size: content.len(),
+        records_kept: 0,
+        records_from_backups: 0,
+        lost_ranges: salvage.lost_ranges,
+        backups_merged: Vec::new(),
+        damaged_copy: None,
+    };
+
+    for backup_path in find_backups(backup_dir, file_name) {
+        let backup_content = match fs::read(&backup_path) {
+            Ok(backup_content) => backup_content,
+            Err(e) => {
+                crate::log_warning!("recovery", "Skipping unreadable backup {:?}: {}", backup_path, e);
+                continue;
+            }
+        };
+        // Records are re-sealed below, so backups under an older key can contribute too
+        let backup_records = match salvage_frames(&backup_content, date, keyring) {
+            Ok(backup) => backup.plaintexts,
+            Err(e) => {
+                crate::log_warning!("recovery", "Skipping backup {:?}: {}", backup_path, e);
+                continue;
+            }
+        };
+        let (merged, added) = merge_records(records, backup_records);
+        records = merged;
+        if added > 0 {
+            report.records_from_backups += added;
+            report.backups_merged.push(backup_path.to_string_lossy().to_string());
+        }
+    }
+    report.records_kept = records.len();
+
+    if report.lost_ranges.is_empty() && report.records_from_backups == 0 {
+        return Ok(report);
+    }
+
+    if !content.is_empty() {
+        fs::create_dir_all(backup_dir)?;
+        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let damaged_copy = backup_dir.join(format!("{}.corrupt_{}", file_name, timestamp));
+        fs::write(&damaged_copy, &content)?;
+        report.damaged_copy = Some(damaged_copy.to_string_lossy().to_string());
+    }
+
+    // Surviving records are sealed again for their new positions
+    let date = date.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not named after its date", file_name))
+    })?;
+    let key = keyring.key(salvage.key_id).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("Key {} is not in the key store", salvage.key_id))
+    })?;
+    let rebuilt = encode_log(&records, key, salvage.key_id, date).map_err(io::Error::other)?;
+    atomic_write_with_backup(&log_path, &rebuilt, None)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use crate::activity_monitor::records::{read_log_file, ActivityRecord};
+    use crate::activity_monitor::records::{encode_frame, encode_legacy_frame};
+    use crate::utils::encryption::{generate_key, seal_string};
+
+    fn frame_bytes(record: &ActivityRecord, key: &[u8; 32], sequence: u64) -> Vec<u8> {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let aad = record_aad(date, sequence, LOG_FORMAT_VERSION);
+        let (ciphertext, nonce) = seal_string(&record.to_plaintext(), key, &aad).unwrap();
+        encode_frame(&nonce, &ciphertext)
+    }
+
+    #[test]
+    fn test_recover_skips_damage_and_merges_backup() {
+        let dir = TestDir::new("recovery");
+        let (log_dir, backup_dir) = (dir.join("log"), dir.join("backup"));
+        fs::create_dir_all(&log_dir).unwrap();
+        fs::create_dir_all(&backup_dir).unwrap();
+        let file_name = "rs-fairsight(2025-01-01).txt";
+
+        let records: Vec<_> = (1..=4).map(|end| ActivityRecord::Active { start: 0, end }).collect();
+        let keyring = KeyRing::with_key(1, generate_key().unwrap());
+        let frames: Vec<_> = records
+            .iter()
+            .enumerate()
+            .map(|(sequence, record)| frame_bytes(record, keyring.key(1).unwrap(), sequence as u64))
+            .collect();
+        let header = LogHeader::current(1).to_bytes();
+
+        // The backup still has the second record, which was later overwritten with garbage
+        let backup = [header.clone(), frames[0].clone(), frames[1].clone()].concat();
+        fs::write(backup_dir.join(format!("{}.backup_1", file_name)), backup).unwrap();
+        let garbage = vec![0xAB; frames[1].len()];
+        let damaged = [header, frames[0].clone(), garbage, frames[2].clone(), frames[3].clone()].concat();
+        fs::write(log_dir.join(file_name), &damaged).unwrap();
+
+        let report = recover_day_log(&log_dir, &backup_dir, file_name, &keyring).unwrap();
+        assert_eq!(report.records_kept, 4);
+        assert_eq!(report.records_from_backups, 1);
+        let lost_start = LOG_HEADER_LEN + frames[0].len();
+        assert_eq!(report.lost_ranges, vec![LostRange { start: lost_start, end: lost_start + frames[1].len() }]);
+        assert!(report.damaged_copy.is_some());
+
+        let contents = read_log_file(&log_dir.join(file_name), &keyring).unwrap();
+        assert_eq!(contents.records, records);
+    }
+
+    #[test]
+    fn test_salvage_legacy_skips_garbage() {
+        let key = generate_key().unwrap();
+        let first = encode_legacy_frame("Active time 20 - 10", &key);
+        let last = encode_legacy_frame("Active time 40 - 30", &key);
+        // Lengths read from inside this region are far too large to be a record
+        let content = [first.clone(), vec![0xFF; 200_000], last].concat();
+
+        let salvage = salvage_with(&content, LEGACY_KEY_ID, 0, Framing::Legacy, &key, None);
+        assert_eq!(salvage.plaintexts.len(), 2);
+        assert_eq!(salvage.lost_ranges, vec![LostRange { start: first.len(), end: first.len() + 200_000 }]);
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::aggregation::{day_bounds, read_day_timeline};
+use super::calendar::{days_off, CalendarDay};
+use super::types::{IntervalKind, TimelineInterval};
+
+/// Working hours of one weekday as "HH:MM" local times
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkingHours {
+    pub start: String,
+    pub end: String,
+}
+
+/// Weekly working hours and how much active time a working day should have.
+/// Days without working hours and imported holidays or leave have no target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkSchedule {
+    pub monday: Option<WorkingHours>,
+    pub tuesday: Option<WorkingHours>,
+    pub wednesday: Option<WorkingHours>,
+    pub thursday: Option<WorkingHours>,
+    pub friday: Option<WorkingHours>,
+    pub saturday: Option<WorkingHours>,
+    pub sunday: Option<WorkingHours>,
+    pub daily_target_minutes: u32,
+}
+
+impl Default for WorkSchedule {
+    /// Monday to Friday, 09:00 to 17:30, with eight hours of activity a day
+    fn default() -> Self {
+        let office = || Some(WorkingHours { start: "09:00".to_string(), end: "17:30".to_string() });
+        Self {
+            monday: office(),
+            tuesday: office(),
+            wednesday: office(),
+            thursday: office(),
+            friday: office(),
+            saturday: None,
+            sunday: None,
+            daily_target_minutes: 8 * 60,
+        }
+    }
+}
+
+impl WorkSchedule {
+    pub fn hours(&self, weekday: Weekday) -> Option<&WorkingHours> {
+        match weekday {
+            Weekday::Mon => self.monday.as_ref(),
+            Weekday::Tue => self.tuesday.as_ref(),
+            Weekday::Wed => self.wednesday.as_ref(),
+            Weekday::Thu => self.thursday.as_ref(),
+            Weekday::Fri => self.friday.as_ref(),
+            Weekday::Sat => self.saturday.as_ref(),
+            Weekday::Sun => self.sunday.as_ref(),
+        }
+    }
+
+    /// Start and end of the working hours on `weekday`, if it is a working day
+    fn times(&self, weekday: Weekday) -> Result<Option<(NaiveTime, NaiveTime)>, String> {
+        let Some(hours) = self.hours(weekday) else { return Ok(None) };
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Invalid time '{}' on {}; use HH:MM", time, weekday))
+        };
+        let (start, end) = (parse(&hours.start)?, parse(&hours.end)?);
+        if end <= start {
+            return Err(format!("Working hours on {} must end after they start", weekday));
+        }
+        Ok(Some((start, end)))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for weekday in [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun] {
+            self.times(weekday)?;
+        }
+        if self.daily_target_minutes > 24 * 60 {
+            return Err("The daily target cannot exceed 24 hours".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Active time of one day measured against the schedule
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayWorkTime {
+    pub date: String,
+    pub target_seconds: i64,
+    pub active_seconds: i64,
+    /// Active time within the day's working hours
+    pub inside_hours_seconds: i64,
+    pub outside_hours_seconds: i64,
+    pub overtime_seconds: i64,
+    pub undertime_seconds: i64,
+    /// Active minus target time from the Monday of this week up to and including this day;
+    /// zero for days after today
+    pub week_balance_seconds: i64,
+    /// Holidays and leave imported for the day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub day_off: Vec<CalendarDay>,
+}
+
+/// Work time per day over a date range, and its totals
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkTimeReport {
+    pub start_date: String,
+    pub end_date: String,
+    pub schedule: WorkSchedule,
+    pub days: Vec<DayWorkTime>,
+    /// Sums over the range; `week_balance_seconds` is the balance at its last day up to today
+    pub totals: DayWorkTime,
+}
+
+/// First instant at or after the local `time` on `date`, which a DST change may have skipped
+fn local_instant<Tz: TimeZone>(date: NaiveDate, time: NaiveTime, tz: &Tz) -> i64 {
+    let local = date.and_time(time);
+    (0..=120)
+        .find_map(|minutes| tz.from_local_datetime(&(local + Duration::minutes(minutes))).earliest())
+        .map_or_else(|| local.and_utc().timestamp(), |instant| instant.timestamp())
+}
+
+/// Measure one day's timeline against the schedule. The week balance is left to the caller.
+/// A day off has no working hours, so all its activity is overtime.
+pub fn day_work_time<Tz: TimeZone>(
+    date: NaiveDate,
+    timeline: &[TimelineInterval],
+    schedule: &WorkSchedule,
+    day_off: Vec<CalendarDay>,
+    tz: &Tz
+) -> Result<DayWorkTime, String> {
+    let working_hours = schedule
+        .times(date.weekday())?
+        .filter(|_| day_off.is_empty())
+        .map(|(start, end)| (local_instant(date, start, tz), local_instant(date, end, tz)));
+
+    let mut day = DayWorkTime { date: date.format("%Y-%m-%d").to_string(), day_off, ..Default::default() };
+    for interval in timeline.iter().filter(|i| i.kind == IntervalKind::Active) {
+        day.active_seconds += interval.end - interval.start;
+        if let Some((start, end)) = working_hours {
+            day.inside_hours_seconds += (interval.end.min(end) - interval.start.max(start)).max(0);
+        }
+    }
+    day.outside_hours_seconds = day.active_seconds - day.inside_hours_seconds;
+    if working_hours.is_some() {
+        day.target_seconds = schedule.daily_target_minutes as i64 * 60;
+    }
+    day.overtime_seconds = (day.active_seconds - day.target_seconds).max(0);
+    day.undertime_seconds = (day.target_seconds - day.active_seconds).max(0);
+    Ok(day)
+}
+
+/// Work time for every day of an inclusive date range in `tz`. Balances start on the Monday
+/// of each week, so a range starting mid-week also reads the days before it. Days after
+/// today are listed without a target.
+pub fn work_time_report<Tz: TimeZone>(
+    log_dir: &Path,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    schedule: &WorkSchedule,
+    tz: &Tz
+) -> Result<WorkTimeReport, String> {
+    let week_start = start_date - Duration::days(start_date.weekday().num_days_from_monday() as i64);
+    let mut days = Vec::new();
+    let mut totals = DayWorkTime::default();
+    let mut balance = 0;
+    let mut calendar = days_off(log_dir, week_start, end_date)?;
+    let today = Utc::now().with_timezone(tz).date_naive();
+
+    for date in week_start.iter_days().take_while(|d| *d <= end_date) {
+        let day_off = calendar.remove(&date).unwrap_or_default();
+        // Days still to come have no target yet, and the balance stops at today
+        if date > today {
+            if date >= start_date {
+                days.push(DayWorkTime { date: date.format("%Y-%m-%d").to_string(), day_off, ..Default::default() });
+            }
+            continue;
+        }
+        if date.weekday() == Weekday::Mon {
+            balance = 0;
+        }
+        let (day_start, day_end) = day_bounds(date, tz);
+        let timeline = read_day_timeline(log_dir, date, day_start, day_end)
+            .map_err(|e| format!("Failed to read activity for {}: {}", date, e))?;
+        let mut day = day_work_time(date, &timeline, schedule, day_off, tz)?;
+        balance += day.active_seconds - day.target_seconds;
+        day.week_balance_seconds = balance;
+
+        if date >= start_date {
+            totals.target_seconds += day.target_seconds;
+            totals.active_seconds += day.active_seconds;
+            totals.inside_hours_seconds += day.inside_hours_seconds;
+            totals.outside_hours_seconds += day.outside_hours_seconds;
+            totals.overtime_seconds += day.overtime_seconds;
+            totals.undertime_seconds += day.undertime_seconds;
+            totals.week_balance_seconds = balance;
+            days.push(day);
+        }
+    }
+
+    Ok(WorkTimeReport {
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        schedule: schedule.clone(),
+        days,
+        totals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use chrono::FixedOffset;
+    use crate::activity_monitor::calendar::DayOffKind;
+
+    #[test]
+    fn test_day_work_time() {
+        // Monday 2025-01-06 in UTC+1: active 08:00 - 10:00 and 17:00 - 19:00 local time
+        let tz = FixedOffset::east_opt(3600).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2025, 1, 6).unwrap();
+        let at = |hour: i64| local_instant(monday, NaiveTime::MIN, &tz) + hour * 3600;
+        let active = |start, end| TimelineInterval { start, end, kind: IntervalKind::Active };
+        let timeline = [active(at(8), at(10)), active(at(17), at(19))];
+
+        let schedule = WorkSchedule::default();
+        let day = day_work_time(monday, &timeline, &schedule, Vec::new(), &tz).unwrap();
+        assert_eq!(day.active_seconds, 4 * 3600);
+        // 09:00 - 10:00 and 17:00 - 17:30 inside, 08:00 - 09:00 and 17:30 - 19:00 outside
+        assert_eq!(day.inside_hours_seconds, 3600 + 1800);
+        assert_eq!(day.outside_hours_seconds, 3600 + 5400);
+        assert_eq!(day.undertime_seconds, 4 * 3600);
+        assert_eq!(day.overtime_seconds, 0);
+
+        // Weekends have no target, so all activity is overtime
+        let sunday = day_work_time(monday - Duration::days(1), &timeline, &schedule, Vec::new(), &tz).unwrap();
+        assert_eq!((sunday.target_seconds, sunday.overtime_seconds, sunday.inside_hours_seconds), (0, 4 * 3600, 0));
+
+        // So do holidays
+        let holiday = CalendarDay {
+            date: "2025-01-06".to_string(),
+            kind: DayOffKind::Holiday,
+            summary: "Epiphany".to_string(),
+            source: "holidays".to_string(),
+            uid: String::new(),
+        };
+        let day = day_work_time(monday, &timeline, &schedule, vec![holiday], &tz).unwrap();
+        assert_eq!((day.target_seconds, day.overtime_seconds, day.undertime_seconds), (0, 4 * 3600, 0));
+
+        let overnight = WorkSchedule {
+            monday: Some(WorkingHours { start: "22:00".to_string(), end: "06:00".to_string() }),
+            ..WorkSchedule::default()
+        };
+        assert!(overnight.validate().is_err());
+    }
+
+    #[test]
+    fn test_future_days_have_no_target() {
+        let dir = TestDir::new("schedule");
+        let tz = FixedOffset::east_opt(0).unwrap();
+        // From next Monday, so no day of the range or its first week has logs to read
+        let today = Utc::now().date_naive();
+        let next_monday = today + Duration::days(7 - today.weekday().num_days_from_monday() as i64);
+        let report = work_time_report(&dir, next_monday, next_monday + Duration::days(13), &WorkSchedule::default(), &tz).unwrap();
+        assert_eq!(report.days.len(), 14);
+        assert!(report.days.iter().all(|day| day.target_seconds == 0 && day.undertime_seconds == 0));
+        assert_eq!((report.totals.target_seconds, report.totals.week_balance_seconds), (0, 0));
+    }
+}
@@ -0,0 +1,101 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::utils::file_utils::atomic_write_with_backup;
+use super::breaks::BreakReminderSettings;
+use super::file_operations::get_platform_directories;
+use super::focus::FocusSettings;
+use super::schedule::WorkSchedule;
+
+const SETTINGS_FILE_NAME: &str = "activity_settings.json";
+
+/// How user activity is detected on this machine
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingMode {
+    /// Global keyboard/mouse hooks (Windows hooks, macOS event tap, Linux evdev/XInput2)
+    Hooks,
+    /// Poll the operating system's idle time instead of observing input events
+    IdlePoll,
+}
+
+/// Per-machine activity monitor settings, stored next to the daily logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivitySettings {
+    pub tracking_mode: TrackingMode,
+    pub idle_poll_interval_secs: u64,
+    /// Record the focused application alongside active time; off unless opted in
+    pub track_applications: bool,
+    /// Also record window titles (may contain document names or URLs)
+    pub track_window_titles: bool,
+    /// Off by default; see `BreakPreset` for common schedules
+    pub break_reminders: BreakReminderSettings,
+    /// Include history imported from other trackers in reports
+    pub show_imported: bool,
+    /// Gap tolerance and deep-focus length of focus sessions
+    pub focus: FocusSettings,
+    /// Working hours and daily active-time target for overtime reports
+    pub schedule: WorkSchedule,
+    /// Show imported holidays and leave on the timeline instead of "Not run"
+    pub show_days_off: bool,
+}
+
+impl Default for ActivitySettings {
+    fn default() -> Self {
+        Self {
+            tracking_mode: TrackingMode::Hooks,
+            idle_poll_interval_secs: 5,
+            track_applications: false,
+            track_window_titles: false,
+            break_reminders: BreakReminderSettings::default(),
+            show_imported: true,
+            focus: FocusSettings::default(),
+            schedule: WorkSchedule::default(),
+            show_days_off: false,
+        }
+    }
+}
+
+// Settings are read on every tracked event, so keep the loaded copy in memory
+static CACHED_SETTINGS: Lazy<Mutex<Option<ActivitySettings>>> = Lazy::new(|| Mutex::new(None));
+
+fn settings_file_path() -> io::Result<PathBuf> {
+    let (log_dir, _) = get_platform_directories()?;
+    Ok(log_dir.join(SETTINGS_FILE_NAME))
+}
+
+/// Current settings, loaded from disk on first use
+pub fn load_activity_settings() -> ActivitySettings {
+    let mut cached = CACHED_SETTINGS.lock().unwrap();
+    cached.get_or_insert_with(read_activity_settings).clone()
+}
+
+/// Read the settings file, falling back to defaults if it is missing or unreadable
+fn read_activity_settings() -> ActivitySettings {
+    let path = match settings_file_path() {
+        Ok(path) => path,
+        Err(_) => return ActivitySettings::default(),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            crate::log_warning!("activity_settings", "Invalid settings file {:?}, using defaults: {}", path, e);
+            ActivitySettings::default()
+        }),
+        Err(_) => ActivitySettings::default(),
+    }
+}
+
+pub fn save_activity_settings(settings: &ActivitySettings) -> io::Result<()> {
+    let path = settings_file_path()?;
+    let json = serde_json::to_string_pretty(settings)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    atomic_write_with_backup(&path, json.as_bytes(), None)?;
+    *CACHED_SETTINGS.lock().unwrap() = Some(settings.clone());
+    Ok(())
+}
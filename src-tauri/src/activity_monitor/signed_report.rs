@@ -0,0 +1,418 @@
+use chrono::{DateTime, NaiveDate, TimeZone};
+use ring::digest::{digest, SHA256};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::aggregation::{day_bounds, mark_day_off, read_day_timeline, summarize_timeline, utc_offset};
+use super::calendar::{days_off, CalendarDay};
+use super::core::get_current_time;
+use super::settings::load_activity_settings;
+use super::types::{ActivityTotals, TimelineInterval};
+
+pub const REPORT_FORMAT_VERSION: u32 = 1;
+
+// The HTML report carries the signed JSON in this element so it can be verified on its own
+const HTML_REPORT_ELEMENT: &str = "<script type=\"application/json\" id=\"innomonitor-signed-report\">";
+
+/// File formats a signed report can be written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Json,
+    /// A readable summary with the signed JSON embedded
+    Html,
+}
+
+/// One day of a report with the stretches its totals were computed from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportDay {
+    pub date: String,
+    pub intervals: Vec<TimelineInterval>,
+    pub totals: ActivityTotals,
+    /// Holidays and leave imported for the day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub day_off: Vec<CalendarDay>,
+}
+
+/// Activity over an inclusive date range, as signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityReport {
+    pub format_version: u32,
+    pub generated_at: u64,
+    /// Timezone the days were cut in
+    pub timezone: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub days: Vec<ReportDay>,
+    /// Sums of the day totals
+    pub totals: ActivityTotals,
+}
+
+/// A report with the installation's public key and its signature over the report's JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedActivityReport {
+    pub report: ActivityReport,
+    /// Ed25519 public key, hex
+    pub public_key: String,
+    /// Ed25519 signature, hex
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportVerification {
+    /// The signature is valid and the totals match the intervals
+    pub valid: bool,
+    pub signature_valid: bool,
+    pub totals_match: bool,
+    pub public_key: String,
+    pub key_fingerprint: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub problems: Vec<String>,
+}
+
+/// The public half of an installation's signing key, to hand to whoever checks its reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportSigningKey {
+    /// Ed25519 public key, hex
+    pub public_key: String,
+    pub fingerprint: String,
+}
+
+impl ReportSigningKey {
+    pub fn of(key_pair: &Ed25519KeyPair) -> Self {
+        Self {
+            public_key: hex::encode(key_pair.public_key().as_ref()),
+            fingerprint: key_fingerprint(key_pair.public_key().as_ref()),
+        }
+    }
+}
+
+/// Short form of a public key to compare by eye, e.g. "3f2a 91c0 ..."
+pub fn key_fingerprint(public_key: &[u8]) -> String {
+    let hash = digest(&SHA256, public_key);
+    hash.as_ref()[..16]
+        .chunks(2)
+        .map(hex::encode)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Day totals added up; the longest active stretch is the longest of any day
+pub fn range_totals(days: &[ReportDay]) -> ActivityTotals {
+    let mut totals = ActivityTotals::default();
+    for day in days {
+        totals.active_seconds += day.totals.active_seconds;
+        totals.inactive_seconds += day.totals.inactive_seconds;
+        totals.suspended_seconds += day.totals.suspended_seconds;
+        totals.clock_adjusted_seconds += day.totals.clock_adjusted_seconds;
+        totals.not_run_seconds += day.totals.not_run_seconds;
+        totals.day_off_seconds += day.totals.day_off_seconds;
+        totals.powered_off_seconds += day.totals.powered_off_seconds;
+        totals.app_closed_seconds += day.totals.app_closed_seconds;
+        totals.crashed_seconds += day.totals.crashed_seconds;
+        totals.unknown_seconds += day.totals.unknown_seconds;
+        totals.first_activity = totals.first_activity.or(day.totals.first_activity);
+        totals.last_activity = day.totals.last_activity.or(totals.last_activity);
+        let length = |interval: &Option<TimelineInterval>| interval.map_or(-1, |i| i.end - i.start);
+        if length(&day.totals.longest_active) > length(&totals.longest_active) {
+            totals.longest_active = day.totals.longest_active;
+        }
+    }
+    totals
+}
+
+/// The report for an inclusive date range in `tz`. Holidays and leave are marked as days off
+/// when the timeline shows them, so the totals match the aggregate.
+pub fn build_report<Tz: TimeZone>(
+    log_dir: &Path,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    tz: &Tz,
+    timezone: &str
+) -> Result<ActivityReport, String> {
+    let show_days_off = load_activity_settings().show_days_off;
+    let mut calendar = days_off(log_dir, start_date, end_date)?;
+    let mut days = Vec::new();
+
+    for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+        let (day_start, day_end) = day_bounds(date, tz);
+        let mut intervals = read_day_timeline(log_dir, date, day_start, day_end)
+            .map_err(|e| format!("Failed to read activity for {}: {}", date, e))?;
+        let day_off = calendar.remove(&date).unwrap_or_default();
+        if show_days_off && !day_off.is_empty() {
+            mark_day_off(&mut intervals);
+        }
+        days.push(ReportDay {
+            date: date.format("%Y-%m-%d").to_string(),
+            totals: summarize_timeline(&intervals),
+            intervals,
+            day_off,
+        });
+    }
+
+    Ok(ActivityReport {
+        format_version: REPORT_FORMAT_VERSION,
+        generated_at: get_current_time(),
+        timezone: timezone.to_string(),
+        start_date: start_date.format("%Y-%m-%d").to_string(),
+        end_date: end_date.format("%Y-%m-%d").to_string(),
+        totals: range_totals(&days),
+        days,
+    })
+}
+
+/// The bytes a signature covers: the report's compact JSON
+fn signed_bytes(report: &ActivityReport) -> Result<Vec<u8>, String> {
+    serde_json::to_vec(report).map_err(|e| format!("Failed to serialize report: {}", e))
+}
+
+pub fn sign_report(report: ActivityReport, key_pair: &Ed25519KeyPair) -> Result<SignedActivityReport, String> {
+    let signature = key_pair.sign(&signed_bytes(&report)?);
+    Ok(SignedActivityReport {
+        report,
+        public_key: hex::encode(key_pair.public_key().as_ref()),
+        signature: hex::encode(signature.as_ref()),
+    })
+}
+
+/// Read a signed report from its JSON or HTML form
+pub fn parse_signed_report(content: &str) -> Result<SignedActivityReport, String> {
+    let json = match content.find(HTML_REPORT_ELEMENT) {
+        Some(start) => {
+            let json = &content[start + HTML_REPORT_ELEMENT.len()..];
+            &json[..json.find("</script>").ok_or("The embedded report is not closed")?]
+        }
+        None => content,
+    };
+    serde_json::from_str(json).map_err(|e| format!("Not a signed activity report: {}", e))
+}
+
+/// Mistakes in the days of a report: gaps in the range, unordered stretches and totals that
+/// do not add up
+fn check_days(report: &ActivityReport) -> Vec<String> {
+    let mut problems = Vec::new();
+    let dates: Vec<Option<NaiveDate>> = report
+        .days
+        .iter()
+        .map(|day| NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").ok())
+        .collect();
+    let expected = match (
+        NaiveDate::parse_from_str(&report.start_date, "%Y-%m-%d"),
+        NaiveDate::parse_from_str(&report.end_date, "%Y-%m-%d"),
+    ) {
+        (Ok(start), Ok(end)) => start.iter_days().take_while(|d| *d <= end).map(Some).collect(),
+        _ => Vec::new(),
+    };
+    if dates != expected {
+        problems.push(format!("The days do not cover {} to {}", report.start_date, report.end_date));
+    }
+
+    for day in &report.days {
+        let ordered = day.intervals.iter().all(|i| i.start <= i.end)
+            && day.intervals.windows(2).all(|pair| pair[0].end <= pair[1].start);
+        if !ordered {
+            problems.push(format!("{}: the intervals overlap or are out of order", day.date));
+        }
+        if summarize_timeline(&day.intervals) != day.totals {
+            problems.push(format!("{}: the totals do not match the intervals", day.date));
+        }
+    }
+    if range_totals(&report.days) != report.totals {
+        problems.push("The report totals do not match the days".to_string());
+    }
+    problems
+}
+
+/// Check the signature and that every total matches the embedded intervals. With
+/// `trusted_key` the report must also be signed by that key (hex).
+pub fn verify_signed_report(signed: &SignedActivityReport, trusted_key: Option<&str>) -> ReportVerification {
+    let report = &signed.report;
+    let public_key = hex::decode(signed.public_key.trim()).unwrap_or_default();
+    let mut problems = Vec::new();
+
+    if report.format_version != REPORT_FORMAT_VERSION {
+        problems.push(format!("Unsupported report format version {}", report.format_version));
+    }
+    if let Some(trusted) = trusted_key {
+        if !trusted.trim().eq_ignore_ascii_case(signed.public_key.trim()) {
+            problems.push("The report is signed by a different key".to_string());
+        }
+    }
+
+    let signature_valid = match (hex::decode(signed.signature.trim()), signed_bytes(report)) {
+        (Ok(signature), Ok(message)) => UnparsedPublicKey::new(&ED25519, &public_key)
+            .verify(&message, &signature)
+            .is_ok(),
+        _ => false,
+    };
+    if !signature_valid {
+        problems.push("The signature does not match the report".to_string());
+    }
+
+    let day_problems = check_days(report);
+    let totals_match = day_problems.is_empty();
+    problems.extend(day_problems);
+
+    ReportVerification {
+        valid: problems.is_empty(),
+        signature_valid,
+        totals_match,
+        key_fingerprint: key_fingerprint(&public_key),
+        public_key: signed.public_key.clone(),
+        start_date: report.start_date.clone(),
+        end_date: report.end_date.clone(),
+        problems,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_duration(seconds: i64) -> String {
+    format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+}
+
+fn clock_time<Tz: TimeZone>(timestamp: Option<i64>, tz: &Tz) -> String {
+    timestamp
+        .and_then(|t| DateTime::from_timestamp(t, 0).map(|utc| utc.with_timezone(&utc_offset(tz, t))))
+        .map_or_else(|| "-".to_string(), |t| t.format("%H:%M").to_string())
+}
+
+/// A standalone HTML page summarizing the report, with clock times in `tz`
+pub fn render_report_html<Tz: TimeZone>(signed: &SignedActivityReport, tz: &Tz) -> Result<String, String> {
+    let report = &signed.report;
+    let row = |label: &str, totals: &ActivityTotals, note: &str| {
+        format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(label),
+            format_duration(totals.active_seconds),
+            format_duration(totals.inactive_seconds),
+            clock_time(totals.first_activity, tz),
+            clock_time(totals.last_activity, tz),
+            escape_html(note)
+        )
+    };
+
+    let mut rows = String::new();
+    for day in &report.days {
+        let note: Vec<&str> = day.day_off.iter().map(|d| d.summary.as_str()).collect();
+        rows.push_str(&row(&day.date, &day.totals, &note.join(", ")));
+    }
+    rows.push_str(&row("Total", &report.totals, ""));
+
+    // "</" would end the script element early; "<\/" is the same JSON string
+    let json = serde_json::to_string(signed)
+        .map_err(|e| format!("Failed to serialize report: {}", e))?
+        .replace("</", "<\\/");
+    let public_key = hex::decode(&signed.public_key).unwrap_or_default();
+
+    Ok(format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>Activity report {start} to {end}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 10px; text-align: left; }}
+tr:last-child {{ font-weight: bold; }}
+code {{ word-break: break-all; }}
+</style>
+</head>
+<body>
+<h1>Activity report {start} to {end}</h1>
+<p>Timezone: {timezone}</p>
+<table>
+<tr><th>Date</th><th>Active</th><th>Inactive</th><th>First activity</th><th>Last activity</th><th>Day off</th></tr>
+{rows}</table>
+<h2>Signature</h2>
+<p>Signed with Ed25519 key <code>{fingerprint}</code> (public key <code>{public_key}</code>).</p>
+<p>Check this file with <code>innomonitor-cli verify-report &lt;FILE&gt;</code>.</p>
+{element}{json}</script>
+</body>
+</html>
+",
+        start = escape_html(&report.start_date),
+        end = escape_html(&report.end_date),
+        timezone = escape_html(&report.timezone),
+        rows = rows,
+        fingerprint = key_fingerprint(&public_key),
+        public_key = escape_html(&signed.public_key),
+        element = HTML_REPORT_ELEMENT,
+        json = json,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use ring::rand::SystemRandom;
+    use super::super::types::IntervalKind;
+
+    fn report() -> ActivityReport {
+        let day = |date: &str, start: i64| {
+            let intervals = vec![
+                TimelineInterval { start, end: start + 3_600, kind: IntervalKind::NotRun },
+                TimelineInterval { start: start + 3_600, end: start + 7_200, kind: IntervalKind::Active },
+                TimelineInterval { start: start + 7_200, end: start + 7_500, kind: IntervalKind::Inactive },
+            ];
+            ReportDay {
+                date: date.to_string(),
+                totals: summarize_timeline(&intervals),
+                intervals,
+                day_off: Vec::new(),
+            }
+        };
+        let days = vec![day("2025-01-06", 1_736_121_600), day("2025-01-07", 1_736_208_000)];
+        ActivityReport {
+            format_version: REPORT_FORMAT_VERSION,
+            generated_at: 1_736_300_000,
+            timezone: "UTC".to_string(),
+            start_date: "2025-01-06".to_string(),
+            end_date: "2025-01-07".to_string(),
+            totals: range_totals(&days),
+            days,
+        }
+    }
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify_report() {
+        let signed = sign_report(report(), &key_pair()).unwrap();
+        assert_eq!(signed.report.totals.active_seconds, 7_200);
+
+        let verification = verify_signed_report(&signed, Some(&signed.public_key));
+        assert!(verification.valid, "{:?}", verification.problems);
+
+        // The HTML page carries the same signed report
+        let html = render_report_html(&signed, &Utc).unwrap();
+        assert_eq!(parse_signed_report(&html).unwrap(), signed);
+        let json = serde_json::to_string(&signed).unwrap();
+        assert_eq!(parse_signed_report(&json).unwrap(), signed);
+
+        // Editing a total breaks both the signature and the totals
+        let mut edited = signed.clone();
+        edited.report.days[0].totals.active_seconds += 3_600;
+        let verification = verify_signed_report(&edited, None);
+        assert!(!verification.signature_valid && !verification.totals_match);
+
+        // An edited report re-signed with another key fails on the signer and the totals
+        let mut extended = report();
+        extended.days[1].intervals[2].end += 600;
+        let forged = sign_report(extended, &key_pair()).unwrap();
+        let verification = verify_signed_report(&forged, Some(&signed.public_key));
+        assert!(verification.signature_valid && !verification.totals_match && !verification.valid);
+    }
+}
@@ -0,0 +1,263 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::utils::encryption::random_bytes;
+use crate::utils::file_utils::atomic_write_with_backup;
+use crate::utils::key_manager::KeyRing;
+use super::core::get_current_time;
+use super::records::{encode_log, read_log_file, ActivityRecord};
+
+/// Longest range a single tag may cover
+const MAX_TAG_SECONDS: u64 = 31 * 24 * 60 * 60;
+
+/// A project, task or note attached to a time range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeTag {
+    pub id: String,
+    pub start: u64,
+    pub end: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub created_at: u64,
+}
+
+/// Part of a range covered by exactly one tag after overlaps are resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedSpan<'a> {
+    pub start: u64,
+    pub end: u64,
+    pub tag: &'a TimeTag,
+}
+
+// Tag files are rewritten as a whole, so changes must not interleave
+static TAGS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Tags for one day live next to its log, e.g. "rs-fairsight(2025-01-31).tags"
+fn tags_file_path(log_dir: &Path, date: NaiveDate) -> PathBuf {
+    log_dir.join(format!("rs-fairsight({}).tags", date.format("%Y-%m-%d")))
+}
+
+fn local_date(timestamp: u64) -> Result<NaiveDate, String> {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.date_naive())
+        .ok_or_else(|| format!("Invalid timestamp {}", timestamp))
+}
+
+/// Days whose tag file holds `tag`: every local day its range touches
+fn tag_days(tag: &TimeTag) -> Result<Vec<NaiveDate>, String> {
+    let first = local_date(tag.start)?;
+    let last = local_date(tag.end.saturating_sub(1).max(tag.start))?;
+    Ok(first.iter_days().take_while(|date| *date <= last).collect())
+}
+
+fn read_day_tags(path: &Path, keyring: &KeyRing) -> Result<Vec<TimeTag>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = read_log_file(path, keyring).map_err(|e| e.to_string())?;
+    Ok(contents
+        .records
+        .into_iter()
+        .filter_map(|record| match record {
+            ActivityRecord::Tag(tag) => Some(tag),
+            _ => None,
+        })
+        .collect())
+}
+
+fn write_day_tags(path: &Path, date: NaiveDate, tags: &[TimeTag], keyring: &KeyRing) -> Result<(), String> {
+    if tags.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(format!("Failed to remove {:?}: {}", path, e)),
+            _ => Ok(()),
+        };
+    }
+
+    let key_id = keyring.current_id();
+    let key = keyring.key(key_id).ok_or("Current key is not in the key store")?;
+    let plaintexts: Vec<String> = tags.iter().map(|tag| ActivityRecord::Tag(tag.clone()).to_plaintext()).collect();
+    let content = encode_log(&plaintexts, key, key_id, date)?;
+    atomic_write_with_backup(path, &content, None).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Attach a project, task and/or note to the range `start..end` (Unix seconds)
+pub fn add_tag(
+    log_dir: &Path,
+    start: u64,
+    end: u64,
+    project: Option<String>,
+    task: Option<String>,
+    note: Option<String>,
+    keyring: &KeyRing
+) -> Result<TimeTag, String> {
+    if start >= end {
+        return Err("A tag must end after it starts".to_string());
+    }
+    if end - start > MAX_TAG_SECONDS {
+        return Err("A tag cannot cover more than 31 days".to_string());
+    }
+    let (project, task, note) = (non_empty(project), non_empty(task), non_empty(note));
+    if project.is_none() && task.is_none() && note.is_none() {
+        return Err("A tag needs a project, task or note".to_string());
+    }
+
+    let id = hex::encode(random_bytes::<8>().map_err(|_| "Failed to generate tag id")?);
+    let tag = TimeTag { id, start, end, project, task, note, created_at: get_current_time() };
+
+    let _guard = TAGS_LOCK.lock().unwrap();
+    for date in tag_days(&tag)? {
+        let path = tags_file_path(log_dir, date);
+        let mut tags = read_day_tags(&path, keyring)?;
+        tags.push(tag.clone());
+        write_day_tags(&path, date, &tags, keyring)?;
+    }
+    Ok(tag)
+}
+
+/// Remove the tag with `id` from every day it covers. Returns whether it existed.
+pub fn remove_tag(log_dir: &Path, id: &str, keyring: &KeyRing) -> Result<bool, String> {
+    let _guard = TAGS_LOCK.lock().unwrap();
+    let mut found = false;
+
+    for (date, path) in tag_files(log_dir) {
+        let mut tags = read_day_tags(&path, keyring)?;
+        let before = tags.len();
+        tags.retain(|tag| tag.id != id);
+        if tags.len() != before {
+            write_day_tags(&path, date, &tags, keyring)?;
+            found = true;
+        }
+    }
+    Ok(found)
+}
+
+fn tag_files(log_dir: &Path) -> Vec<(NaiveDate, PathBuf)> {
+    fs::read_dir(log_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let date_str = name.strip_prefix("rs-fairsight(")?.strip_suffix(").tags")?;
+            let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok()?;
+            Some((date, entry.path()))
+        })
+        .collect()
+}
+
+/// Tags touching any day from `start_date` to `end_date`, by start time
+pub fn tags_in_range(
+    log_dir: &Path,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    keyring: &KeyRing
+) -> Result<Vec<TimeTag>, String> {
+    let mut seen = HashSet::new();
+    let mut tags = Vec::new();
+    for date in start_date.iter_days().take_while(|date| *date <= end_date) {
+        for tag in read_day_tags(&tags_file_path(log_dir, date), keyring)? {
+            if seen.insert(tag.id.clone()) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags.sort_by(|a, b| (a.start, &a.id).cmp(&(b.start, &b.id)));
+    Ok(tags)
+}
+
+/// Split `range_start..range_end` into spans covered by one tag each. Where tags overlap, the
+/// most recently created one wins, with the larger id breaking ties, so totals never count
+/// time twice and do not depend on the order tags were read in.
+pub fn resolve_overlaps(tags: &[TimeTag], range_start: u64, range_end: u64) -> Vec<TaggedSpan<'_>> {
+    let mut bounds: Vec<u64> = tags
+        .iter()
+        .flat_map(|tag| [tag.start, tag.end])
+        .map(|bound| bound.clamp(range_start, range_end))
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut spans: Vec<TaggedSpan> = Vec::new();
+    for window in bounds.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let winner = tags
+            .iter()
+            .filter(|tag| tag.start <= start && tag.end >= end)
+            .max_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        let Some(tag) = winner else { continue };
+
+        match spans.last_mut() {
+            Some(last) if last.end == start && last.tag.id == tag.id => last.end = end,
+            _ => spans.push(TaggedSpan { start, end, tag }),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    fn tag(id: &str, start: u64, end: u64, created_at: u64) -> TimeTag {
+        TimeTag {
+            id: id.to_string(),
+            start,
+            end,
+            project: Some(id.to_string()),
+            task: None,
+            note: None,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_later_tags_win_overlaps() {
+        let tags = vec![tag("a", 0, 100, 1), tag("b", 50, 150, 2), tag("c", 60, 70, 0)];
+        let spans: Vec<_> = resolve_overlaps(&tags, 0, 120)
+            .into_iter()
+            .map(|span| (span.start, span.end, span.tag.id.as_str()))
+            .collect();
+        assert_eq!(spans, vec![(0, 50, "a"), (50, 120, "b")]);
+
+        // Reading tags in another order gives the same answer
+        let reversed: Vec<_> = tags.into_iter().rev().collect();
+        let spans: Vec<_> = resolve_overlaps(&reversed, 0, 120).into_iter().map(|span| span.tag.id.clone()).collect();
+        assert_eq!(spans, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_tags_are_stored_per_day() {
+        let dir = TestDir::new("tags");
+        let keyring = KeyRing::with_key(1, crate::utils::encryption::generate_key().unwrap());
+
+        let noon = Local.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap().timestamp() as u64;
+        let added = add_tag(&dir, noon, noon + 86_400, Some(" Apollo ".into()), None, None, &keyring).unwrap();
+        assert_eq!(added.project.as_deref(), Some("Apollo"));
+        assert!(add_tag(&dir, noon, noon + 60, None, Some(" ".into()), None, &keyring).is_err());
+
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        assert_eq!(tags_in_range(&dir, day(2), day(2), &keyring).unwrap(), vec![added.clone()]);
+        assert_eq!(tags_in_range(&dir, day(1), day(3), &keyring).unwrap().len(), 1);
+
+        assert!(remove_tag(&dir, &added.id, &keyring).unwrap());
+        assert!(tags_in_range(&dir, day(1), day(3), &keyring).unwrap().is_empty());
+        assert!(!tags_file_path(&dir, day(1)).exists());
+    }
+}
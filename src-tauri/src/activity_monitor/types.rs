@@ -1,4 +1,8 @@
-use std::sync::atomic::AtomicUsize;
+use serde::{Deserialize, Serialize};
+
+use super::calendar::CalendarDay;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::time::Duration;
 
 // Define the type of message to send (just the timestamp)
 pub type TimeUpdateMessage = u64;
@@ -6,4 +10,176 @@ pub type TimeUpdateMessage = u64;
 // Constants
 pub static INACTIVE_TIME_PERIOD: u64 = 300;
 pub static BACKUP_COUNTER: AtomicUsize = AtomicUsize::new(0);
-pub static BACKUP_FREQUENCY: usize = 10; // Backup every 10 operations instead of 50
+pub static LAST_BACKUP_TIME: AtomicU64 = AtomicU64::new(0);
+pub static BACKUP_INTERVAL_SECS: u64 = 600; // Copy the day log to the backup directory at most every 10 minutes
+// The activity log is fsynced after this many records or this much time, whichever comes first
+pub static SYNC_EVERY_RECORDS: usize = 20;
+pub static SYNC_INTERVAL: Duration = Duration::from_secs(30);
+// Records kept in memory while a passphrase-protected key store is locked (about a day of activity)
+pub static MAX_PENDING_RECORDS: usize = 100_000;
+
+/// Time spent with one application focused
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplicationUsage {
+    pub process_name: String,
+    pub exe_path: Option<String>,
+    pub total_seconds: i64,
+    pub window_titles: Vec<WindowTitleUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowTitleUsage {
+    pub title: String,
+    pub seconds: i64,
+}
+
+/// Time attributed to one project and task after overlapping tags are resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagTotal {
+    pub project: Option<String>,
+    pub task: Option<String>,
+    /// Tagged time, whether or not there was input
+    pub tagged_seconds: i64,
+    /// Part of the tagged time recorded as active
+    pub active_seconds: i64,
+}
+
+/// How a stretch of the activity timeline was spent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntervalKind {
+    Active,
+    Inactive,
+    Suspended,
+    ClockAdjusted,
+    /// Time not run with no later startup to explain it, e.g. the rest of today
+    NotRun,
+    /// Time not run on a holiday or leave day, when calendar days are shown on the timeline
+    DayOff,
+    /// Not run because the machine was off
+    PoweredOff,
+    /// Not run because the app was quit
+    AppClosed,
+    /// Not run because the app stopped without shutting down
+    Crashed,
+    /// Not run, but the run before left no lifecycle records to tell why, e.g. an older version
+    Unknown,
+}
+
+impl IntervalKind {
+    /// Name used in the text timeline, e.g. "Not run"
+    pub fn label(self) -> &'static str {
+        match self {
+            IntervalKind::Active => "Active",
+            IntervalKind::Inactive => "Inactive",
+            IntervalKind::Suspended => "Suspended",
+            IntervalKind::ClockAdjusted => "Clock adjusted",
+            IntervalKind::NotRun => "Not run",
+            IntervalKind::DayOff => "Day off",
+            IntervalKind::PoweredOff => "Powered off",
+            IntervalKind::AppClosed => "App closed",
+            IntervalKind::Crashed => "Crashed",
+            IntervalKind::Unknown => "Unknown",
+        }
+    }
+
+    /// Time the tracker was not running, whatever the reason
+    pub fn is_not_running(self) -> bool {
+        matches!(
+            self,
+            IntervalKind::NotRun
+                | IntervalKind::DayOff
+                | IntervalKind::PoweredOff
+                | IntervalKind::AppClosed
+                | IntervalKind::Crashed
+                | IntervalKind::Unknown
+        )
+    }
+}
+
+/// One stretch of the activity timeline, in Unix seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineInterval {
+    pub start: i64,
+    pub end: i64,
+    pub kind: IntervalKind,
+}
+
+/// Seconds per kind of stretch and the edges of the active time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityTotals {
+    pub active_seconds: i64,
+    pub inactive_seconds: i64,
+    pub suspended_seconds: i64,
+    pub clock_adjusted_seconds: i64,
+    pub not_run_seconds: i64,
+    pub day_off_seconds: i64,
+    #[serde(default)]
+    pub powered_off_seconds: i64,
+    #[serde(default)]
+    pub app_closed_seconds: i64,
+    #[serde(default)]
+    pub crashed_seconds: i64,
+    #[serde(default)]
+    pub unknown_seconds: i64,
+    /// Start of the first active stretch
+    pub first_activity: Option<i64>,
+    /// End of the last active stretch
+    pub last_activity: Option<i64>,
+    /// Longest uninterrupted active stretch
+    pub longest_active: Option<TimelineInterval>,
+}
+
+/// Active time one device contributed; time active on several devices at once counts for each
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceActivity {
+    pub device_id: String,
+    pub name: String,
+    pub active_seconds: i64,
+}
+
+/// Totals of one day of an aggregate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayActivity {
+    pub date: String,
+    pub totals: ActivityTotals,
+    /// Holidays and leave imported for the day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub day_off: Vec<CalendarDay>,
+    /// Active time per device, when other devices' logs were merged into the day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<DeviceActivity>,
+    /// The day in the text timeline format, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Activity timeline and totals over an inclusive date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityAggregate {
+    pub start_date: String,
+    pub end_date: String,
+    /// Consecutive stretches covering the whole range, including the time the app was not run
+    pub intervals: Vec<TimelineInterval>,
+    pub totals: ActivityTotals,
+    /// Days of the range that are not holidays or leave
+    pub working_days: usize,
+    /// Active time per working day; activity on days off counts towards the total only
+    pub average_active_seconds: i64,
+    /// Active time per device over the range, when other devices' logs were merged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<DeviceActivity>,
+    pub days: Vec<DayActivity>,
+}
+
+/// Active seconds by weekday and time of day over a date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityHeatmap {
+    pub start_date: String,
+    pub end_date: String,
+    pub bucket_minutes: u32,
+    /// Seven rows from Monday to Sunday, each with one column per bucket of the day
+    pub active_seconds: Vec<Vec<i64>>,
+    /// Days of the range falling on each weekday from Monday, for averages
+    pub day_counts: Vec<u32>,
+}
@@ -0,0 +1,271 @@
+//! Reads, verifies and summarizes InnoMonitor activity logs without starting the GUI.
+//! Works on this machine's logs or on a copied log or backup directory.
+
+use chrono::{NaiveDate, TimeZone};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use innomonitor_lib::activity_monitor::aggregation::{aggregate_activity, aggregate_log_results_in, recorded_zone};
+use innomonitor_lib::activity_monitor::chain::verify_log_chain;
+use innomonitor_lib::activity_monitor::export::{ExportFormat, Rounding, RoundingMode};
+use innomonitor_lib::activity_monitor::file_operations::{get_platform_directories, override_platform_directories};
+use innomonitor_lib::activity_monitor::records::read_log_file;
+use innomonitor_lib::activity_monitor::signed_report::{parse_signed_report, verify_signed_report, ReportFormat};
+use innomonitor_lib::activity_monitor::types::ActivityTotals;
+use innomonitor_lib::utils::commands::{export_activity_content, parse_timezone, signed_report_content};
+use innomonitor_lib::utils::key_manager::{active_keyring, install_key_store, unlock_activity_keys, KeyStore};
+use innomonitor_lib::utils::logger::set_console_output;
+
+const USAGE: &str = "\
+Usage: innomonitor-cli [OPTIONS] <COMMAND>
+
+Commands:
+  day <DATE>                     Timeline of one day
+  summary <START> [END]          Active, inactive and suspended time per day
+  verify <START> <END>           Check every record and the hash chain between days
+  decrypt <FILE>                 Print the records of a log or backup file as JSON lines
+  export <START> <END> <FORMAT>  Export the timeline as csv, jsonl or ics
+  report <START> <END> <FORMAT>  Write a signed activity report as json or html
+  verify-report <FILE>           Check the signature and totals of a signed report
+
+Options:
+  --dir <DIR>           Directory with the rs-fairsight(*.txt) logs (default: this machine's)
+  --keys <DIR>          Directory with activity_keys.json (default: the log directory)
+  --timezone <TZ>       IANA timezone for days and clock times (default: as recorded)
+  --round <MIN[:MODE]>  Round exported intervals to MIN minutes; MODE is nearest, up or down
+  --output <FILE>       Write the export or report to FILE instead of stdout
+  --key <HEX>           Public key a verified report must be signed with
+  --verbose             Show log messages
+
+Dates are YYYY-MM-DD. Passphrase-protected keys are unlocked with $INNOMONITOR_PASSPHRASE.";
+
+const PASSPHRASE_VAR: &str = "INNOMONITOR_PASSPHRASE";
+
+#[derive(Default)]
+struct Options {
+    dir: Option<PathBuf>,
+    keys: Option<PathBuf>,
+    timezone: Option<String>,
+    rounding: Option<Rounding>,
+    output: Option<PathBuf>,
+    key: Option<String>,
+    verbose: bool,
+    command: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--dir" => options.dir = Some(PathBuf::from(value("--dir")?)),
+            "--keys" => options.keys = Some(PathBuf::from(value("--keys")?)),
+            "--timezone" => options.timezone = Some(value("--timezone")?),
+            "--round" => options.rounding = Some(parse_rounding(&value("--round")?)?),
+            "--output" | "-o" => options.output = Some(PathBuf::from(value("--output")?)),
+            "--key" => options.key = Some(value("--key")?),
+            "--verbose" | "-v" => options.verbose = true,
+            "--help" | "-h" => options.command = vec!["help".to_string()],
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ => options.command.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+/// "15" or "15:up"
+fn parse_rounding(value: &str) -> Result<Rounding, String> {
+    let (minutes, mode) = value.split_once(':').unwrap_or((value, "nearest"));
+    let increment_minutes = minutes.parse().map_err(|_| format!("Invalid rounding '{}'", value))?;
+    let mode = match mode {
+        "nearest" => RoundingMode::Nearest,
+        "up" => RoundingMode::Up,
+        "down" => RoundingMode::Down,
+        _ => return Err(format!("Unknown rounding mode '{}'", mode)),
+    };
+    Ok(Rounding { increment_minutes, mode })
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| format!("Invalid date '{}': {}", value, e))
+}
+
+fn parse_format(value: &str) -> Result<ExportFormat, String> {
+    match value {
+        "csv" => Ok(ExportFormat::Csv),
+        "jsonl" => Ok(ExportFormat::Jsonl),
+        "ics" => Ok(ExportFormat::Ics),
+        _ => Err(format!("Unknown export format '{}'", value)),
+    }
+}
+
+fn parse_report_format(value: &str) -> Result<ReportFormat, String> {
+    match value {
+        "json" => Ok(ReportFormat::Json),
+        "html" => Ok(ReportFormat::Html),
+        _ => Err(format!("Unknown report format '{}'", value)),
+    }
+}
+
+/// Point the library at the chosen log directory and key store
+fn open_logs(options: &Options) -> Result<(), String> {
+    if let Some(dir) = &options.dir {
+        if !dir.is_dir() {
+            return Err(format!("{:?} is not a directory", dir));
+        }
+        override_platform_directories(dir.clone(), dir.clone());
+    }
+    let passphrase = std::env::var(PASSPHRASE_VAR).ok();
+
+    // Never create a key store in a copied directory; it must already be there
+    if let Some(keys_dir) = options.keys.as_ref().or(options.dir.as_ref()) {
+        let mut store = KeyStore::open(keys_dir)
+            .map_err(|e| format!("Failed to open key store in {:?}: {}", keys_dir, e))?;
+        if store.keyring().is_none() {
+            store.unlock(passphrase.as_deref())?;
+        }
+        install_key_store(store);
+    } else if let Some(passphrase) = passphrase {
+        unlock_activity_keys(&passphrase)?;
+    }
+    Ok(())
+}
+
+fn format_duration(seconds: i64) -> String {
+    format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+}
+
+fn print_summary<Tz: TimeZone>(start: NaiveDate, end: NaiveDate, tz: &Tz) -> Result<(), String> {
+    let aggregate = aggregate_activity(start, end, tz, false).map_err(|e| format!("Failed to read logs: {}", e))?;
+    let row = |label: &str, totals: &ActivityTotals| {
+        println!(
+            "{:<12}{:>12}{:>12}{:>12}",
+            label,
+            format_duration(totals.active_seconds),
+            format_duration(totals.inactive_seconds),
+            format_duration(totals.suspended_seconds)
+        );
+    };
+
+    println!("{:<12}{:>12}{:>12}{:>12}", "Date", "Active", "Inactive", "Suspended");
+    for day in &aggregate.days {
+        row(&day.date, &day.totals);
+    }
+    if start < end {
+        row("Total", &aggregate.totals);
+    }
+    Ok(())
+}
+
+fn verify(start: NaiveDate, end: NaiveDate) -> Result<ExitCode, String> {
+    let (log_dir, _) = get_platform_directories().map_err(|e| e.to_string())?;
+    let verification = verify_log_chain(&log_dir, start, end, &active_keyring()?);
+    for day in &verification.days {
+        println!("{}  {} records ({} unchained)", day.date, day.records, day.unchained_records);
+    }
+    for issue in &verification.issues {
+        println!("{}  {:?}: {}", issue.date, issue.kind, issue.message);
+    }
+    if let Some(hash) = &verification.final_hash {
+        // Records cut from the end of the last day only show as a different final hash
+        println!("Final hash {} (compare with an earlier run to catch records removed at the end)", hash);
+    }
+    if verification.valid {
+        println!("Chain intact from {} to {}", verification.start, verification.end);
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("{} problem(s) found", verification.issues.len());
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn decrypt(path: &Path) -> Result<ExitCode, String> {
+    let contents = read_log_file(path, &active_keyring()?).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    match contents.header {
+        Some(header) => eprintln!("Format version {}, key {}", header.version, header.key_id),
+        None => eprintln!("Legacy file without header"),
+    }
+    for record in &contents.records {
+        println!("{}", record.to_plaintext());
+    }
+    for problem in &contents.unreadable {
+        eprintln!("Unreadable record: {}", problem);
+    }
+    if contents.truncated {
+        eprintln!("The file ends with an incomplete record");
+    }
+    let damaged = contents.truncated || !contents.unreadable.is_empty();
+    Ok(if damaged { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
+
+fn verify_report(path: &Path, key: Option<&str>) -> Result<ExitCode, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let verification = verify_signed_report(&parse_signed_report(&content)?, key);
+    println!("Report {} to {}", verification.start_date, verification.end_date);
+    println!("Signed with key {}", verification.key_fingerprint);
+    for problem in &verification.problems {
+        println!("{}", problem);
+    }
+    if verification.valid {
+        println!("Signature valid and totals match the intervals");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn write_output(options: &Options, content: String) -> Result<(), String> {
+    match &options.output {
+        Some(path) => std::fs::write(path, content).map_err(|e| format!("Failed to write {:?}: {}", path, e)),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
+fn run<Tz: TimeZone>(options: &Options, tz: &Tz) -> Result<ExitCode, String> {
+    let args: Vec<&str> = options.command.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["day", date] => {
+            let file_name = format!("rs-fairsight({}).txt", parse_date(date)?.format("%Y-%m-%d"));
+            let timeline = aggregate_log_results_in(&file_name, tz).map_err(|e| e.to_string())?;
+            print!("{}", timeline);
+        }
+        ["summary", date] => print_summary(parse_date(date)?, parse_date(date)?, tz)?,
+        ["summary", start, end] => print_summary(parse_date(start)?, parse_date(end)?, tz)?,
+        ["verify", start, end] => return verify(parse_date(start)?, parse_date(end)?),
+        ["decrypt", file] => return decrypt(Path::new(file)),
+        ["export", start, end, format] => {
+            let format = parse_format(format)?;
+            let content = export_activity_content(start, end, format, options.timezone.as_deref(), options.rounding)?;
+            write_output(options, content)?;
+        }
+        ["report", start, end, format] => {
+            let format = parse_report_format(format)?;
+            write_output(options, signed_report_content(start, end, format, options.timezone.as_deref())?)?;
+        }
+        ["verify-report", file] => return verify_report(Path::new(file), options.key.as_deref()),
+        ["help"] => println!("{}", USAGE),
+        _ => return Err(format!("Unrecognised command\n\n{}", USAGE)),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let result = parse_args(std::env::args().skip(1)).and_then(|options| {
+        set_console_output(options.verbose);
+        open_logs(&options)?;
+        match parse_timezone(options.timezone.as_deref())? {
+            Some(tz) => run(&options, &tz),
+            None => {
+                let (log_dir, _) = get_platform_directories().map_err(|e| e.to_string())?;
+                run(&options, &recorded_zone(&log_dir))
+            }
+        }
+    });
+    result.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        ExitCode::FAILURE
+    })
+}
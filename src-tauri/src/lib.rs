@@ -0,0 +1,308 @@
+pub mod activity_monitor;
+pub mod network_monitor;
+pub mod utils;
+
+use chrono::Local;
+#[cfg(target_os = "windows")]
+use std::path::Path;
+use tauri::Manager;
+
+use crate::activity_monitor::core::record_shutdown;
+use crate::activity_monitor::{initialize_time_tracking, start_lifecycle_tracking};
+use crate::activity_monitor::log_writer::sync_activity_log;
+use crate::activity_monitor::recovery::recover_day_log;
+use crate::network_monitor::{
+    persistent_state::get_persistent_state_manager,
+};
+use crate::utils::{
+    commands::{
+        add_activity_tag, aggregate_week_activity_logs, apply_break_reminder_preset,
+        check_network_permissions_status, check_unexpected_shutdown, cleanup_network_backups,
+        cleanup_old_network_data, clear_all_logs, create_network_backup, export_activity,
+        export_device_activity, export_signed_report, get_activity_aggregate, get_activity_heatmap,
+        get_activity_key_status, get_activity_recovery_reports, get_activity_settings,
+        get_activity_tag_totals, get_activity_tags, get_adapter_persistent_state, get_all_logs,
+        get_application_usage, get_available_network_dates, get_calendar_days,
+        get_current_network_totals, get_devices, get_focus_sessions, get_health_status,
+        get_lifetime_stats, get_network_adapters_command, get_network_history, get_network_stats,
+        get_recent_logs_limited, get_report_signing_key, get_work_time, greet,
+        import_activity_history, import_calendar_file, is_network_monitoring,
+        load_daily_summary_command, merge_device_activity, recover_activity_log,
+        remove_activity_tag, remove_imported_calendar, remove_merged_device_activity,
+        request_network_permissions, restore_network_backup, rotate_activity_log_key,
+        set_activity_key_protection, set_activity_tracking_mode, set_break_reminders,
+        set_focus_settings, set_show_days_off, set_show_imported_activity, set_work_schedule,
+        start_network_monitoring, stop_network_monitoring, sync_time_data, unlock_activity_log_keys,
+        verify_activity_logs, verify_signed_report_file,
+    },
+    file_utils::is_log_file_valid,
+    health_monitor::initialize_health_monitoring,
+    hooks::setup_hooks,
+    key_manager::active_keyring,
+    ui_setup::{handle_window_event, setup_tray_and_window_events},
+    web_server::start_web_server,
+};
+
+// Global flag to prevent duplicate auto-start attempts
+static AUTO_START_COMPLETED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use crate::activity_monitor::file_operations::get_platform_directories;
+
+/// Repair today's activity log if a crash left it damaged, before anything appends to it
+fn recover_todays_log() {
+    // Open the activity log key store; the first run generates this installation's key
+    let keyring = match active_keyring() {
+        Ok(keyring) => Some(keyring),
+        Err(e) => {
+            crate::log_warning!("main", "Activity log keys unavailable, skipping log validation: {}", e);
+            None
+        }
+    };
+
+    // Initialize backup and validation with better error handling
+    #[cfg(target_os = "windows")]
+    {
+        let log_dir = Path::new("C:\\fairsight-log");
+        let backup_dir = Path::new("C:\\fairsight-backup");
+        let current_date = Local::now().format("%Y-%m-%d").to_string();
+        let file_name = format!("rs-fairsight({}).txt", current_date);
+        let log_file_path = log_dir.join(&file_name);
+
+        // Ensure directories exist
+        if let Err(e) = std::fs::create_dir_all(log_dir) {
+            eprintln!("Warning: Failed to create log directory: {}", e);
+        }
+        if let Err(e) = std::fs::create_dir_all(backup_dir) {
+            eprintln!("Warning: Failed to create backup directory: {}", e);
+        }
+
+        let needs_recovery = keyring.as_ref()
+            .filter(|keyring| log_file_path.exists() && !is_log_file_valid(&log_file_path, keyring));
+        if let Some(keyring) = needs_recovery {
+            match recover_day_log(log_dir, backup_dir, &file_name, keyring) {
+                Ok(report) => println!(
+                    "Recovered activity log at startup: {} records kept, {} damaged region(s)",
+                    report.records_kept, report.lost_ranges.len()
+                ),
+                Err(e) => eprintln!("Warning: Failed to recover activity log: {}", e),
+            }
+        } else {
+            println!("Log file is valid or doesn't exist at startup");
+        }
+    }
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        let (log_dir, backup_dir) = match get_platform_directories() {
+            Ok(dirs) => dirs,
+            Err(e) => {
+                eprintln!("Error: Could not determine log directories: {}", e);
+                return;
+            }
+        };
+        let current_date = Local::now().format("%Y-%m-%d").to_string();
+        let file_name = format!("rs-fairsight({}).txt", current_date);
+        let log_file_path = log_dir.join(&file_name);
+
+        // Ensure directories exist
+        if let Err(e) = std::fs::create_dir_all(&log_dir) {
+            eprintln!("Warning: Failed to create log directory: {}", e);
+        }
+        if let Err(e) = std::fs::create_dir_all(&backup_dir) {
+            eprintln!("Warning: Failed to create backup directory: {}", e);
+        }
+
+        let needs_recovery = keyring.as_ref()
+            .filter(|keyring| log_file_path.exists() && !is_log_file_valid(&log_file_path, keyring));
+        if let Some(keyring) = needs_recovery {
+            match recover_day_log(&log_dir, &backup_dir, &file_name, keyring) {
+                Ok(report) => println!(
+                    "Recovered activity log at startup: {} records kept, {} damaged region(s)",
+                    report.records_kept, report.lost_ranges.len()
+                ),
+                Err(e) => eprintln!("Warning: Failed to recover activity log: {}", e),
+            }
+        }
+    }
+}
+
+/// Start the tracker, web server and GUI; returns when the app exits
+pub fn run() {
+    // Initialize logging first
+    crate::log_info!("main", "Application starting...");
+    
+    // On Windows, try to load the bundled Npcap DLLs
+    #[cfg(target_os = "windows")]
+    {
+        if let Err(e) = crate::utils::dll_loader::ensure_npcap_dlls_loaded() {
+            crate::log_error!("main", "Failed to load Npcap DLLs: {}", e);
+        }
+    }
+    
+    let mut builder = tauri::Builder::default();
+
+    #[cfg(desktop)]
+    {
+        builder = builder.plugin(
+            tauri_plugin_single_instance::init(|app, _args, _cwd| {
+                let _ = app.get_webview_window("main").expect("no main window").set_focus();
+            })
+        );
+    }
+    
+    // Initialize time tracking
+    initialize_time_tracking();
+    crate::log_info!("main", "Time tracking initialized");
+    
+    // Initialize health monitoring
+    initialize_health_monitoring();
+    crate::log_info!("main", "Health monitoring initialized");
+
+    // Start web server in background
+    std::thread::spawn(|| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(start_web_server());
+    });
+
+    // (Moved) Auto-start network monitoring will now be handled in the Tauri .setup closure below
+
+    builder
+        .setup(|app| {
+            setup_tray_and_window_events(app)?;
+
+            // A second launch exits in the single-instance plugin before this point, so only
+            // the running instance repairs the day log, marks its start and records input
+            recover_todays_log();
+            start_lifecycle_tracking();
+            setup_hooks();
+
+            // Auto-start network monitoring after Tauri is fully initialized
+            tauri::async_runtime::spawn(async {
+                // Wait a bit for the application to fully initialize (optional, can be tuned)
+                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+                // Prevent duplicate auto-start attempts
+                if AUTO_START_COMPLETED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                    println!("ℹ️  Auto-start already completed, skipping duplicate attempt");
+                    return;
+                }
+
+                // Start comprehensive monitoring on all suitable adapters
+                match crate::utils::commands::start_comprehensive_monitoring().await {
+                    Ok(msg) => println!("✅ Auto-started comprehensive network monitoring: {}", msg),
+                    Err(e) => eprintln!("❌ Failed to auto-start comprehensive monitoring: {}", e),
+                }
+            });
+
+            // Check for unexpected shutdown and warn if needed
+            match get_persistent_state_manager().was_unexpected_shutdown() {
+                Ok(true) => {
+                    println!("⚠️  Detected unexpected shutdown - some network data may have been lost");
+                }
+                Ok(false) => {
+                    println!("✅ Clean shutdown detected - data integrity maintained");
+                }
+                Err(e) => {
+                    eprintln!("❌ Failed to check shutdown state: {}", e);
+                }
+            }
+
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            handle_window_event(window, event);
+            
+            // Handle clean shutdown on various window events
+            match event {
+                tauri::WindowEvent::CloseRequested { .. } => {
+                    println!("🔄 Application closing - marking clean shutdown...");
+                    if let Err(e) = sync_activity_log() {
+                        eprintln!("⚠️  Failed to flush activity log: {}", e);
+                    }
+                    if let Err(e) = get_persistent_state_manager().mark_clean_shutdown() {
+                        eprintln!("⚠️  Failed to mark clean shutdown: {}", e);
+                    }
+                }
+                tauri::WindowEvent::Destroyed => {
+                    println!("🔄 Window destroyed - ensuring clean shutdown...");
+                    if let Err(e) = record_shutdown() {
+                        eprintln!("⚠️  Failed to record shutdown in activity log: {}", e);
+                    }
+                    if let Err(e) = get_persistent_state_manager().mark_clean_shutdown() {
+                        eprintln!("⚠️  Failed to mark clean shutdown on destroy: {}", e);
+                    }
+                }
+                _ => {}
+            }
+        })
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .invoke_handler(
+            tauri::generate_handler![
+                greet,
+                sync_time_data,
+                aggregate_week_activity_logs,
+                get_activity_aggregate,
+                get_activity_heatmap,
+                get_health_status,
+                get_all_logs,
+                get_recent_logs_limited,
+                clear_all_logs,
+                get_network_adapters_command,
+                start_network_monitoring,
+                stop_network_monitoring,
+                get_network_stats,
+                is_network_monitoring,
+                get_network_history,
+                get_available_network_dates,
+                cleanup_old_network_data,
+                create_network_backup,
+                restore_network_backup,
+                cleanup_network_backups,
+                get_adapter_persistent_state,
+                get_lifetime_stats,
+                check_unexpected_shutdown,
+                get_current_network_totals,
+                request_network_permissions,
+                check_network_permissions_status,
+                load_daily_summary_command,
+                get_activity_settings,
+                set_activity_tracking_mode,
+                get_application_usage,
+                recover_activity_log,
+                get_activity_recovery_reports,
+                get_activity_key_status,
+                unlock_activity_log_keys,
+                set_activity_key_protection,
+                rotate_activity_log_key,
+                verify_activity_logs,
+                add_activity_tag,
+                remove_activity_tag,
+                get_activity_tags,
+                get_activity_tag_totals,
+                set_break_reminders,
+                apply_break_reminder_preset,
+                export_activity,
+                import_activity_history,
+                set_show_imported_activity,
+                get_focus_sessions,
+                set_focus_settings,
+                get_work_time,
+                set_work_schedule,
+                import_calendar_file,
+                get_calendar_days,
+                remove_imported_calendar,
+                set_show_days_off,
+                export_signed_report,
+                verify_signed_report_file,
+                get_report_signing_key,
+                merge_device_activity,
+                export_device_activity,
+                get_devices,
+                remove_merged_device_activity
+            ]
+        )
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
@@ -46,6 +46,12 @@ impl PersistentStateManager {
                 .ok_or_else(|| "Could not find home directory".to_string())?;
             state_dir = home_dir.join("Documents").join("fairsight-network-log");
         }
+        #[cfg(target_os = "linux")]
+        {
+            let home_dir = dirs::home_dir()
+                .ok_or_else(|| "Could not find home directory".to_string())?;
+            state_dir = home_dir.join(".local").join("share").join("innomonitor").join("network-log");
+        }
         #[cfg(target_os = "windows")]
         {
             state_dir = std::path::Path::new("C:\\fairsight-network-log").to_path_buf();
@@ -1,9 +1,10 @@
 use tauri::AppHandle;
+use tauri::Emitter;
+use tauri_plugin_notification::NotificationExt;
 use std::sync::Mutex;
 use once_cell::sync::Lazy;
 
-#[cfg(target_os = "windows")]
-use tauri::Emitter;
+use crate::activity_monitor::breaks::{describe_duration, BreakEvent};
 
 static APP_HANDLE: Lazy<Mutex<Option<AppHandle>>> = Lazy::new(|| Mutex::new(None));
 
@@ -24,3 +25,29 @@ pub fn send_message(msg: String) {
         handle.emit("my-event", msg).unwrap();
     }
 }
+
+/// Tell the UI about a break event and surface reminders as a desktop notification and tray tooltip
+pub fn notify_break(event: &BreakEvent) {
+    let Some(handle) = APP_HANDLE.lock().unwrap().clone() else { return };
+
+    if let Err(e) = handle.emit("break-reminder", event) {
+        crate::log_warning!("breaks", "Failed to send break event to the UI: {}", e);
+    }
+
+    let tooltip = match event {
+        BreakEvent::Reminder { worked_secs, break_secs, .. } => {
+            let body = format!(
+                "You have been working for {}. Step away from the screen for {}.",
+                describe_duration(*worked_secs), describe_duration(*break_secs)
+            );
+            if let Err(e) = handle.notification().builder().title("Time for a break").body(&body).show() {
+                crate::log_warning!("breaks", "Failed to show break notification: {}", e);
+            }
+            "InnoMonitor - time for a break"
+        }
+        BreakEvent::BreakTaken { .. } => "InnoMonitor",
+    };
+    if let Some(tray) = handle.tray_by_id("main_tray") {
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+}
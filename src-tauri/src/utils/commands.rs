@@ -1,4 +1,29 @@
-use crate::activity_monitor::aggregation::aggregate_log_results;
+use crate::activity_monitor::aggregation::{
+    activity_heatmap, aggregate_activity, aggregate_application_usage_range, aggregate_log_results,
+    aggregate_log_results_in, aggregate_tag_totals, recorded_zone, timeline_range,
+};
+use crate::activity_monitor::breaks::{BreakPreset, BreakReminderSettings};
+use crate::activity_monitor::calendar::{
+    days_off, import_calendar, remove_calendar, CalendarDay, CalendarImportReport, DayOffKind,
+};
+use crate::activity_monitor::chain::{verify_log_chain, ChainVerification};
+use crate::activity_monitor::devices::{
+    export_device_archive, merge_device_logs, registered_devices, remove_merged_device, DeviceList, MergeReport,
+};
+use crate::activity_monitor::export::{apply_rounding, render_export, ExportFormat, Rounding};
+use crate::activity_monitor::file_operations::get_platform_directories;
+use crate::activity_monitor::focus::{focus_report, FocusReport, FocusSettings};
+use crate::activity_monitor::importer::{import_file, ImportFormat, ImportReport};
+use crate::activity_monitor::lifecycle::{previous_run, ShutdownCheck};
+use crate::activity_monitor::schedule::{work_time_report, WorkSchedule, WorkTimeReport};
+use crate::activity_monitor::recovery::{get_recovery_reports, recover_day_log, RecoveryReport};
+use crate::activity_monitor::signed_report::{
+    build_report, parse_signed_report, render_report_html, sign_report, verify_signed_report, ReportFormat,
+    ReportSigningKey, ReportVerification,
+};
+use crate::activity_monitor::tags::{add_tag, remove_tag, tags_in_range, TimeTag};
+use crate::activity_monitor::types::{ActivityAggregate, ActivityHeatmap, ApplicationUsage, TagTotal};
+use crate::activity_monitor::settings::{load_activity_settings, save_activity_settings, ActivitySettings, TrackingMode};
 use crate::network_monitor::{
     network_monitor::{get_network_adapters, get_monitoring_adapters, NetworkAdapter},
     network_storage::{NETWORK_STORAGE, DailyNetworkSummary},
@@ -7,6 +32,10 @@ use crate::network_monitor::{
 };
 use crate::utils::{
     health_monitor::{HEALTH_MONITOR, get_comprehensive_system_health, SystemHealthStatus},
+    key_manager::{
+        active_keyring, key_status, load_or_create_signing_key, rotate_activity_key, set_key_protection,
+        unlock_activity_keys, KeyProtection, KeyRotationReport, KeyStatus,
+    },
     logger::{get_logs, get_recent_logs, clear_logs, LogEntry},
 };
 
@@ -15,26 +44,474 @@ pub fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Parses an IANA timezone name such as "Europe/Berlin"; `None` reports in the zone the logs were recorded in
+pub fn parse_timezone(timezone: Option<&str>) -> Result<Option<chrono_tz::Tz>, String> {
+    timezone
+        .map(|name| name.parse::<chrono_tz::Tz>().map_err(|_| format!("Unknown timezone '{}'", name)))
+        .transpose()
+}
+
+/// Aggregates one daily log in `timezone`, or the zone it was recorded in if none is given
+fn aggregate_log_in(file_name: &str, timezone: Option<chrono_tz::Tz>) -> Result<String, Box<dyn std::error::Error>> {
+    match timezone {
+        Some(tz) => aggregate_log_results_in(file_name, &tz),
+        None => aggregate_log_results(file_name),
+    }
+}
+
 #[tauri::command]
-pub fn sync_time_data(report_date: &str) -> String {
-    match aggregate_log_results(report_date) {
+pub fn sync_time_data(report_date: &str, timezone: Option<String>) -> String {
+    let result = parse_timezone(timezone.as_deref())
+        .and_then(|tz| aggregate_log_in(report_date, tz).map_err(|e| e.to_string()));
+    match result {
         Ok(result) => result,
         Err(e) => format!("Error: {}", e),
     }
 }
 
 #[tauri::command]
-pub fn aggregate_week_activity_logs(data_list: Vec<String>) -> Vec<String> {
+pub fn aggregate_week_activity_logs(data_list: Vec<String>, timezone: Option<String>) -> Result<Vec<String>, String> {
+    let tz = parse_timezone(timezone.as_deref())?;
     let mut logdb_list = Vec::with_capacity(data_list.len());
 
     for (_i, s) in data_list.into_iter().enumerate() {
         let styled = format!("rs-fairsight({}).txt", s);
-        let result = aggregate_log_results(&styled)
+        let result = aggregate_log_in(&styled, tz)
             .unwrap_or_else(|e| format!("Error aggregating {}: {}", styled, e));
         logdb_list.push(result);
     }
 
-    logdb_list
+    Ok(logdb_list)
+}
+
+/// Timeline and totals over an inclusive date range; `include_text` adds each day's text timeline
+#[tauri::command]
+pub fn get_activity_aggregate(
+    start_date: String,
+    end_date: String,
+    timezone: Option<String>,
+    include_text: Option<bool>
+) -> Result<ActivityAggregate, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let include_text = include_text.unwrap_or(false);
+    let result = match parse_timezone(timezone.as_deref())? {
+        Some(tz) => aggregate_activity(start, end, &tz, include_text),
+        None => aggregate_activity(start, end, &recorded_zone(&activity_directories()?.0), include_text),
+    };
+    result.map_err(|e| format!("Failed to aggregate activity: {}", e))
+}
+
+/// Active time by weekday and time of day; `bucket_minutes` defaults to hourly buckets
+#[tauri::command]
+pub fn get_activity_heatmap(
+    start_date: String,
+    end_date: String,
+    timezone: Option<String>,
+    bucket_minutes: Option<u32>
+) -> Result<ActivityHeatmap, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let bucket_minutes = bucket_minutes.unwrap_or(60);
+    let result = match parse_timezone(timezone.as_deref())? {
+        Some(tz) => activity_heatmap(start, end, &tz, bucket_minutes),
+        None => activity_heatmap(start, end, &recorded_zone(&activity_directories()?.0), bucket_minutes),
+    };
+    result.map_err(|e| format!("Failed to build activity heatmap: {}", e))
+}
+
+#[tauri::command]
+pub fn get_application_usage(start_date: String, end_date: String) -> Result<Vec<ApplicationUsage>, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    aggregate_application_usage_range(start, end)
+        .map_err(|e| format!("Failed to aggregate application usage: {}", e))
+}
+
+/// Log and backup directories of the activity monitor
+fn activity_directories() -> Result<(std::path::PathBuf, std::path::PathBuf), String> {
+    get_platform_directories().map_err(|e| format!("Failed to locate activity logs: {}", e))
+}
+
+/// Longest date range a report or export may cover, in days
+pub const MAX_DATE_RANGE_DAYS: i64 = 366;
+
+/// Parses an inclusive "YYYY-MM-DD" date range of at most [`MAX_DATE_RANGE_DAYS`] days
+fn parse_date_range(start_date: &str, end_date: &str) -> Result<(chrono::NaiveDate, chrono::NaiveDate), String> {
+    let start = chrono::NaiveDate::parse_from_str(start_date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid start date '{}': {}", start_date, e))?;
+    let end = chrono::NaiveDate::parse_from_str(end_date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid end date '{}': {}", end_date, e))?;
+    if start > end {
+        return Err("Start date cannot be after end date".to_string());
+    }
+    if (end - start).num_days() >= MAX_DATE_RANGE_DAYS {
+        return Err(format!("Date range cannot be longer than {} days", MAX_DATE_RANGE_DAYS));
+    }
+    Ok((start, end))
+}
+
+/// The activity timeline over an inclusive date range rendered in `format`
+pub fn export_activity_content(
+    start_date: &str,
+    end_date: &str,
+    format: ExportFormat,
+    timezone: Option<&str>,
+    rounding: Option<Rounding>
+) -> Result<String, String> {
+    fn render<Tz: chrono::TimeZone>(
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        format: ExportFormat,
+        tz: &Tz,
+        rounding: Option<Rounding>
+    ) -> Result<String, String> {
+        let intervals = timeline_range(start, end, tz)
+            .map_err(|e| format!("Failed to read activity timeline: {}", e))?;
+        let intervals = match rounding {
+            Some(rounding) => apply_rounding(&intervals, rounding, tz),
+            None => intervals,
+        };
+        Ok(render_export(&intervals, format, tz))
+    }
+
+    let (start, end) = parse_date_range(start_date, end_date)?;
+    match parse_timezone(timezone)? {
+        Some(tz) => render(start, end, format, &tz, rounding),
+        None => render(start, end, format, &recorded_zone(&activity_directories()?.0), rounding),
+    }
+}
+
+#[tauri::command]
+pub fn export_activity(
+    start_date: String,
+    end_date: String,
+    format: ExportFormat,
+    path: String,
+    timezone: Option<String>,
+    rounding: Option<Rounding>
+) -> Result<String, String> {
+    let content = export_activity_content(&start_date, &end_date, format, timezone.as_deref(), rounding)?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    crate::log_info!("export", "Exported activity {} to {} as {:?}", start_date, end_date, format);
+    Ok(format!("Exported activity from {} to {} to {}", start_date, end_date, path))
+}
+
+/// A report of an inclusive date range signed with this installation's key
+pub fn signed_report_content(
+    start_date: &str,
+    end_date: &str,
+    format: ReportFormat,
+    timezone: Option<&str>
+) -> Result<String, String> {
+    fn render<Tz: chrono::TimeZone>(
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        format: ReportFormat,
+        tz: &Tz,
+        timezone: &str
+    ) -> Result<String, String> {
+        let (log_dir, _) = activity_directories()?;
+        let report = build_report(&log_dir, start, end, tz, timezone)?;
+        let signed = sign_report(report, &load_or_create_signing_key()?)?;
+        match format {
+            ReportFormat::Json => serde_json::to_string_pretty(&signed)
+                .map_err(|e| format!("Failed to serialize report: {}", e)),
+            ReportFormat::Html => render_report_html(&signed, tz),
+        }
+    }
+
+    let (start, end) = parse_date_range(start_date, end_date)?;
+    match parse_timezone(timezone)? {
+        Some(tz) => render(start, end, format, &tz, tz.name()),
+        None => render(start, end, format, &recorded_zone(&activity_directories()?.0), "Local time as recorded"),
+    }
+}
+
+#[tauri::command]
+pub fn export_signed_report(
+    start_date: String,
+    end_date: String,
+    format: ReportFormat,
+    path: String,
+    timezone: Option<String>
+) -> Result<String, String> {
+    let content = signed_report_content(&start_date, &end_date, format, timezone.as_deref())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    crate::log_info!("export", "Exported signed report {} to {} as {:?}", start_date, end_date, format);
+    Ok(format!("Exported signed report from {} to {} to {}", start_date, end_date, path))
+}
+
+/// Check a signed report file; `public_key` additionally requires it to be signed by that key
+#[tauri::command]
+pub fn verify_signed_report_file(path: String, public_key: Option<String>) -> Result<ReportVerification, String> {
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok(verify_signed_report(&parse_signed_report(&content)?, public_key.as_deref()))
+}
+
+#[tauri::command]
+pub fn get_report_signing_key() -> Result<ReportSigningKey, String> {
+    Ok(ReportSigningKey::of(&load_or_create_signing_key()?))
+}
+
+#[tauri::command]
+pub fn add_activity_tag(
+    start: u64,
+    end: u64,
+    project: Option<String>,
+    task: Option<String>,
+    note: Option<String>
+) -> Result<TimeTag, String> {
+    let (log_dir, _) = activity_directories()?;
+    add_tag(&log_dir, start, end, project, task, note, &active_keyring()?)
+}
+
+#[tauri::command]
+pub fn remove_activity_tag(id: String) -> Result<String, String> {
+    let (log_dir, _) = activity_directories()?;
+    if remove_tag(&log_dir, &id, &active_keyring()?)? {
+        Ok(format!("Removed tag {}", id))
+    } else {
+        Err(format!("No tag with id {}", id))
+    }
+}
+
+#[tauri::command]
+pub fn get_activity_tags(start_date: String, end_date: String) -> Result<Vec<TimeTag>, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let (log_dir, _) = activity_directories()?;
+    tags_in_range(&log_dir, start, end, &active_keyring()?)
+}
+
+#[tauri::command]
+pub fn get_activity_tag_totals(start_date: String, end_date: String) -> Result<Vec<TagTotal>, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    aggregate_tag_totals(start, end)
+        .map_err(|e| format!("Failed to aggregate tags: {}", e))
+}
+
+#[tauri::command]
+pub fn get_activity_settings() -> ActivitySettings {
+    load_activity_settings()
+}
+
+#[tauri::command]
+pub fn set_activity_tracking_mode(mode: TrackingMode) -> Result<String, String> {
+    let mut settings = load_activity_settings();
+    settings.tracking_mode = mode;
+    save_activity_settings(&settings)
+        .map_err(|e| format!("Failed to save activity settings: {}", e))?;
+    Ok(format!("Activity tracking mode set to {:?}. Restart InnoMonitor to apply.", mode))
+}
+
+#[tauri::command]
+pub fn set_break_reminders(reminders: BreakReminderSettings) -> Result<String, String> {
+    if reminders.enabled && (reminders.work_secs == 0 || reminders.break_secs == 0 || reminders.repeat_secs == 0) {
+        return Err("Work, break and repeat durations must be greater than zero".to_string());
+    }
+    let mut settings = load_activity_settings();
+    settings.break_reminders = reminders;
+    save_activity_settings(&settings)
+        .map_err(|e| format!("Failed to save activity settings: {}", e))?;
+    Ok(if reminders.enabled { "Break reminders enabled" } else { "Break reminders disabled" }.to_string())
+}
+
+#[tauri::command]
+pub fn import_activity_history(path: String, format: ImportFormat) -> Result<ImportReport, String> {
+    let (log_dir, _) = activity_directories()?;
+    import_file(&log_dir, std::path::Path::new(&path), format, &active_keyring()?)
+}
+
+#[tauri::command]
+pub fn set_show_imported_activity(show: bool) -> Result<String, String> {
+    let mut settings = load_activity_settings();
+    settings.show_imported = show;
+    save_activity_settings(&settings)
+        .map_err(|e| format!("Failed to save activity settings: {}", e))?;
+    Ok(if show { "Imported activity shown in reports" } else { "Imported activity hidden from reports" }.to_string())
+}
+
+/// Merge another device's logs from a copy of its log folder or a device archive
+#[tauri::command]
+pub fn merge_device_activity(
+    path: String,
+    device_name: Option<String>,
+    passphrase: Option<String>
+) -> Result<MergeReport, String> {
+    let (log_dir, _) = activity_directories()?;
+    merge_device_logs(
+        &log_dir,
+        std::path::Path::new(&path),
+        device_name.as_deref(),
+        passphrase.as_deref(),
+        &active_keyring()?
+    )
+}
+
+/// Write this device's activity of an inclusive date range to a device archive for merging
+/// on another machine. The archive is not encrypted.
+#[tauri::command]
+pub fn export_device_activity(start_date: String, end_date: String, path: String) -> Result<String, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let (log_dir, _) = activity_directories()?;
+    let archive = export_device_archive(&log_dir, start, end, &active_keyring()?)?;
+    let json = serde_json::to_vec(&archive).map_err(|e| format!("Failed to serialize archive: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(format!("Exported {} records from {} to {} to {}", archive.records.len(), start_date, end_date, path))
+}
+
+#[tauri::command]
+pub fn get_devices() -> Result<DeviceList, String> {
+    let (log_dir, _) = activity_directories()?;
+    registered_devices(&log_dir)
+}
+
+#[tauri::command]
+pub fn remove_merged_device_activity(device_id: String) -> Result<String, String> {
+    let (log_dir, _) = activity_directories()?;
+    let removed = remove_merged_device(&log_dir, &device_id, &active_keyring()?)?;
+    Ok(format!("Removed {} records merged from {}", removed, device_id))
+}
+
+/// Import the all-day events of an .ics file as holidays or leave
+#[tauri::command]
+pub fn import_calendar_file(path: String, kind: DayOffKind, name: Option<String>) -> Result<CalendarImportReport, String> {
+    let (log_dir, _) = activity_directories()?;
+    import_calendar(&log_dir, std::path::Path::new(&path), kind, name)
+}
+
+#[tauri::command]
+pub fn get_calendar_days(start_date: String, end_date: String) -> Result<Vec<CalendarDay>, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let (log_dir, _) = activity_directories()?;
+    Ok(days_off(&log_dir, start, end)?.into_values().flatten().collect())
+}
+
+#[tauri::command]
+pub fn remove_imported_calendar(source: String) -> Result<String, String> {
+    let (log_dir, _) = activity_directories()?;
+    match remove_calendar(&log_dir, &source)? {
+        0 => Err(format!("No calendar named {}", source)),
+        removed => Ok(format!("Removed {} days of {}", removed, source)),
+    }
+}
+
+#[tauri::command]
+pub fn set_show_days_off(show: bool) -> Result<String, String> {
+    let mut settings = load_activity_settings();
+    settings.show_days_off = show;
+    save_activity_settings(&settings)
+        .map_err(|e| format!("Failed to save activity settings: {}", e))?;
+    Ok(if show { "Holidays and leave shown on the timeline" } else { "Holidays and leave hidden from the timeline" }.to_string())
+}
+
+/// Focus sessions and fragmentation per day; `gap_tolerance_minutes` overrides the setting
+#[tauri::command]
+pub fn get_focus_sessions(
+    start_date: String,
+    end_date: String,
+    timezone: Option<String>,
+    gap_tolerance_minutes: Option<u32>
+) -> Result<FocusReport, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let (log_dir, _) = activity_directories()?;
+    let mut settings = load_activity_settings().focus;
+    if let Some(minutes) = gap_tolerance_minutes {
+        settings.gap_tolerance_minutes = minutes;
+    }
+    let keyring = active_keyring()?;
+    match parse_timezone(timezone.as_deref())? {
+        Some(tz) => focus_report(&log_dir, start, end, &tz, settings, &keyring),
+        None => focus_report(&log_dir, start, end, &recorded_zone(&log_dir), settings, &keyring),
+    }
+}
+
+#[tauri::command]
+pub fn set_focus_settings(focus: FocusSettings) -> Result<FocusSettings, String> {
+    let mut settings = load_activity_settings();
+    settings.focus = focus;
+    save_activity_settings(&settings)
+        .map_err(|e| format!("Failed to save activity settings: {}", e))?;
+    Ok(focus)
+}
+
+/// Active time inside and outside working hours, overtime and the weekly balance per day
+#[tauri::command]
+pub fn get_work_time(start_date: String, end_date: String, timezone: Option<String>) -> Result<WorkTimeReport, String> {
+    let (start, end) = parse_date_range(&start_date, &end_date)?;
+    let (log_dir, _) = activity_directories()?;
+    let schedule = load_activity_settings().schedule;
+    match parse_timezone(timezone.as_deref())? {
+        Some(tz) => work_time_report(&log_dir, start, end, &schedule, &tz),
+        None => work_time_report(&log_dir, start, end, &schedule, &recorded_zone(&log_dir)),
+    }
+}
+
+#[tauri::command]
+pub fn set_work_schedule(schedule: WorkSchedule) -> Result<WorkSchedule, String> {
+    schedule.validate()?;
+    let mut settings = load_activity_settings();
+    settings.schedule = schedule.clone();
+    save_activity_settings(&settings)
+        .map_err(|e| format!("Failed to save activity settings: {}", e))?;
+    Ok(schedule)
+}
+
+#[tauri::command]
+pub fn apply_break_reminder_preset(preset: BreakPreset) -> Result<BreakReminderSettings, String> {
+    let reminders = preset.settings();
+    set_break_reminders(reminders)?;
+    Ok(reminders)
+}
+
+#[tauri::command]
+pub fn recover_activity_log(date: String) -> Result<RecoveryReport, String> {
+    chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date '{}': {}", date, e))?;
+    let (log_dir, backup_dir) = activity_directories()?;
+    let file_name = format!("rs-fairsight({}).txt", date);
+
+    recover_day_log(&log_dir, &backup_dir, &file_name, &active_keyring()?)
+        .map_err(|e| format!("Failed to recover {}: {}", file_name, e))
+}
+
+#[tauri::command]
+pub fn verify_activity_logs(start: String, end: String) -> Result<ChainVerification, String> {
+    let (start_date, end_date) = parse_date_range(&start, &end)?;
+    let (log_dir, _) = activity_directories()?;
+
+    let verification = verify_log_chain(&log_dir, start_date, end_date, &active_keyring()?);
+    for issue in &verification.issues {
+        crate::log_warning!("verify", "{}: {}", issue.date, issue.message);
+    }
+    if let Some(hash) = &verification.final_hash {
+        crate::log_info!("verify", "Chain up to {} ends in {}", verification.end, hash);
+    }
+    Ok(verification)
+}
+
+#[tauri::command]
+pub fn get_activity_recovery_reports() -> Vec<RecoveryReport> {
+    get_recovery_reports()
+}
+
+#[tauri::command]
+pub fn get_activity_key_status() -> Result<KeyStatus, String> {
+    key_status()
+}
+
+#[tauri::command]
+pub fn unlock_activity_log_keys(passphrase: String) -> Result<String, String> {
+    unlock_activity_keys(&passphrase)?;
+    Ok("Activity log keys unlocked".to_string())
+}
+
+#[tauri::command]
+pub fn set_activity_key_protection(protection: KeyProtection, passphrase: Option<String>) -> Result<String, String> {
+    set_key_protection(protection, passphrase.as_deref())?;
+    Ok(format!("Activity log keys are now protected by {:?}", protection))
+}
+
+#[tauri::command]
+pub fn rotate_activity_log_key() -> Result<KeyRotationReport, String> {
+    rotate_activity_key()
 }
 
 #[tauri::command]
@@ -469,9 +946,17 @@ pub fn get_lifetime_stats() -> Result<std::collections::HashMap<String, AdapterP
     get_persistent_state_manager().get_all_adapter_states()
 }
 
+/// Whether the previous session ended without shutting down, with the day and time the
+/// activity log last heard from it
 #[tauri::command]
-pub fn check_unexpected_shutdown() -> Result<bool, String> {
-    get_persistent_state_manager().was_unexpected_shutdown()
+pub fn check_unexpected_shutdown() -> Result<ShutdownCheck, String> {
+    let network_data_lost = get_persistent_state_manager().was_unexpected_shutdown()?;
+    let previous_run = previous_run();
+    Ok(ShutdownCheck {
+        unexpected: network_data_lost || previous_run.as_ref().is_some_and(|run| !run.clean),
+        previous_run,
+        network_data_lost,
+    })
 }
 
 #[tauri::command]
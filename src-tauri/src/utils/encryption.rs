@@ -1,13 +1,18 @@
 use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
 use ring::error::Unspecified;
+use ring::pbkdf2;
 use ring::rand::{SecureRandom, SystemRandom};
+use std::num::NonZeroU32;
 
-pub const KEY: [u8; 32] = [0x42; 32]; // Replace with a securely generated key
+/// Built-in key of activity logs written before per-installation keys (key id 0).
+/// Kept only so those logs stay readable; new logs use keys from the key manager.
+pub const LEGACY_KEY: [u8; 32] = [0x42; 32];
 
-/// Encrypt a string, returning the encrypted bytes and nonce
-pub fn encrypt_string(
+/// Encrypt a string bound to `aad`, returning the ciphertext with its tag (no length prefix) and the nonce
+pub fn seal_string(
     plaintext: &str,
-    key_bytes: &[u8; 32]
+    key_bytes: &[u8; 32],
+    aad: &[u8]
 ) -> Result<(Vec<u8>, [u8; 12]), Unspecified> {
     let rng = SystemRandom::new();
     let mut nonce_bytes = [0u8; 12];
@@ -18,27 +23,61 @@ pub fn encrypt_string(
     let nonce = Nonce::assume_unique_for_key(nonce_bytes);
 
     let mut data = plaintext.as_bytes().to_vec();
-    key.seal_in_place_append_tag(nonce, Aad::empty(), &mut data)?;
-
-    // Prepend the length of the encrypted data
-    let mut result = Vec::new();
-    let len = data.len() as u32;
-    result.extend_from_slice(&len.to_le_bytes()); // 4 bytes for length
-    result.extend_from_slice(&data);
+    key.seal_in_place_append_tag(nonce, Aad::from(aad), &mut data)?;
 
-    Ok((result, nonce_bytes))
+    Ok((data, nonce_bytes))
 }
 
-/// Decrypt a string from encrypted bytes and nonce
+/// Decrypt a string that was sealed bound to `aad`; fails if `aad` differs
 pub fn decrypt_string(
-    encrypted_data: &mut Vec<u8>,
+    encrypted_data: &mut [u8],
     key_bytes: &[u8; 32],
-    nonce_bytes: [u8; 12]
+    nonce_bytes: [u8; 12],
+    aad: &[u8]
 ) -> Result<String, Unspecified> {
     let unbound_key = UnboundKey::new(&AES_256_GCM, key_bytes)?;
     let key = LessSafeKey::new(unbound_key);
     let nonce = Nonce::assume_unique_for_key(nonce_bytes);
 
-    let decrypted_data = key.open_in_place(nonce, Aad::empty(), encrypted_data)?;
+    let decrypted_data = key.open_in_place(nonce, Aad::from(aad), encrypted_data)?;
     String::from_utf8(decrypted_data.to_vec()).map_err(|_| Unspecified)
 }
+
+/// Generate a random 256-bit key
+pub fn generate_key() -> Result<[u8; 32], Unspecified> {
+    random_bytes()
+}
+
+/// Generate random bytes, e.g. for salts
+pub fn random_bytes<const N: usize>() -> Result<[u8; N], Unspecified> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new().fill(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Derive a key-encryption key from a passphrase with PBKDF2-HMAC-SHA256
+pub fn derive_key_from_passphrase(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut derived = [0u8; 32];
+    let iterations = NonZeroU32::new(iterations.max(1)).unwrap();
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut derived);
+    derived
+}
+
+/// Encrypt `key` with `wrapping_key`; `aad` binds the result to its context (e.g. the key id)
+pub fn wrap_key(wrapping_key: &[u8; 32], key: &[u8; 32], aad: &[u8]) -> Result<(Vec<u8>, [u8; 12]), Unspecified> {
+    let nonce_bytes = random_bytes::<12>()?;
+    let sealing_key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, wrapping_key)?);
+
+    let mut data = key.to_vec();
+    sealing_key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::from(aad), &mut data)?;
+    Ok((data, nonce_bytes))
+}
+
+/// Reverse `wrap_key`. Fails on a wrong wrapping key (e.g. a mistyped passphrase).
+pub fn unwrap_key(wrapping_key: &[u8; 32], wrapped: &[u8], nonce_bytes: [u8; 12], aad: &[u8]) -> Result<[u8; 32], Unspecified> {
+    let opening_key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, wrapping_key)?);
+
+    let mut data = wrapped.to_vec();
+    let key = opening_key.open_in_place(Nonce::assume_unique_for_key(nonce_bytes), Aad::from(aad), &mut data)?;
+    key.try_into().map_err(|_| Unspecified)
+}
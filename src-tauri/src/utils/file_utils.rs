@@ -2,7 +2,8 @@ use std::fs;
 use std::path::Path;
 use std::io::Write;
 use chrono;
-use crate::utils::encryption::decrypt_string;
+use crate::activity_monitor::records::read_log_file;
+use crate::utils::key_manager::KeyRing;
 
 /// Atomically save a backup with timestamp
 pub fn save_backup(source_dir: &Path, target_dir: &Path, file_name: &str) -> std::io::Result<()> {
@@ -68,62 +69,6 @@ fn cleanup_old_backups(backup_dir: &Path, base_file_name: &str, keep_count: usiz
     Ok(())
 }
 
-/// Restore from the most recent valid backup
-pub fn load_backup(backup_dir: &Path, restore_dir: &Path, file_name: &str) -> std::io::Result<()> {
-    if !restore_dir.exists() {
-        fs::create_dir_all(restore_dir)?;
-    }
-    
-    // Find the most recent backup
-    let mut backups = Vec::new();
-    
-    if let Ok(entries) = fs::read_dir(backup_dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let entry_file_name = entry.file_name();
-                let entry_file_name_str = entry_file_name.to_string_lossy();
-                
-                if entry_file_name_str.starts_with(&format!("{}.backup_", file_name)) {
-                    if let Ok(metadata) = entry.metadata() {
-                        if let Ok(modified) = metadata.modified() {
-                            backups.push((entry.path(), modified));
-                        }
-                    }
-                }
-            }
-        }
-    }
-    
-    if backups.is_empty() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "No backup files found"
-        ));
-    }
-    
-    // Sort by modification time (newest first)
-    backups.sort_by(|a, b| b.1.cmp(&a.1));
-    
-    // Try to restore from the most recent valid backup
-    for (backup_path, _) in backups {
-        match fs::copy(&backup_path, restore_dir.join(file_name)) {
-            Ok(_) => {
-                println!("Successfully restored from backup: {:?}", backup_path);
-                return Ok(());
-            }
-            Err(e) => {
-                eprintln!("Failed to restore from backup {:?}: {}", backup_path, e);
-                continue;
-            }
-        }
-    }
-    
-    Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "All backup restoration attempts failed"
-    ))
-}
-
 /// Write data atomically to a file with automatic backup
 pub fn atomic_write_with_backup(
     file_path: &Path,
@@ -139,6 +84,16 @@ pub fn atomic_write_with_backup(
         }
     }
     
+    write_and_replace(file_path, data, false)
+}
+
+/// Write data atomically to a file only the current user may read. On Unix the file is
+/// created with owner-only permissions before anything is written to it.
+pub fn atomic_write_owner_only(file_path: &Path, data: &[u8]) -> std::io::Result<()> {
+    write_and_replace(file_path, data, true)
+}
+
+fn write_and_replace(file_path: &Path, data: &[u8], owner_only: bool) -> std::io::Result<()> {
     // Create parent directory if it doesn't exist
     if let Some(parent) = file_path.parent() {
         if !parent.exists() {
@@ -152,7 +107,18 @@ pub fn atomic_write_with_backup(
     );
     
     {
-        let mut temp_file = fs::File::create(&temp_path)?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if owner_only {
+            use std::os::unix::fs::OpenOptionsExt;
+            // A temporary file left over by a crash would keep its permissions
+            let _ = fs::remove_file(&temp_path);
+            options.mode(0o600);
+        }
+        #[cfg(not(unix))]
+        let _ = owner_only;
+        let mut temp_file = options.open(&temp_path)?;
         temp_file.write_all(data)?;
         temp_file.sync_all()?; // Ensure data is written to disk
     }
@@ -163,47 +129,8 @@ pub fn atomic_write_with_backup(
     Ok(())
 }
 
-pub fn is_log_file_valid(file_path: &Path, key: &[u8; 32]) -> bool {
-    let content = match fs::read(file_path) {
-        Ok(c) => c,
-        Err(_) => return false,
-    };
-    let mut offset = 0;
-
-    while offset < content.len() {
-        // Check for enough bytes for nonce (12) + length (4)
-        if content.len() - offset < 12 + 4 {
-            return false;
-        }
-
-        // Read nonce (12 bytes)
-        let nonce_bytes: [u8; 12] = match content[offset..offset + 12].try_into() {
-            Ok(n) => n,
-            Err(_) => return false,
-        };
-        offset += 12;
-
-        // Read length (4 bytes)
-        let len_bytes: [u8; 4] = match content[offset..offset + 4].try_into() {
-            Ok(l) => l,
-            Err(_) => return false,
-        };
-        let encrypted_len = u32::from_le_bytes(len_bytes) as usize;
-        offset += 4;
-
-        // Check for enough bytes for encrypted content
-        if content.len() - offset < encrypted_len {
-            return false;
-        }
-
-        let mut encrypted_data = content[offset..offset + encrypted_len].to_vec();
-        offset += encrypted_len;
-
-        // Try to decrypt
-        if decrypt_string(&mut encrypted_data, key, nonce_bytes).is_err() {
-            return false;
-        }
-    }
-
-    true
+/// A daily activity log is valid when every record decrypts at the position and day it was sealed for.
+/// An incomplete last record is left by a crash mid-append and is cut off by the log writer, so it is allowed.
+pub fn is_log_file_valid(file_path: &Path, keyring: &KeyRing) -> bool {
+    read_log_file(file_path, keyring).is_ok()
 }
@@ -0,0 +1,322 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::activity_monitor::get_current_time;
+use super::{ActivitySink, InputSource};
+
+/// Failed polls in a row after which the provider is given up and a new one is detected
+const MAX_CONSECUTIVE_POLL_ERRORS: u32 = 5;
+/// First wait before starting idle polling again
+const RESTART_DELAY: Duration = Duration::from_secs(10);
+/// Longest wait between attempts to start idle polling again
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+/// Reports how long the user has been idle, in seconds
+pub trait IdleTimeProvider: Send {
+    fn name(&self) -> &'static str;
+    fn idle_seconds(&mut self) -> Result<u64, String>;
+}
+
+/// Input source that polls the OS idle time and emits the time of the last user input.
+/// Needs no input hooks, so it works on Wayland and without extra permissions.
+pub struct IdlePollSource {
+    provider: Box<dyn IdleTimeProvider>,
+    interval: Duration,
+}
+
+impl IdlePollSource {
+    pub fn new(provider: Box<dyn IdleTimeProvider>, interval: Duration) -> Self {
+        Self { provider, interval }
+    }
+}
+
+impl InputSource for IdlePollSource {
+    fn name(&self) -> &'static str {
+        "idle poll"
+    }
+
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String> {
+        crate::log_info!("hooks", "Polling idle time via {} every {:?}", self.provider.name(), self.interval);
+        let mut last_emitted = 0;
+        let mut failures = 0;
+
+        loop {
+            match self.provider.idle_seconds() {
+                Ok(idle) => {
+                    failures = 0;
+                    let last_input = get_current_time().saturating_sub(idle);
+
+                    // Only report input that happened since the previous poll
+                    if last_input > last_emitted {
+                        last_emitted = last_input;
+                        sink.emit_at(last_input);
+                    }
+                }
+                // A single failed poll, e.g. a slow loginctl, only skips that poll
+                Err(e) => {
+                    failures += 1;
+                    if failures >= MAX_CONSECUTIVE_POLL_ERRORS {
+                        return Err(format!("{} failed {} times in a row: {}", self.provider.name(), failures, e));
+                    }
+                    crate::log_warning!("hooks", "Idle time poll via {} failed: {}", self.provider.name(), e);
+                }
+            }
+
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+/// Pick the best idle-time backend available on this platform
+pub fn detect_idle_provider() -> Result<Box<dyn IdleTimeProvider>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        match XScreenSaverIdle::connect() {
+            Ok(provider) => return Ok(Box::new(provider)),
+            Err(e) => crate::log_warning!("hooks", "XScreenSaver idle time unavailable: {}", e),
+        }
+        let mut logind = LogindIdle::new();
+        logind.idle_seconds().map_err(|e| format!("No idle time source available: {}", e))?;
+        Ok(Box::new(logind))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        Ok(Box::new(WindowsLastInputIdle))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Ok(Box::new(MacEventSourceIdle))
+    }
+}
+
+/// Poll the idle time for as long as the app runs. A provider that keeps failing, e.g. after
+/// the X server restarted, is replaced by a newly detected one.
+pub fn setup_idle_polling(sink: ActivitySink, interval: Duration) {
+    crate::log_info!("hooks", "Setting up idle-time based activity tracking...");
+    thread::spawn(move || {
+        let mut restart_delay = RESTART_DELAY;
+        loop {
+            let started = std::time::Instant::now();
+            let result = detect_idle_provider()
+                .and_then(|provider| IdlePollSource::new(provider, interval).run(sink.clone()));
+            match result {
+                Ok(()) => return,
+                Err(e) => crate::log_error!(
+                    "hooks",
+                    "Idle-time tracking stopped, restarting in {:?}: {}",
+                    restart_delay, e
+                ),
+            }
+            // Back off while it keeps failing right away
+            if started.elapsed() > MAX_RESTART_DELAY {
+                restart_delay = RESTART_DELAY;
+            }
+            thread::sleep(restart_delay);
+            restart_delay = (restart_delay * 2).min(MAX_RESTART_DELAY);
+        }
+    });
+}
+
+/// Idle time from the X11 MIT-SCREEN-SAVER extension
+#[cfg(target_os = "linux")]
+pub struct XScreenSaverIdle {
+    conn: x11rb::rust_connection::RustConnection,
+    root: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl XScreenSaverIdle {
+    pub fn connect() -> Result<Self, String> {
+        use x11rb::connection::Connection;
+
+        let (conn, screen_num) = x11rb::connect(None)
+            .map_err(|e| format!("Failed to connect to X server: {}", e))?;
+        let root = conn.setup().roots[screen_num].root;
+        let mut provider = Self { conn, root };
+        provider.idle_seconds()?;
+        Ok(provider)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl IdleTimeProvider for XScreenSaverIdle {
+    fn name(&self) -> &'static str {
+        "XScreenSaver"
+    }
+
+    fn idle_seconds(&mut self) -> Result<u64, String> {
+        use x11rb::protocol::screensaver::ConnectionExt as _;
+
+        let info = self.conn.screensaver_query_info(self.root)
+            .map_err(|e| format!("XScreenSaver query failed: {}", e))?
+            .reply()
+            .map_err(|e| format!("XScreenSaver query failed: {}", e))?;
+        Ok(info.ms_since_user_input as u64 / 1000)
+    }
+}
+
+/// Idle time from systemd-logind's IdleHint/IdleSinceHint session properties.
+/// The desktop environment only sets the hint after its own idle delay, so this is coarser
+/// than XScreenSaver but works on Wayland.
+#[cfg(target_os = "linux")]
+pub struct LogindIdle {
+    session: String,
+}
+
+#[cfg(target_os = "linux")]
+impl LogindIdle {
+    pub fn new() -> Self {
+        Self {
+            session: std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Default for LogindIdle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl IdleTimeProvider for LogindIdle {
+    fn name(&self) -> &'static str {
+        "logind IdleHint"
+    }
+
+    fn idle_seconds(&mut self) -> Result<u64, String> {
+        let output = std::process::Command::new("loginctl")
+            .args(["show-session", &self.session, "-p", "IdleHint", "-p", "IdleSinceHint"])
+            .output()
+            .map_err(|e| format!("Failed to run loginctl: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "loginctl failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        parse_logind_idle(&String::from_utf8_lossy(&output.stdout), get_current_time())
+    }
+}
+
+/// Converts `loginctl show-session` output into idle seconds relative to `now`
+#[cfg(target_os = "linux")]
+fn parse_logind_idle(output: &str, now: u64) -> Result<u64, String> {
+    let mut idle_hint = None;
+    let mut idle_since_usec = None;
+
+    for line in output.lines() {
+        match line.trim().split_once('=') {
+            Some(("IdleHint", value)) => idle_hint = Some(value == "yes"),
+            Some(("IdleSinceHint", value)) => idle_since_usec = value.parse::<u64>().ok(),
+            _ => {}
+        }
+    }
+
+    match (idle_hint, idle_since_usec) {
+        (Some(false), _) => Ok(0),
+        (Some(true), Some(since)) => Ok(now.saturating_sub(since / 1_000_000)),
+        _ => Err("loginctl did not report IdleHint/IdleSinceHint".to_string()),
+    }
+}
+
+/// Idle time from `GetLastInputInfo`
+#[cfg(target_os = "windows")]
+pub struct WindowsLastInputIdle;
+
+#[cfg(target_os = "windows")]
+impl IdleTimeProvider for WindowsLastInputIdle {
+    fn name(&self) -> &'static str {
+        "GetLastInputInfo"
+    }
+
+    fn idle_seconds(&mut self) -> Result<u64, String> {
+        use winapi::um::sysinfoapi::GetTickCount;
+        use winapi::um::winuser::{GetLastInputInfo, LASTINPUTINFO};
+
+        unsafe {
+            let mut info = LASTINPUTINFO {
+                cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+                dwTime: 0,
+            };
+            if GetLastInputInfo(&mut info) == 0 {
+                return Err("GetLastInputInfo failed".to_string());
+            }
+            // Both values are 32-bit tick counts, so wrapping_sub handles the 49-day rollover
+            Ok(GetTickCount().wrapping_sub(info.dwTime) as u64 / 1000)
+        }
+    }
+}
+
+/// Idle time from `CGEventSourceSecondsSinceLastEventType`
+#[cfg(target_os = "macos")]
+pub struct MacEventSourceIdle;
+
+#[cfg(target_os = "macos")]
+impl IdleTimeProvider for MacEventSourceIdle {
+    fn name(&self) -> &'static str {
+        "CGEventSource"
+    }
+
+    fn idle_seconds(&mut self) -> Result<u64, String> {
+        const COMBINED_SESSION_STATE: i32 = 0;
+        const ANY_INPUT_EVENT_TYPE: u32 = !0;
+
+        #[link(name = "CoreGraphics", kind = "framework")]
+        extern "C" {
+            fn CGEventSourceSecondsSinceLastEventType(state_id: i32, event_type: u32) -> f64;
+        }
+
+        let seconds = unsafe {
+            CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT_TYPE)
+        };
+        Ok(seconds.max(0.0) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Replays idle times and errors, then keeps failing
+    struct ScriptedIdle(Vec<Result<u64, String>>);
+
+    impl IdleTimeProvider for ScriptedIdle {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn idle_seconds(&mut self) -> Result<u64, String> {
+            if self.0.is_empty() {
+                return Err("gone".to_string());
+            }
+            self.0.remove(0)
+        }
+    }
+
+    #[test]
+    fn test_poll_errors_are_skipped_until_they_persist() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&received);
+        let sink = ActivitySink::new(move |time| captured.lock().unwrap().push(time));
+
+        let provider = ScriptedIdle(vec![Ok(1_000), Err("busy".to_string()), Ok(0)]);
+        let mut source = IdlePollSource::new(Box::new(provider), Duration::ZERO);
+        let error = source.run(sink).unwrap_err();
+        assert!(error.contains("5 times in a row"));
+        // The poll after the failed one is still reported
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_logind_idle() {
+        assert_eq!(parse_logind_idle("IdleHint=no\nIdleSinceHint=0\n", 1_000), Ok(0));
+        assert_eq!(parse_logind_idle("IdleHint=yes\nIdleSinceHint=400000000\n", 1_000), Ok(600));
+        assert!(parse_logind_idle("", 1_000).is_err());
+    }
+}
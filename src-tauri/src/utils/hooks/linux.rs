@@ -0,0 +1,292 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::os::raw::c_long;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{self, ConnectionExt as _};
+use x11rb::protocol::Event;
+
+use crate::activity_monitor::get_current_time;
+use super::{run_with_retries, ActivitySink, InputSource};
+
+// struct input_event { struct timeval time; __u16 type; __u16 code; __s32 value; }
+const INPUT_EVENT_SIZE: usize = 2 * std::mem::size_of::<c_long>() + 8;
+
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+
+const DEVICE_RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Keyboard and mouse activity read directly from evdev devices under /dev/input.
+/// Requires read access to the device nodes (usually membership of the `input` group).
+pub struct EvdevInputSource {
+    input_dir: PathBuf,
+}
+
+impl EvdevInputSource {
+    pub fn new() -> Self {
+        Self {
+            input_dir: PathBuf::from("/dev/input"),
+        }
+    }
+
+    /// Event devices that report keys or relative motion, i.e. keyboards, mice and touchpads.
+    /// Sensors that only report absolute axes (accelerometers etc.) are skipped.
+    fn discover_devices(&self) -> Vec<PathBuf> {
+        let mut devices = Vec::new();
+        let entries = match fs::read_dir(&self.input_dir) {
+            Ok(entries) => entries,
+            Err(_) => return devices,
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with("event") {
+                continue;
+            }
+
+            let caps_path = Path::new("/sys/class/input").join(&name).join("device/capabilities/ev");
+            let is_input_device = fs::read_to_string(&caps_path)
+                .map(|caps| reports_user_input(parse_capability_bits(&caps)))
+                .unwrap_or(true); // Without sysfs we cannot tell, so try the device anyway
+
+            if is_input_device {
+                devices.push(entry.path());
+            }
+        }
+
+        devices.sort();
+        devices
+    }
+}
+
+impl Default for EvdevInputSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for EvdevInputSource {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String> {
+        let watched: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+        let mut first_scan = true;
+
+        loop {
+            let mut opened = 0;
+            let mut denied = 0;
+
+            for device in self.discover_devices() {
+                if watched.lock().unwrap().contains(&device) {
+                    continue;
+                }
+
+                match fs::File::open(&device) {
+                    Ok(file) => {
+                        opened += 1;
+                        watched.lock().unwrap().insert(device.clone());
+                        let device_sink = sink.clone();
+                        let device_watched = Arc::clone(&watched);
+                        thread::spawn(move || {
+                            read_device_events(file, &device, &device_sink);
+                            device_watched.lock().unwrap().remove(&device);
+                        });
+                    }
+                    Err(e) => {
+                        if e.kind() == std::io::ErrorKind::PermissionDenied {
+                            denied += 1;
+                        }
+                    }
+                }
+            }
+
+            if first_scan {
+                if opened == 0 {
+                    return Err(if denied > 0 {
+                        format!("Permission denied on {} input devices - add the user to the 'input' group", denied)
+                    } else {
+                        "No readable keyboard or mouse devices found under /dev/input".to_string()
+                    });
+                }
+                crate::log_info!("hooks", "Watching {} evdev input devices", opened);
+                first_scan = false;
+            } else if opened > 0 {
+                crate::log_info!("hooks", "Watching {} newly attached evdev input devices", opened);
+            }
+
+            thread::sleep(DEVICE_RESCAN_INTERVAL);
+        }
+    }
+}
+
+fn read_device_events(mut file: fs::File, device: &Path, sink: &ActivitySink) {
+    let mut buffer = [0u8; INPUT_EVENT_SIZE * 64];
+    let mut last_emitted = 0;
+
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                crate::log_warning!("hooks", "Stopped reading {:?}: {}", device, e);
+                break;
+            }
+        };
+
+        let has_activity = buffer[..read]
+            .chunks_exact(INPUT_EVENT_SIZE)
+            .any(|raw| parse_input_event(raw).is_some_and(|(ev_type, value)| is_user_activity(ev_type, value)));
+
+        // Mouse motion produces hundreds of events per second; one per second is enough
+        if has_activity {
+            let current_time = get_current_time();
+            if current_time != last_emitted {
+                last_emitted = current_time;
+                sink.emit_at(current_time);
+            }
+        }
+    }
+}
+
+/// Returns the (type, value) pair of a raw `input_event`
+fn parse_input_event(raw: &[u8]) -> Option<(u16, i32)> {
+    if raw.len() != INPUT_EVENT_SIZE {
+        return None;
+    }
+    let offset = INPUT_EVENT_SIZE - 8;
+    let ev_type = u16::from_ne_bytes(raw[offset..offset + 2].try_into().ok()?);
+    let value = i32::from_ne_bytes(raw[offset + 4..offset + 8].try_into().ok()?);
+    Some((ev_type, value))
+}
+
+fn is_user_activity(ev_type: u16, value: i32) -> bool {
+    match ev_type {
+        EV_KEY => value != 0, // press or autorepeat, not release
+        EV_REL | EV_ABS => true,
+        _ => false,
+    }
+}
+
+/// Parses the hex bitmask from /sys/class/input/eventN/device/capabilities/ev
+fn parse_capability_bits(caps: &str) -> u64 {
+    caps.split_whitespace()
+        .last()
+        .and_then(|word| u64::from_str_radix(word, 16).ok())
+        .unwrap_or(0)
+}
+
+fn reports_user_input(bits: u64) -> bool {
+    bits & (1 << EV_KEY) != 0 || bits & (1 << EV_REL) != 0
+}
+
+/// Keyboard and mouse activity via XInput2 raw events on the X11 root window.
+/// Used when evdev devices are not readable; does not work under pure Wayland sessions.
+pub struct X11InputSource;
+
+impl InputSource for X11InputSource {
+    fn name(&self) -> &'static str {
+        "X11 XInput2"
+    }
+
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String> {
+        let (conn, screen_num) = x11rb::connect(None)
+            .map_err(|e| format!("Failed to connect to X server: {}", e))?;
+        let root = conn.setup().roots[screen_num].root;
+
+        conn.xinput_xi_query_version(2, 0)
+            .map_err(|e| format!("XInput2 query failed: {}", e))?
+            .reply()
+            .map_err(|e| format!("XInput2 is not available: {}", e))?;
+
+        let mask = xinput::XIEventMask::RAW_KEY_PRESS
+            | xinput::XIEventMask::RAW_BUTTON_PRESS
+            | xinput::XIEventMask::RAW_MOTION;
+        conn.xinput_xi_select_events(
+            root,
+            &[xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![mask],
+            }]
+        )
+        .map_err(|e| format!("Failed to select XInput2 events: {}", e))?
+        .check()
+        .map_err(|e| format!("Failed to select XInput2 events: {}", e))?;
+
+        crate::log_info!("hooks", "Listening for XInput2 raw events on the root window");
+
+        let mut last_emitted = 0;
+        loop {
+            let event = conn.wait_for_event()
+                .map_err(|e| format!("X11 connection lost: {}", e))?;
+
+            match event {
+                Event::XinputRawKeyPress(_)
+                | Event::XinputRawButtonPress(_)
+                | Event::XinputRawMotion(_) => {
+                    let current_time = get_current_time();
+                    if current_time != last_emitted {
+                        last_emitted = current_time;
+                        sink.emit_at(current_time);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn setup_hooks(sink: ActivitySink) {
+    crate::log_info!("hooks", "Setting up Linux input monitoring...");
+    thread::spawn(move || {
+        let mut evdev = EvdevInputSource::new();
+        if run_with_retries(&mut evdev, &sink, 3, Duration::from_secs(2)) {
+            return;
+        }
+
+        crate::log_warning!("hooks", "evdev input unavailable, falling back to X11 XInput2");
+        let mut x11 = X11InputSource;
+        run_with_retries(&mut x11, &sink, 3, Duration::from_secs(3));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_event(ev_type: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut raw = vec![0u8; INPUT_EVENT_SIZE - 8];
+        raw.extend_from_slice(&ev_type.to_ne_bytes());
+        raw.extend_from_slice(&code.to_ne_bytes());
+        raw.extend_from_slice(&value.to_ne_bytes());
+        raw
+    }
+
+    #[test]
+    fn test_input_event_classification() {
+        let key_press = parse_input_event(&raw_event(EV_KEY, 30, 1)).unwrap();
+        let key_release = parse_input_event(&raw_event(EV_KEY, 30, 0)).unwrap();
+        let mouse_move = parse_input_event(&raw_event(EV_REL, 0, -3)).unwrap();
+        let syn_report = parse_input_event(&raw_event(0x00, 0, 0)).unwrap();
+
+        assert!(is_user_activity(key_press.0, key_press.1));
+        assert!(!is_user_activity(key_release.0, key_release.1));
+        assert!(is_user_activity(mouse_move.0, mouse_move.1));
+        assert!(!is_user_activity(syn_report.0, syn_report.1));
+    }
+
+    #[test]
+    fn test_capability_filter() {
+        assert!(reports_user_input(parse_capability_bits("120013\n"))); // keyboard
+        assert!(reports_user_input(parse_capability_bits("17"))); // mouse
+        assert!(!reports_user_input(parse_capability_bits("9"))); // accelerometer: SYN + ABS
+    }
+}
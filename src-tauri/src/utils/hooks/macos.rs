@@ -0,0 +1,121 @@
+use core_foundation::base::{kCFAllocatorDefault, TCFType};
+use core_foundation::runloop::{CFRunLoop, CFRunLoopSource};
+use core_foundation::mach_port::{CFMachPortRef, CFMachPortCreateRunLoopSource};
+use core_graphics::event::{
+    CGEvent,
+    CGEventTap,
+    CGEventTapProxy,
+    CGEventType,
+    CGEventTapLocation,
+    CGEventTapPlacement,
+    CGEventTapOptions,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::{run_with_retries, ActivitySink, InputSource};
+
+static EVENT_TAP_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Keyboard and mouse activity via a `CGEventTap` on the HID event stream
+pub struct MacEventTapSource;
+
+impl InputSource for MacEventTapSource {
+    fn name(&self) -> &'static str {
+        "macOS event tap"
+    }
+
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String> {
+        setup_macos_hooks_inner(sink)
+    }
+}
+
+pub fn setup_hooks(sink: ActivitySink) {
+    crate::log_info!("hooks", "Setting up macOS event tap...");
+    let tap_sink = sink.clone();
+    std::thread::spawn(move || {
+        let mut source = MacEventTapSource;
+        EVENT_TAP_RUNNING.store(true, Ordering::SeqCst);
+        run_with_retries(&mut source, &tap_sink, 3, Duration::from_secs(3));
+        EVENT_TAP_RUNNING.store(false, Ordering::SeqCst);
+    });
+
+    // Start a monitoring thread to restart the event tap if it stops
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(Duration::from_secs(30)); // Check every 30 seconds
+
+            if !EVENT_TAP_RUNNING.load(Ordering::SeqCst) {
+                crate::log_warning!("hooks", "Event tap not running, attempting restart...");
+                setup_hooks(sink); // Recursive call to restart
+                break; // Exit this monitoring thread as a new one will be started
+            }
+        }
+    });
+}
+
+fn setup_macos_hooks_inner(sink: ActivitySink) -> Result<(), String> {
+    unsafe {
+        // Create the event tap
+        let event_tap = CGEventTap::new(
+            CGEventTapLocation::HID,
+            CGEventTapPlacement::HeadInsertEventTap,
+            CGEventTapOptions::Default,
+            vec![
+                CGEventType::KeyDown,
+                CGEventType::MouseMoved,
+                CGEventType::LeftMouseDown,
+                CGEventType::RightMouseDown,
+                CGEventType::OtherMouseDown,
+                CGEventType::ScrollWheel
+            ],
+            move |proxy, event_type, event| event_callback(&sink, proxy, event_type, event)
+        ).map_err(|e| format!("Failed to create event tap: {:?}", e))?;
+
+        // Check if the event tap is enabled
+        event_tap.enable();
+
+        // Get the raw mach port pointer using as_concrete_TypeRef
+        let mach_port: CFMachPortRef = event_tap.mach_port.as_concrete_TypeRef();
+
+        // Create a run loop source from the mach port
+        let raw_source = CFMachPortCreateRunLoopSource(kCFAllocatorDefault, mach_port, 0);
+        if raw_source.is_null() {
+            return Err("Failed to create run loop source".to_string());
+        }
+        let source = CFRunLoopSource::wrap_under_create_rule(raw_source);
+
+        // Add the source to the current run loop and run it
+        let current_runloop = CFRunLoop::get_current();
+        current_runloop.add_source(&source, core_foundation::runloop::kCFRunLoopCommonModes);
+
+        crate::log_info!("hooks", "Starting macOS event tap run loop...");
+        CFRunLoop::run_current();
+
+        crate::log_warning!("hooks", "macOS event tap run loop stopped");
+        Ok(())
+    }
+}
+
+fn event_callback(
+    sink: &ActivitySink,
+    _proxy: CGEventTapProxy,
+    event_type: CGEventType,
+    event: &CGEvent
+) -> Option<CGEvent> {
+    // Reset the running flag to indicate the event tap is active
+    EVENT_TAP_RUNNING.store(true, Ordering::SeqCst);
+
+    match event_type {
+        CGEventType::KeyDown
+        | CGEventType::MouseMoved
+        | CGEventType::LeftMouseDown
+        | CGEventType::RightMouseDown
+        | CGEventType::OtherMouseDown
+        | CGEventType::ScrollWheel => {
+            sink.emit_now();
+        }
+        _ => {}
+    }
+    Some(event.clone())
+}
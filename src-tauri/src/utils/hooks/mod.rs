@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::activity_monitor::{get_current_time, TimeUpdateMessage, EVENT_QUEUE_SENDER};
+use crate::activity_monitor::settings::{load_activity_settings, TrackingMode};
+use crate::utils::health_monitor::report_activity;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "linux")]
+mod linux;
+pub mod idle;
+pub mod synthetic;
+
+#[cfg(target_os = "windows")]
+pub use windows::WindowsHookSource;
+#[cfg(target_os = "macos")]
+pub use macos::MacEventTapSource;
+#[cfg(target_os = "linux")]
+pub use linux::{EvdevInputSource, X11InputSource};
+pub use synthetic::SyntheticInputSource;
+
+/// Receives one call per user input event seen by an `InputSource`.
+#[derive(Clone)]
+pub struct ActivitySink {
+    emit: Arc<dyn Fn(TimeUpdateMessage) + Send + Sync>,
+}
+
+impl ActivitySink {
+    pub fn new<F>(emit: F) -> Self
+    where
+        F: Fn(TimeUpdateMessage) + Send + Sync + 'static,
+    {
+        Self { emit: Arc::new(emit) }
+    }
+
+    /// Sink that feeds the activity monitor's event queue and the health monitor
+    pub fn event_queue() -> Self {
+        Self::new(|current_time| {
+            report_activity(); // Report to health monitor
+            if let Err(e) = EVENT_QUEUE_SENDER.lock().unwrap().send(current_time) {
+                crate::log_error!("hooks", "Failed to send event to queue: {}", e);
+            }
+        })
+    }
+
+    pub fn emit_at(&self, time: TimeUpdateMessage) {
+        (self.emit)(time);
+    }
+
+    pub fn emit_now(&self) {
+        self.emit_at(get_current_time());
+    }
+}
+
+/// A platform mechanism that observes keyboard and mouse input.
+pub trait InputSource: Send {
+    fn name(&self) -> &'static str;
+
+    /// Blocks while delivering input events to `sink`.
+    /// Returns `Ok` when the source stops normally and `Err` when it could not start or failed.
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String>;
+}
+
+/// Run `source` on the current thread, retrying up to `max_retries` times on failure.
+/// Returns `true` if the source eventually ran to completion.
+pub fn run_with_retries(
+    source: &mut dyn InputSource,
+    sink: &ActivitySink,
+    max_retries: u32,
+    retry_delay: std::time::Duration
+) -> bool {
+    let mut retry_count = 0;
+
+    while retry_count < max_retries {
+        match source.run(sink.clone()) {
+            Ok(_) => {
+                crate::log_info!("hooks", "{} input source stopped normally", source.name());
+                return true;
+            }
+            Err(e) => {
+                retry_count += 1;
+                crate::log_error!("hooks", "{} input source failed (attempt {}): {}", source.name(), retry_count, e);
+
+                if retry_count < max_retries {
+                    std::thread::sleep(retry_delay);
+                }
+            }
+        }
+    }
+
+    crate::log_error!("hooks", "Failed to run {} input source after {} attempts", source.name(), max_retries);
+    false
+}
+
+/// Start activity detection using the tracking mode configured for this machine
+pub fn setup_hooks() {
+    let settings = load_activity_settings();
+    let sink = ActivitySink::event_queue();
+
+    match settings.tracking_mode {
+        TrackingMode::Hooks => setup_input_hooks(sink),
+        TrackingMode::IdlePoll => {
+            let interval = std::time::Duration::from_secs(settings.idle_poll_interval_secs.max(1));
+            idle::setup_idle_polling(sink, interval);
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn setup_input_hooks(sink: ActivitySink) {
+    windows::setup_hooks(sink);
+}
+
+#[cfg(target_os = "macos")]
+fn setup_input_hooks(sink: ActivitySink) {
+    macos::setup_hooks(sink);
+}
+
+#[cfg(target_os = "linux")]
+fn setup_input_hooks(sink: ActivitySink) {
+    linux::setup_hooks(sink);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_synthetic_source_feeds_sink() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&received);
+        let sink = ActivitySink::new(move |time| captured.lock().unwrap().push(time));
+
+        let mut source = SyntheticInputSource::new(vec![100, 101, 105]);
+        assert!(run_with_retries(&mut source, &sink, 1, std::time::Duration::ZERO));
+        assert_eq!(*received.lock().unwrap(), vec![100, 101, 105]);
+    }
+
+    #[test]
+    fn test_failing_source_is_retried() {
+        let sink = ActivitySink::new(|_| {});
+        let mut source = SyntheticInputSource::failing("device unavailable");
+        assert!(!run_with_retries(&mut source, &sink, 3, std::time::Duration::ZERO));
+        assert_eq!(source.attempts(), 3);
+    }
+}
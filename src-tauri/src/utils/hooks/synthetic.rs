@@ -0,0 +1,51 @@
+use crate::activity_monitor::TimeUpdateMessage;
+use super::{ActivitySink, InputSource};
+
+/// Input source that replays a fixed list of timestamps, used to drive the
+/// activity pipeline without real keyboard or mouse hooks.
+pub struct SyntheticInputSource {
+    events: Vec<TimeUpdateMessage>,
+    failure: Option<String>,
+    attempts: u32,
+}
+
+impl SyntheticInputSource {
+    pub fn new(events: Vec<TimeUpdateMessage>) -> Self {
+        Self {
+            events,
+            failure: None,
+            attempts: 0,
+        }
+    }
+
+    /// A source that fails every time it is started
+    pub fn failing(reason: &str) -> Self {
+        Self {
+            events: Vec::new(),
+            failure: Some(reason.to_string()),
+            attempts: 0,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+impl InputSource for SyntheticInputSource {
+    fn name(&self) -> &'static str {
+        "synthetic"
+    }
+
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String> {
+        self.attempts += 1;
+        if let Some(reason) = &self.failure {
+            return Err(reason.clone());
+        }
+
+        for time in self.events.drain(..) {
+            sink.emit_at(time);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use once_cell::sync::Lazy;
+use std::ptr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use winapi::um::winuser::{
+    CallNextHookEx,
+    GetMessageA,
+    SetWindowsHookExA,
+    UnhookWindowsHookEx,
+    MSG,
+    WH_KEYBOARD_LL,
+    WH_MOUSE_LL,
+    WM_KEYDOWN,
+};
+
+use crate::utils::app_state::send_message;
+use super::{run_with_retries, ActivitySink, InputSource};
+
+// Low-level hook callbacks carry no user data, so the active sink lives here
+static HOOK_SINK: Lazy<Mutex<Option<ActivitySink>>> = Lazy::new(|| Mutex::new(None));
+
+/// Keyboard and mouse activity via `SetWindowsHookExA` low-level hooks
+pub struct WindowsHookSource;
+
+impl InputSource for WindowsHookSource {
+    fn name(&self) -> &'static str {
+        "Windows hooks"
+    }
+
+    fn run(&mut self, sink: ActivitySink) -> Result<(), String> {
+        *HOOK_SINK.lock().unwrap() = Some(sink);
+        let result = setup_windows_hooks_inner();
+        *HOOK_SINK.lock().unwrap() = None;
+        result
+    }
+}
+
+pub fn setup_hooks(sink: ActivitySink) {
+    crate::log_info!("hooks", "Setting up Windows hooks...");
+    thread::spawn(move || {
+        let mut source = WindowsHookSource;
+        if !run_with_retries(&mut source, &sink, 5, Duration::from_secs(2)) {
+            send_message("Critical: Failed to setup Windows hooks after multiple attempts".to_string());
+        }
+    });
+}
+
+fn setup_windows_hooks_inner() -> Result<(), String> {
+    unsafe {
+        // Set up keyboard hook
+        let kb_hook_id = SetWindowsHookExA(
+            WH_KEYBOARD_LL,
+            Some(keyboard_hook_callback),
+            ptr::null_mut(),
+            0
+        );
+        if kb_hook_id.is_null() {
+            return Err("Failed to set keyboard hook".to_string());
+        }
+
+        // Set up mouse hook
+        let mouse_hook_id = SetWindowsHookExA(
+            WH_MOUSE_LL,
+            Some(mouse_hook_callback),
+            ptr::null_mut(),
+            0
+        );
+        if mouse_hook_id.is_null() {
+            UnhookWindowsHookEx(kb_hook_id);
+            return Err("Failed to set mouse hook".to_string());
+        }
+
+        send_message("Successfully set both keyboard and mouse hooks".to_string());
+        crate::log_info!("hooks", "Successfully set both keyboard and mouse hooks");
+
+        // Message loop with error handling
+        let mut msg: MSG = std::mem::zeroed();
+        loop {
+            let result = GetMessageA(&mut msg, ptr::null_mut(), 0, 0);
+            if result == -1 {
+                // Error occurred
+                UnhookWindowsHookEx(kb_hook_id);
+                UnhookWindowsHookEx(mouse_hook_id);
+                return Err("Message loop error".to_string());
+            } else if result == 0 {
+                // WM_QUIT received
+                break;
+            }
+            // Continue processing messages
+        }
+
+        // Cleanup
+        UnhookWindowsHookEx(kb_hook_id);
+        UnhookWindowsHookEx(mouse_hook_id);
+        Ok(())
+    }
+}
+
+fn activity_handler() {
+    if let Some(sink) = HOOK_SINK.lock().unwrap().as_ref() {
+        sink.emit_now();
+    }
+}
+
+unsafe extern "system" fn keyboard_hook_callback(
+    code: i32,
+    w_param: usize,
+    l_param: isize
+) -> isize {
+    if code >= 0 && w_param == (WM_KEYDOWN as usize) {
+        activity_handler();
+    }
+    CallNextHookEx(ptr::null_mut(), code, w_param, l_param)
+}
+
+unsafe extern "system" fn mouse_hook_callback(code: i32, w_param: usize, l_param: isize) -> isize {
+    if code >= 0 {
+        activity_handler();
+    }
+    CallNextHookEx(ptr::null_mut(), code, w_param, l_param)
+}
@@ -0,0 +1,766 @@
+use once_cell::sync::Lazy;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::activity_monitor::core::get_current_time;
+use crate::activity_monitor::file_operations::get_platform_directories;
+use crate::activity_monitor::log_writer::with_activity_log_closed;
+use crate::activity_monitor::records::{reencrypt_log_content, LEGACY_KEY_ID};
+use crate::utils::encryption::{derive_key_from_passphrase, generate_key, random_bytes, unwrap_key, wrap_key, LEGACY_KEY};
+use crate::utils::file_utils::{atomic_write_owner_only, atomic_write_with_backup};
+
+const KEY_STORE_FILE_NAME: &str = "activity_keys.json";
+// Plain PKCS#8 signing key of older versions, moved into the key store on first use
+const LEGACY_SIGNING_KEY_FILE_NAME: &str = "report_signing_key.pk8";
+// Sealing id of the report signing key, apart from the log key ids
+const SIGNING_KEY_ID: u32 = u32::MAX;
+#[cfg(not(test))]
+const PBKDF2_ITERATIONS: u32 = 600_000;
+// Keeps the passphrase tests fast in debug builds
+#[cfg(test)]
+const PBKDF2_ITERATIONS: u32 = 1_000;
+
+/// Decrypted activity log keys by key id. The legacy built-in key (id 0) is always present.
+#[derive(Clone)]
+pub struct KeyRing {
+    keys: BTreeMap<u32, [u8; 32]>,
+    current_id: u32,
+}
+
+impl KeyRing {
+    /// Only the legacy built-in key, which is then also used for new logs
+    pub fn legacy() -> Self {
+        Self {
+            keys: BTreeMap::from([(LEGACY_KEY_ID, LEGACY_KEY)]),
+            current_id: LEGACY_KEY_ID,
+        }
+    }
+
+    /// The legacy key plus `key`, which is used for new logs
+    pub fn with_key(key_id: u32, key: [u8; 32]) -> Self {
+        let mut keyring = Self::legacy();
+        keyring.keys.insert(key_id, key);
+        keyring.current_id = key_id;
+        keyring
+    }
+
+    pub fn key(&self, key_id: u32) -> Option<&[u8; 32]> {
+        self.keys.get(&key_id)
+    }
+
+    /// Key id new logs are encrypted with
+    pub fn current_id(&self) -> u32 {
+        self.current_id
+    }
+}
+
+/// Where the per-installation keys are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyProtection {
+    /// In the key store file, readable only by the user; on Windows also encrypted for the user
+    File,
+    /// Wrapped with a key derived from a user passphrase; logs cannot be written until it is entered
+    Passphrase,
+    /// In the desktop keyring through the Secret Service API (Linux)
+    SecretService,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "protection", rename_all = "snake_case")]
+enum SealedKey {
+    File { key: String },
+    /// Encrypted for the Windows user with the Data Protection API
+    UserProtected { protected: String },
+    Passphrase { nonce: String, wrapped: String },
+    SecretService,
+}
+
+impl SealedKey {
+    fn protection(&self) -> KeyProtection {
+        match self {
+            SealedKey::File { .. } | SealedKey::UserProtected { .. } => KeyProtection::File,
+            SealedKey::Passphrase { .. } => KeyProtection::Passphrase,
+            SealedKey::SecretService => KeyProtection::SecretService,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredKey {
+    id: u32,
+    created_at: u64,
+    #[serde(flatten)]
+    sealed: SealedKey,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PassphraseKdf {
+    salt: String,
+    iterations: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyStoreFile {
+    current_key_id: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    passphrase_kdf: Option<PassphraseKdf>,
+    keys: Vec<StoredKey>,
+    /// Seed of the Ed25519 report signing key, sealed like the log keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_key: Option<StoredKey>,
+}
+
+/// Lock state and key ids, for the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyStatus {
+    pub locked: bool,
+    pub protection: KeyProtection,
+    pub current_key_id: u32,
+    pub key_ids: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotationReport {
+    pub previous_key_id: u32,
+    pub new_key_id: u32,
+    pub files_reencrypted: usize,
+    /// Files left on their previous key; the previous keys stay in the store so these remain readable
+    pub failed_files: Vec<String>,
+}
+
+/// The key store file of one installation together with its unlocked keys
+pub struct KeyStore {
+    path: PathBuf,
+    file: KeyStoreFile,
+    keyring: Option<KeyRing>,
+    // Derived from the passphrase on unlock so new keys can be wrapped without asking again
+    wrapping_key: Option<[u8; 32]>,
+    signing_seed: Option<[u8; 32]>,
+    // The store next to this machine's logs, rather than one opened from a copy
+    installation: bool,
+}
+
+impl KeyStore {
+    /// Open the key store in `dir`, generating the installation's first key if there is none
+    pub fn open_or_create(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(KEY_STORE_FILE_NAME);
+        if path.exists() {
+            return Self::open(dir);
+        }
+
+        fs::create_dir_all(dir)?;
+        let key = generate_key().map_err(|_| io::Error::other("Key generation failed"))?;
+        let first_id = LEGACY_KEY_ID + 1;
+        let store = Self {
+            path,
+            file: KeyStoreFile {
+                current_key_id: first_id,
+                passphrase_kdf: None,
+                keys: vec![StoredKey {
+                    id: first_id,
+                    created_at: get_current_time(),
+                    sealed: seal_in_file(&key).map_err(io::Error::other)?,
+                }],
+                signing_key: None,
+            },
+            keyring: Some(KeyRing::with_key(first_id, key)),
+            wrapping_key: None,
+            signing_seed: None,
+            installation: false,
+        };
+        store.save()?;
+        crate::log_info!("key_manager", "Generated activity log key {}", first_id);
+        Ok(store)
+    }
+
+    /// Open the existing key store in `dir`. Keys that need no passphrase are unlocked.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(KEY_STORE_FILE_NAME);
+        let file: KeyStoreFile = serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut store = Self { path, file, keyring: None, wrapping_key: None, signing_seed: None, installation: false };
+        if store.protection() != KeyProtection::Passphrase {
+            if let Err(e) = store.unlock(None) {
+                crate::log_error!("key_manager", "Failed to unlock activity log keys: {}", e);
+            }
+        }
+        Ok(store)
+    }
+
+    pub fn keyring(&self) -> Option<&KeyRing> {
+        self.keyring.as_ref()
+    }
+
+    /// Protection of the current key
+    pub fn protection(&self) -> KeyProtection {
+        self.file
+            .keys
+            .iter()
+            .find(|stored| stored.id == self.file.current_key_id)
+            .map_or(KeyProtection::File, |stored| stored.sealed.protection())
+    }
+
+    pub fn status(&self) -> KeyStatus {
+        KeyStatus {
+            locked: self.keyring.is_none(),
+            protection: self.protection(),
+            current_key_id: self.file.current_key_id,
+            key_ids: self.file.keys.iter().map(|stored| stored.id).collect(),
+        }
+    }
+
+    /// Decrypt every stored key. `passphrase` is needed when any key is passphrase-protected.
+    pub fn unlock(&mut self, passphrase: Option<&str>) -> Result<(), String> {
+        let wrapping_key = match (&self.file.passphrase_kdf, passphrase) {
+            (Some(kdf), Some(passphrase)) => {
+                let salt = hex::decode(&kdf.salt).map_err(|e| format!("Invalid key store salt: {}", e))?;
+                Some(derive_key_from_passphrase(passphrase, &salt, kdf.iterations))
+            }
+            _ => None,
+        };
+
+        let mut keyring = KeyRing::legacy();
+        for stored in &self.file.keys {
+            keyring.keys.insert(stored.id, unseal(stored, wrapping_key.as_ref())?);
+        }
+        keyring.current_id = self.file.current_key_id;
+        let signing_seed = self
+            .file
+            .signing_key
+            .as_ref()
+            .map(|stored| unseal(stored, wrapping_key.as_ref()))
+            .transpose()?;
+
+        self.keyring = Some(keyring);
+        self.wrapping_key = wrapping_key;
+        self.signing_seed = signing_seed;
+        Ok(())
+    }
+
+    /// Re-seal every key with `protection`. Requires the store to be unlocked.
+    pub fn set_protection(&mut self, protection: KeyProtection, passphrase: Option<&str>) -> Result<(), String> {
+        let keyring = self.keyring.clone().ok_or("Activity log keys are locked")?;
+
+        match protection {
+            KeyProtection::Passphrase => {
+                let passphrase = passphrase.filter(|p| !p.is_empty()).ok_or("A passphrase is required")?;
+                let salt = random_bytes::<16>().map_err(|_| "Failed to generate salt".to_string())?;
+                self.wrapping_key = Some(derive_key_from_passphrase(passphrase, &salt, PBKDF2_ITERATIONS));
+                self.file.passphrase_kdf = Some(PassphraseKdf {
+                    salt: hex::encode(salt),
+                    iterations: PBKDF2_ITERATIONS,
+                });
+            }
+            _ => {
+                self.wrapping_key = None;
+                self.file.passphrase_kdf = None;
+            }
+        }
+
+        for index in 0..self.file.keys.len() {
+            let id = self.file.keys[index].id;
+            let key = keyring.key(id).copied().ok_or_else(|| format!("Key {} is not unlocked", id))?;
+            self.file.keys[index].sealed = self.seal(id, &key, protection)?;
+        }
+        if let Some(seed) = self.signing_seed {
+            let sealed = self.seal(SIGNING_KEY_ID, &seed, protection)?;
+            if let Some(stored) = self.file.signing_key.as_mut() {
+                stored.sealed = sealed;
+            }
+        }
+        self.save().map_err(|e| format!("Failed to save key store: {}", e))
+    }
+
+    /// The Ed25519 key for signing activity reports. The installation's store generates it on
+    /// first use, or takes over the plain key file of older versions; a store opened from a
+    /// copied directory never gets a key of its own.
+    pub fn signing_key(&mut self) -> Result<Ed25519KeyPair, String> {
+        if self.keyring.is_none() {
+            return Err("Activity log keys are locked".to_string());
+        }
+        let seed = match self.signing_seed {
+            Some(seed) => seed,
+            // A copy may use the key file it came with, but never stores or generates one
+            None if !self.installation => self.legacy_signing_seed()?.ok_or(
+                "This key store has no report signing key; sign reports on the installation that wrote the logs"
+            )?,
+            None => {
+                let seed = match self.legacy_signing_seed()? {
+                    Some(seed) => seed,
+                    None => generate_key().map_err(|_| "Report signing key generation failed".to_string())?,
+                };
+                let sealed = self.seal(SIGNING_KEY_ID, &seed, self.protection())?;
+                self.file.signing_key = Some(StoredKey { id: SIGNING_KEY_ID, created_at: get_current_time(), sealed });
+                self.save().map_err(|e| format!("Failed to save key store: {}", e))?;
+                self.signing_seed = Some(seed);
+                self.remove_legacy_signing_key();
+                crate::log_info!("key_manager", "Stored report signing key in the key store");
+                seed
+            }
+        };
+        Ed25519KeyPair::from_seed_unchecked(&seed).map_err(|e| format!("Invalid report signing key: {}", e))
+    }
+
+    fn legacy_signing_key_path(&self) -> Option<PathBuf> {
+        self.path.parent().map(|dir| dir.join(LEGACY_SIGNING_KEY_FILE_NAME))
+    }
+
+    /// Seed of the plain PKCS#8 signing key older versions kept next to the key store
+    fn legacy_signing_seed(&self) -> Result<Option<[u8; 32]>, String> {
+        let Some(path) = self.legacy_signing_key_path().filter(|path| path.exists()) else { return Ok(None) };
+        let pkcs8 = fs::read(&path).map_err(|e| format!("Failed to read report signing key: {}", e))?;
+        let key_pair = Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|e| format!("Invalid report signing key: {}", e))?;
+        // ring writes PKCS#8 v2 documents with the seed after a 16-byte prefix
+        let seed: [u8; 32] = pkcs8
+            .get(16..48)
+            .and_then(|seed| seed.try_into().ok())
+            .ok_or("Invalid report signing key")?;
+        Ed25519KeyPair::from_seed_and_public_key(&seed, key_pair.public_key().as_ref())
+            .map_err(|e| format!("Invalid report signing key: {}", e))?;
+        Ok(Some(seed))
+    }
+
+    fn remove_legacy_signing_key(&self) {
+        if let Some(path) = self.legacy_signing_key_path().filter(|path| path.exists()) {
+            if let Err(e) = fs::remove_file(&path) {
+                crate::log_warning!("key_manager", "Failed to remove {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// Generate a key with the current protection and store it without making it current
+    fn add_key(&mut self) -> Result<u32, String> {
+        let protection = self.protection();
+        let mut keyring = self.keyring.clone().ok_or("Activity log keys are locked")?;
+        let id = self.file.keys.iter().map(|stored| stored.id).max().unwrap_or(LEGACY_KEY_ID) + 1;
+        let key = generate_key().map_err(|_| "Key generation failed".to_string())?;
+
+        let sealed = self.seal(id, &key, protection)?;
+        self.file.keys.push(StoredKey {
+            id,
+            created_at: get_current_time(),
+            sealed,
+        });
+        self.save().map_err(|e| format!("Failed to save key store: {}", e))?;
+
+        keyring.keys.insert(id, key);
+        self.keyring = Some(keyring);
+        Ok(id)
+    }
+
+    fn set_current_key(&mut self, id: u32) -> Result<(), String> {
+        self.file.current_key_id = id;
+        if let Some(keyring) = self.keyring.as_mut() {
+            keyring.current_id = id;
+        }
+        self.save().map_err(|e| format!("Failed to save key store: {}", e))
+    }
+
+    fn seal(&self, id: u32, key: &[u8; 32], protection: KeyProtection) -> Result<SealedKey, String> {
+        Ok(match protection {
+            KeyProtection::File => seal_in_file(key)?,
+            KeyProtection::Passphrase => {
+                let wrapping_key = self.wrapping_key.as_ref().ok_or("A passphrase is required")?;
+                let (wrapped, nonce) = wrap_key(wrapping_key, key, &id.to_le_bytes())
+                    .map_err(|_| "Failed to wrap key".to_string())?;
+                SealedKey::Passphrase {
+                    nonce: hex::encode(nonce),
+                    wrapped: hex::encode(wrapped),
+                }
+            }
+            KeyProtection::SecretService => {
+                secret_service::store(id, key)?;
+                SealedKey::SecretService
+            }
+        })
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        atomic_write_owner_only(&self.path, json.as_bytes())
+    }
+
+    /// Re-seal keys that older versions kept in plain text on Windows for the current user
+    fn protect_plain_keys(&mut self) {
+        let plain = self
+            .file
+            .keys
+            .iter()
+            .chain(&self.file.signing_key)
+            .any(|stored| matches!(stored.sealed, SealedKey::File { .. }));
+        if cfg!(windows) && plain && self.keyring.is_some() && self.protection() == KeyProtection::File {
+            if let Err(e) = self.set_protection(KeyProtection::File, None) {
+                crate::log_warning!("key_manager", "Failed to protect the activity log keys: {}", e);
+            }
+        }
+    }
+
+    /// Generate a new current key and re-encrypt every daily log and backup in `dirs` with it
+    pub fn rotate(&mut self, dirs: &[&Path]) -> Result<KeyRotationReport, String> {
+        let previous_key_id = self.file.current_key_id;
+        let new_key_id = self.add_key()?;
+        let keyring = self.keyring.clone().ok_or("Activity log keys are locked")?;
+
+        let mut report = KeyRotationReport {
+            previous_key_id,
+            new_key_id,
+            files_reencrypted: 0,
+            failed_files: Vec::new(),
+        };
+
+        for path in dirs.iter().flat_map(|dir| activity_log_files(dir)) {
+            let result = fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| reencrypt_log_content(&path, &content, &keyring, new_key_id))
+                .and_then(|reencrypted| match reencrypted {
+                    Some(data) => atomic_write_with_backup(&path, &data, None)
+                        .map(|_| true)
+                        .map_err(|e| e.to_string()),
+                    None => Ok(false),
+                });
+
+            match result {
+                Ok(true) => report.files_reencrypted += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    crate::log_warning!("key_manager", "Could not re-encrypt {:?}: {}", path, e);
+                    report.failed_files.push(path.to_string_lossy().to_string());
+                }
+            }
+        }
+
+        self.set_current_key(new_key_id)?;
+        Ok(report)
+    }
+}
+
+/// Decrypt a stored key; `wrapping_key` is needed for passphrase-protected keys
+fn unseal(stored: &StoredKey, wrapping_key: Option<&[u8; 32]>) -> Result<[u8; 32], String> {
+    match &stored.sealed {
+        SealedKey::File { key } => decode_key(key),
+        SealedKey::UserProtected { protected } => {
+            let protected = hex::decode(protected).map_err(|e| format!("Invalid protected key: {}", e))?;
+            user_data_protection::unprotect(&protected)?
+                .try_into()
+                .map_err(|_| "Invalid key in key store".to_string())
+        }
+        SealedKey::Passphrase { nonce, wrapped } => {
+            let wrapping_key = wrapping_key.ok_or("A passphrase is required to unlock the activity log keys")?;
+            let nonce: [u8; 12] = hex::decode(nonce)
+                .ok()
+                .and_then(|nonce| nonce.try_into().ok())
+                .ok_or("Invalid key store nonce")?;
+            let wrapped = hex::decode(wrapped).map_err(|e| format!("Invalid wrapped key: {}", e))?;
+            unwrap_key(wrapping_key, &wrapped, nonce, &stored.id.to_le_bytes())
+                .map_err(|_| "Incorrect passphrase".to_string())
+        }
+        SealedKey::SecretService => secret_service::lookup(stored.id),
+    }
+}
+
+fn decode_key(hex_key: &str) -> Result<[u8; 32], String> {
+    hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| "Invalid key in key store".to_string())
+}
+
+/// Seal `key` for the key store file, encrypted for the current user where the OS supports it
+fn seal_in_file(key: &[u8; 32]) -> Result<SealedKey, String> {
+    if cfg!(windows) {
+        let protected = user_data_protection::protect(key)?;
+        Ok(SealedKey::UserProtected { protected: hex::encode(protected) })
+    } else {
+        Ok(SealedKey::File { key: hex::encode(key) })
+    }
+}
+
+/// Daily logs, backups and damaged copies in `dir`
+fn activity_log_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with("rs-fairsight(") && !name.ends_with(".tmp")
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+mod secret_service {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    const APPLICATION: &str = "innomonitor";
+
+    // libsecret's secret-tool talks to whichever Secret Service provider the desktop runs
+    pub fn store(key_id: u32, key: &[u8; 32]) -> Result<(), String> {
+        let label = match key_id {
+            super::SIGNING_KEY_ID => "InnoMonitor report signing key".to_string(),
+            _ => format!("InnoMonitor activity log key {}", key_id),
+        };
+        let mut child = Command::new("secret-tool")
+            .args(["store", "--label", &label])
+            .args(["application", APPLICATION, "key-id", &key_id.to_string()])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        child
+            .stdin
+            .take()
+            .ok_or("Failed to open secret-tool stdin")?
+            .write_all(hex::encode(key).as_bytes())
+            .map_err(|e| format!("Failed to pass key to secret-tool: {}", e))?;
+
+        let output = child.wait_with_output().map_err(|e| format!("secret-tool failed: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("secret-tool store failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+
+    pub fn lookup(key_id: u32) -> Result<[u8; 32], String> {
+        let output = Command::new("secret-tool")
+            .args(["lookup", "application", APPLICATION, "key-id", &key_id.to_string()])
+            .output()
+            .map_err(|e| format!("Failed to run secret-tool: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("Key {} not found in the Secret Service keyring", key_id));
+        }
+        super::decode_key(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod secret_service {
+    pub fn store(_key_id: u32, _key: &[u8; 32]) -> Result<(), String> {
+        Err("Secret Service key storage is only available on Linux".to_string())
+    }
+
+    pub fn lookup(_key_id: u32) -> Result<[u8; 32], String> {
+        Err("Secret Service key storage is only available on Linux".to_string())
+    }
+}
+
+#[cfg(windows)]
+mod user_data_protection {
+    use std::ptr;
+    use winapi::um::dpapi::{CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN};
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::wincrypt::DATA_BLOB;
+
+    // Only the same Windows user on the same machine can decrypt what DPAPI encrypted
+    pub fn protect(data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut input = DATA_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+        let mut output = DATA_BLOB { cbData: 0, pbData: ptr::null_mut() };
+        let ok = unsafe {
+            CryptProtectData(
+                &mut input,
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            )
+        };
+        take_output(ok, output).map_err(|e| format!("Failed to protect key: {}", e))
+    }
+
+    pub fn unprotect(data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut input = DATA_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+        let mut output = DATA_BLOB { cbData: 0, pbData: ptr::null_mut() };
+        let ok = unsafe {
+            CryptUnprotectData(
+                &mut input,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut output,
+            )
+        };
+        take_output(ok, output).map_err(|e| format!("Failed to unprotect key, it may belong to another user: {}", e))
+    }
+
+    fn take_output(ok: i32, output: DATA_BLOB) -> Result<Vec<u8>, std::io::Error> {
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let data = unsafe { std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec() };
+        unsafe { LocalFree(output.pbData as _) };
+        Ok(data)
+    }
+}
+
+#[cfg(not(windows))]
+mod user_data_protection {
+    pub fn protect(_data: &[u8]) -> Result<Vec<u8>, String> {
+        Err("User data protection is only available on Windows".to_string())
+    }
+
+    pub fn unprotect(_data: &[u8]) -> Result<Vec<u8>, String> {
+        Err("User data protection is only available on Windows".to_string())
+    }
+}
+
+// The installation's key store, opened on first use
+static KEY_STORE: Lazy<Mutex<Option<KeyStore>>> = Lazy::new(|| Mutex::new(None));
+
+fn with_key_store<T>(f: impl FnOnce(&mut KeyStore) -> Result<T, String>) -> Result<T, String> {
+    let mut key_store = KEY_STORE.lock().unwrap();
+    if key_store.is_none() {
+        let (log_dir, _) = get_platform_directories().map_err(|e| e.to_string())?;
+        let mut opened = KeyStore::open_or_create(&log_dir).map_err(|e| format!("Failed to open key store: {}", e))?;
+        opened.installation = true;
+        opened.protect_plain_keys();
+        *key_store = Some(opened);
+    }
+    f(key_store.as_mut().unwrap())
+}
+
+/// Use `store` for the rest of the process instead of the one next to the activity logs
+pub fn install_key_store(store: KeyStore) {
+    *KEY_STORE.lock().unwrap() = Some(store);
+}
+
+/// Keys for reading and writing activity logs; fails while a passphrase-protected store is locked
+pub fn active_keyring() -> Result<KeyRing, String> {
+    with_key_store(|store| {
+        store
+            .keyring()
+            .cloned()
+            .ok_or_else(|| "Activity log keys are locked".to_string())
+    })
+}
+
+pub fn key_status() -> Result<KeyStatus, String> {
+    with_key_store(|store| Ok(store.status()))
+}
+
+pub fn unlock_activity_keys(passphrase: &str) -> Result<(), String> {
+    with_key_store(|store| store.unlock(Some(passphrase)))
+}
+
+pub fn set_key_protection(protection: KeyProtection, passphrase: Option<&str>) -> Result<(), String> {
+    with_key_store(|store| store.set_protection(protection, passphrase))
+}
+
+/// Rotate to a new key, re-encrypting the daily logs and their backups
+pub fn rotate_activity_key() -> Result<KeyRotationReport, String> {
+    let (log_dir, backup_dir) = get_platform_directories().map_err(|e| e.to_string())?;
+    // Hold the log closed so no record is appended with the old key mid-rotation
+    let report = with_activity_log_closed(|| with_key_store(|store| store.rotate(&[&log_dir, &backup_dir])))?;
+    crate::log_info!(
+        "key_manager",
+        "Rotated activity log key {} -> {}: {} files re-encrypted, {} failed",
+        report.previous_key_id, report.new_key_id, report.files_reencrypted, report.failed_files.len()
+    );
+    Ok(report)
+}
+
+/// The installation's report signing key, kept in the key store with the log keys
+pub fn load_or_create_signing_key() -> Result<Ed25519KeyPair, String> {
+    with_key_store(|store| store.signing_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    #[test]
+    fn test_first_run_generates_key() {
+        let dir = TestDir::new("keys-first-run");
+        let store = KeyStore::open_or_create(&dir).unwrap();
+        let keyring = store.keyring().unwrap().clone();
+        assert_eq!(keyring.current_id(), 1);
+        assert_eq!(keyring.key(LEGACY_KEY_ID), Some(&LEGACY_KEY));
+        assert_ne!(keyring.key(1), Some(&LEGACY_KEY));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(KEY_STORE_FILE_NAME)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let reopened = KeyStore::open_or_create(&dir).unwrap();
+        assert_eq!(reopened.keyring().unwrap().key(1), keyring.key(1));
+    }
+
+    #[test]
+    fn test_passphrase_protection() {
+        let dir = TestDir::new("keys-passphrase");
+        let mut store = KeyStore::open_or_create(&dir).unwrap();
+        let key = *store.keyring().unwrap().key(1).unwrap();
+        store.set_protection(KeyProtection::Passphrase, Some("correct horse")).unwrap();
+
+        let mut reopened = KeyStore::open_or_create(&dir).unwrap();
+        assert!(reopened.status().locked);
+        assert!(reopened.unlock(Some("wrong")).is_err());
+        reopened.unlock(Some("correct horse")).unwrap();
+        assert_eq!(reopened.keyring().unwrap().key(1), Some(&key));
+    }
+
+    #[test]
+    fn test_rotation_reencrypts_logs() {
+        use crate::activity_monitor::records::{encode_legacy_frame, read_log_file, ActivityRecord, LogHeader};
+
+        let dir = TestDir::new("keys-rotation");
+        let mut store = KeyStore::open_or_create(&dir).unwrap();
+
+        // A legacy log written with the built-in key
+        let log_path = dir.join("rs-fairsight(2025-01-01).txt");
+        fs::write(&log_path, encode_legacy_frame("Active time 200 - 100\n", &LEGACY_KEY)).unwrap();
+
+        let report = store.rotate(&[&dir]).unwrap();
+        assert_eq!((report.previous_key_id, report.new_key_id), (1, 2));
+        assert_eq!(report.files_reencrypted, 1);
+
+        let keyring = store.keyring().unwrap();
+        assert_eq!(keyring.current_id(), 2);
+        let contents = read_log_file(&log_path, keyring).unwrap();
+        assert_eq!(contents.header, Some(LogHeader::current(2)));
+        assert_eq!(contents.records, vec![ActivityRecord::Active { start: 100, end: 200 }]);
+        assert!(read_log_file(&log_path, &KeyRing::legacy()).is_err());
+    }
+
+    #[test]
+    fn test_signing_key_is_sealed_with_the_log_keys() {
+        let dir = TestDir::new("keys-signing");
+        let legacy = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let legacy_public = Ed25519KeyPair::from_pkcs8(legacy.as_ref()).unwrap().public_key().as_ref().to_vec();
+        fs::write(dir.join(LEGACY_SIGNING_KEY_FILE_NAME), legacy.as_ref()).unwrap();
+
+        // A copy can use the key file it came with but never stores or generates one
+        let mut copy = KeyStore::open_or_create(&dir).unwrap();
+        assert_eq!(copy.signing_key().unwrap().public_key().as_ref(), legacy_public.as_slice());
+        fs::remove_file(dir.join(LEGACY_SIGNING_KEY_FILE_NAME)).unwrap();
+        assert!(copy.signing_key().is_err());
+        fs::write(dir.join(LEGACY_SIGNING_KEY_FILE_NAME), legacy.as_ref()).unwrap();
+
+        // The installation takes the old key over, so earlier reports still verify
+        let mut store = KeyStore::open(&dir).unwrap();
+        store.installation = true;
+        assert_eq!(store.signing_key().unwrap().public_key().as_ref(), legacy_public.as_slice());
+        assert!(!dir.join(LEGACY_SIGNING_KEY_FILE_NAME).exists());
+        store.set_protection(KeyProtection::Passphrase, Some("correct horse")).unwrap();
+
+        let mut reopened = KeyStore::open(&dir).unwrap();
+        assert!(reopened.signing_key().is_err());
+        reopened.unlock(Some("correct horse")).unwrap();
+        assert_eq!(reopened.signing_key().unwrap().public_key().as_ref(), legacy_public.as_slice());
+    }
+}
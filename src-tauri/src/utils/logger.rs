@@ -1,4 +1,5 @@
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::VecDeque;
 use chrono::Local;
 use serde::{Deserialize, Serialize};
@@ -39,6 +40,13 @@ static GLOBAL_LOGGER: Lazy<Mutex<VecDeque<LogEntry>>> = Lazy::new(|| {
     Mutex::new(VecDeque::with_capacity(MAX_LOG_ENTRIES))
 });
 
+// The command-line tool keeps stdout for its own output
+static CONSOLE_OUTPUT: AtomicBool = AtomicBool::new(true);
+
+pub fn set_console_output(enabled: bool) {
+    CONSOLE_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
 pub fn log_message(level: LogLevel, module: &str, message: &str) {
     let entry = LogEntry {
         timestamp: Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
@@ -48,7 +56,9 @@ pub fn log_message(level: LogLevel, module: &str, message: &str) {
     };
 
     // Print to console as well
-    println!("[{}] [{}] {}: {}", entry.timestamp, entry.level, entry.module, entry.message);
+    if CONSOLE_OUTPUT.load(Ordering::Relaxed) {
+        println!("[{}] [{}] {}: {}", entry.timestamp, entry.level, entry.module, entry.message);
+    }
 
     let mut logger = GLOBAL_LOGGER.lock().unwrap();
     
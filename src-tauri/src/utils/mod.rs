@@ -6,10 +6,13 @@ pub mod encryption;
 pub mod file_utils;
 pub mod health_monitor;
 pub mod hooks;
+pub mod key_manager;
 pub mod logger;
 pub mod macos_utils;
 pub mod ui_setup;
 pub mod web_server;
+#[cfg(test)]
+pub mod test_dir;
 
 pub fn run() {
     println!("Running rust_fairsight_lib..."); // Non-Tauri background task
@@ -11,6 +11,7 @@ use crate::macos_utils::{set_activation_policy, activate_app};
 #[cfg(target_os = "macos")]
 use cocoa::appkit::NSApplicationActivationPolicy;
 
+use crate::activity_monitor::core::record_shutdown;
 use crate::utils::app_state::set_app_handle;
 
 pub fn setup_tray_and_window_events(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
@@ -101,6 +102,9 @@ pub fn setup_tray_and_window_events(app: &mut tauri::App) -> Result<(), Box<dyn
         .on_menu_event(|app, event| {
             match event.id().as_ref() {
                 "quit" => {
+                    if let Err(e) = record_shutdown() {
+                        crate::log_error!("ui_setup", "Failed to record shutdown: {}", e);
+                    }
                     std::process::exit(0);
                 }
                 "hide" => {
@@ -1,8 +1,8 @@
 use axum::{
-    extract::Query,
-    http::StatusCode,
+    extract::{ConnectInfo, Path, Query},
+    http::{header, Method, StatusCode},
     response::{IntoResponse, Json},
-    routing::get,
+    routing::{delete, get},
     Router,
 };
 use serde::Deserialize;
@@ -12,7 +12,16 @@ use axum::serve;
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
 
-use crate::utils::commands::aggregate_week_activity_logs;
+use crate::activity_monitor::calendar::CalendarDay;
+use crate::activity_monitor::schedule::WorkTimeReport;
+use crate::activity_monitor::export::{ExportFormat, Rounding, RoundingMode};
+use crate::activity_monitor::tags::TimeTag;
+use crate::activity_monitor::types::{ActivityAggregate, ActivityHeatmap, TagTotal};
+use crate::utils::commands::{
+    add_activity_tag, aggregate_week_activity_logs, export_activity_content, get_activity_aggregate,
+    get_activity_heatmap, get_activity_tag_totals, get_activity_tags, get_calendar_days, get_work_time,
+    remove_activity_tag, MAX_DATE_RANGE_DAYS,
+};
 
 // Define a struct to hold the query parameters
 #[derive(Deserialize, Debug)]
@@ -21,11 +30,65 @@ struct DateRangeQuery {
     start_date: String,
     #[serde(rename = "endDate")]
     end_date: String,
+    // IANA timezone to report in, e.g. "Europe/Berlin"; defaults to the zone the logs were recorded in
+    timezone: Option<String>,
+}
+
+// Query parameters of GET /activity
+#[derive(Deserialize, Debug)]
+struct ActivityQuery {
+    #[serde(rename = "startDate")]
+    start_date: String,
+    #[serde(rename = "endDate")]
+    end_date: String,
+    timezone: Option<String>,
+    // Also return each day in the text format of /aggregate
+    #[serde(rename = "includeText")]
+    include_text: Option<bool>,
+}
+
+// Query parameters of GET /heatmap
+#[derive(Deserialize, Debug)]
+struct HeatmapQuery {
+    #[serde(rename = "startDate")]
+    start_date: String,
+    #[serde(rename = "endDate")]
+    end_date: String,
+    timezone: Option<String>,
+    // Width of a column in minutes; hourly if not given
+    #[serde(rename = "bucketMinutes")]
+    bucket_minutes: Option<u32>,
+}
+
+// Query parameters of GET /export/{format}
+#[derive(Deserialize, Debug)]
+struct ExportQuery {
+    #[serde(rename = "startDate")]
+    start_date: String,
+    #[serde(rename = "endDate")]
+    end_date: String,
+    timezone: Option<String>,
+    // Round interval boundaries to this many minutes
+    #[serde(rename = "roundMinutes")]
+    round_minutes: Option<u32>,
+    #[serde(rename = "roundMode")]
+    round_mode: Option<RoundingMode>,
+}
+
+// Body of a POST /tags request
+#[derive(Deserialize, Debug)]
+struct NewTagBody {
+    start: u64,
+    end: u64,
+    project: Option<String>,
+    task: Option<String>,
+    note: Option<String>,
 }
 
 // Custom error type for the Axum handler
 enum AppError {
     BadRequest(String),
+    Forbidden(String),
     InternalServerError(String),
 }
 
@@ -34,6 +97,7 @@ impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         let (status, error_message) = match self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             AppError::InternalServerError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
         };
 
@@ -67,6 +131,9 @@ async fn aggregate_handler(
     if start_date > end_date {
         return Err(AppError::BadRequest("startDate cannot be after endDate.".to_string()));
     }
+    if (end_date - start_date).num_days() >= MAX_DATE_RANGE_DAYS {
+        return Err(AppError::BadRequest(format!("Date range cannot be longer than {} days.", MAX_DATE_RANGE_DAYS)));
+    }
 
     // 2. Generate list of dates in the range (inclusive)
     let mut dates_to_process = Vec::new();
@@ -88,20 +155,130 @@ async fn aggregate_handler(
         .map(|date| format!("{}", date.format("%Y-%m-%d")))
         .collect();
 
-    let results = aggregate_week_activity_logs(filenames);
+    let results = aggregate_week_activity_logs(filenames, params.timezone)
+        .map_err(AppError::BadRequest)?;
 
     Ok(Json(results))
 }
 
+async fn activity_handler(Query(params): Query<ActivityQuery>) -> Result<Json<ActivityAggregate>, AppError> {
+    get_activity_aggregate(params.start_date, params.end_date, params.timezone, params.include_text)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+async fn heatmap_handler(Query(params): Query<HeatmapQuery>) -> Result<Json<ActivityHeatmap>, AppError> {
+    get_activity_heatmap(params.start_date, params.end_date, params.timezone, params.bucket_minutes)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+async fn work_time_handler(Query(params): Query<DateRangeQuery>) -> Result<Json<WorkTimeReport>, AppError> {
+    get_work_time(params.start_date, params.end_date, params.timezone)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+async fn calendar_handler(Query(params): Query<DateRangeQuery>) -> Result<Json<Vec<CalendarDay>>, AppError> {
+    get_calendar_days(params.start_date, params.end_date)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+// Tags carry free-text notes, so like changes they are only served to this machine
+async fn list_tags_handler(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Query(params): Query<DateRangeQuery>,
+) -> Result<Json<Vec<TimeTag>>, AppError> {
+    require_loopback(peer)?;
+    get_activity_tags(params.start_date, params.end_date)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+// The server listens on the network for read-only reports; tags are only read and changed from this machine
+fn require_loopback(peer: SocketAddr) -> Result<(), AppError> {
+    if peer.ip().is_loopback() {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("Tags can only be accessed from this machine.".to_string()))
+    }
+}
+
+async fn add_tag_handler(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(body): Json<NewTagBody>,
+) -> Result<Json<TimeTag>, AppError> {
+    require_loopback(peer)?;
+    add_activity_tag(body.start, body.end, body.project, body.task, body.note)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+async fn remove_tag_handler(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+) -> Result<Json<String>, AppError> {
+    require_loopback(peer)?;
+    remove_activity_tag(id)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+async fn tag_totals_handler(Query(params): Query<DateRangeQuery>) -> Result<Json<Vec<TagTotal>>, AppError> {
+    get_activity_tag_totals(params.start_date, params.end_date)
+        .map(Json)
+        .map_err(AppError::BadRequest)
+}
+
+async fn export_handler(
+    Path(format): Path<ExportFormat>,
+    Query(params): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let rounding = params.round_minutes.map(|increment_minutes| Rounding {
+        increment_minutes,
+        mode: params.round_mode.unwrap_or(RoundingMode::Nearest),
+    });
+    let content = export_activity_content(
+        &params.start_date,
+        &params.end_date,
+        format,
+        params.timezone.as_deref(),
+        rounding,
+    )
+    .map_err(AppError::BadRequest)?;
+
+    let disposition = format!(
+        "attachment; filename=\"activity-{}-to-{}.{}\"",
+        params.start_date,
+        params.end_date,
+        format.extension()
+    );
+    let headers = [
+        (header::CONTENT_TYPE, format.content_type().to_string()),
+        (header::CONTENT_DISPOSITION, disposition),
+    ];
+    Ok((headers, content))
+}
+
 pub async fn start_web_server() {
+    // Browsers may read reports from any page but not send the requests that change tags
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods(Any)
+        .allow_methods([Method::GET])
         .allow_headers(Any);
 
     let app = Router::new()
         .route("/", get(|| async { "Server is running" }))
         .route("/aggregate", get(aggregate_handler))
+        .route("/activity", get(activity_handler))
+        .route("/heatmap", get(heatmap_handler))
+        .route("/worktime", get(work_time_handler))
+        .route("/calendar", get(calendar_handler))
+        .route("/tags", get(list_tags_handler).post(add_tag_handler))
+        .route("/tags/totals", get(tag_totals_handler))
+        .route("/tags/{id}", delete(remove_tag_handler))
+        .route("/export/{format}", get(export_handler))
         .layer(cors);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 7930));
@@ -119,7 +296,7 @@ pub async fn start_web_server() {
     };
 
     println!("Server is now listening for connections...");
-    if let Err(e) = serve(listener, app.into_make_service()).await {
+    if let Err(e) = serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await {
         eprintln!("Server error: {}", e);
     }
 }
@@ -71,10 +71,12 @@ export const useNetworkMonitoring = (adapters) => {
 	useEffect(() => {
 		const checkShutdown = async () => {
 			try {
-				const wasUnexpected = await invoke('check_unexpected_shutdown');
-				setUnexpectedShutdown(wasUnexpected);
-				if (wasUnexpected) {
-					console.warn('⚠️  Previous session ended unexpectedly - some data may have been lost');
+				const check = await invoke('check_unexpected_shutdown');
+				setUnexpectedShutdown(check.unexpected);
+				if (check.unexpected) {
+					const run = check.previous_run;
+					const when = run && !run.clean ? ` (${run.ending.replace('_', ' ')} on ${run.date} at ${run.time})` : '';
+					console.warn(`⚠️  Previous session ended unexpectedly${when} - some data may have been lost`);
 				}
 			} catch (err) {
 				console.error('Failed to check shutdown state:', err);
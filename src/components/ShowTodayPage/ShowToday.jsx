@@ -2,7 +2,7 @@ import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { PieChart } from '@mui/x-charts/PieChart';
 import { Button, Card, CardContent, Typography, Box } from "@mui/material";
-import { ACTIVE_COLOR, INACTIVE_COLOR, NOTRUN_COLOR } from '../../utils/colorSetting';
+import { ACTIVE_COLOR, CLOCK_ADJUSTED_COLOR, DAY_OFF_COLOR, INACTIVE_COLOR, NOTRUN_COLOR, SUSPENDED_COLOR } from '../../utils/colorSetting';
 import AnimatedContainer from '../shared/AnimatedContainer';
 import { useAnimations } from '../../utils/fantasyAnimations';
 import FantasyCard from '../shared/FantasyCard';
@@ -87,7 +87,8 @@ const ShowTodayCard = () => {
             });
             const duration = end - start;
 
-            if (state === "Not run") {
+            // Time the app was closed, crashed, the machine was off or the reason is unknown counts as not run
+            if (["Not run", "Powered off", "App closed", "Crashed", "Unknown"].includes(state)) {
                 colorSlot.push(NOTRUN_COLOR);
                 percentSlot.push(duration / 864000);
                 notRunDuration += duration;
@@ -99,6 +100,16 @@ const ShowTodayCard = () => {
                 colorSlot.push(ACTIVE_COLOR);
                 percentSlot.push(duration / 864000);
                 activeDuration += duration;
+            } else if (state === "Suspended" || state === "Clock adjusted") {
+                // Sleep and clock changes have their own colour in the bar and count as not tracked
+                colorSlot.push(state === "Suspended" ? SUSPENDED_COLOR : CLOCK_ADJUSTED_COLOR);
+                percentSlot.push(duration / 864000);
+                notRunDuration += duration;
+            } else if (state === "Day off") {
+                // Untracked time on a day off, shown apart from time the app should have run
+                colorSlot.push(DAY_OFF_COLOR);
+                percentSlot.push(duration / 864000);
+                notRunDuration += duration;
             }
         }
 
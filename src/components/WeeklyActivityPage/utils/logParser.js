@@ -55,16 +55,20 @@ export const parseLog = (log) => {
 
 			if (state === "Active") activeDuration += duration;
 			else if (state === "Inactive") inactiveDuration += duration;
-			// Remove "Not run" parsing from here - it should be calculated
+			// Sleep and clock changes count as not run, like the time the tracker was stopped
+			else if (state === "Suspended" || state === "Clock adjusted") notRunDuration += duration;
+			// Untracked time on a day off is not run either
+			else if (state === "Day off") notRunDuration += duration;
+			// Other "Not run" lines are covered by the rest of the day, calculated below
 		} catch (e) {
 			console.error("Error parsing log line:", lines[i], e);
 		}
 	}
 
-	// Calculate notRunDuration: total seconds in a day minus active and inactive
+	// Add the rest of the day: total seconds in a day minus everything parsed above
 	const totalSecondsInDay = 24 * 60 * 60;
 	// Ensure calculated notRun isn't negative due to small parsing errors or overlap
-	notRunDuration = Math.max(0, totalSecondsInDay - activeDuration - inactiveDuration);
+	notRunDuration += Math.max(0, totalSecondsInDay - activeDuration - inactiveDuration - notRunDuration);
 
 	// Optional: Sanity check if total duration exceeds a day
 	if (activeDuration + inactiveDuration + notRunDuration > totalSecondsInDay + 60) { // Allow 1 min buffer for rounding
@@ -1,3 +1,6 @@
 export const ACTIVE_COLOR = '#50b8e7';
 export const INACTIVE_COLOR = '#FF746C';
-export const NOTRUN_COLOR = '#232b2b';
\ No newline at end of file
+export const NOTRUN_COLOR = '#232b2b';
+export const SUSPENDED_COLOR = '#6c7a89';
+export const CLOCK_ADJUSTED_COLOR = '#b39ddb';
+export const DAY_OFF_COLOR = '#81c784';
\ No newline at end of file